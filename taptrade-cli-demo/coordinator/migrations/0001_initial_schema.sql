@@ -0,0 +1,101 @@
+-- baseline schema of the coordinator database. Uses IF NOT EXISTS so databases created
+-- before the schema was versioned are adopted without changes.
+
+-- requests of makers awaiting submission of their bond
+CREATE TABLE IF NOT EXISTS maker_requests (
+	robohash BLOB PRIMARY KEY,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	escrow_locking_input_amount_without_trade_sum INTEGER NOT NULL,
+	request_created_ts INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
+
+-- offers that are active in the orderbook awaiting a taker
+CREATE TABLE IF NOT EXISTS active_maker_offers (
+	offer_id TEXT PRIMARY KEY,
+	robohash BLOB,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	escrow_locking_input_amount_without_trade_sum INTEGER,
+	bond_tx_hex TEXT NOT NULL,
+	payout_address TEXT NOT NULL,
+	change_address_maker TEXT NOT NULL,
+	escrow_inputs_hex_maker_csv TEXT NOT NULL,
+	taproot_pubkey_hex_maker TEXT NOT NULL,
+	musig_pub_nonce_hex TEXT NOT NULL,
+	musig_pubkey_hex TEXT NOT NULL,
+	taker_bond_address TEXT
+);
+
+-- offers that are taken and are in the trade process,
+-- escrow_psbt_is_confirmed will be set 1 once the escrow psbt is confirmed onchain
+CREATE TABLE IF NOT EXISTS taken_offers (
+	offer_id TEXT PRIMARY KEY,
+	robohash_maker BLOB,
+	robohash_taker BLOB,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address_maker TEXT NOT NULL,
+	bond_address_taker TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	bond_tx_hex_maker TEXT NOT NULL,
+	bond_tx_hex_taker TEXT NOT NULL,
+	payout_address_maker TEXT NOT NULL,
+	taproot_xonly_pubkey_hex_maker TEXT NOT NULL,
+	payout_address_taker TEXT NOT NULL,
+	taproot_xonly_pubkey_hex_taker TEXT NOT NULL,
+	musig_pub_nonce_hex_maker TEXT NOT NULL,
+	musig_pubkey_compressed_hex_maker TEXT NOT NULL,
+	musig_pub_nonce_hex_taker TEXT NOT NULL,
+	musig_pubkey_compressed_hex_taker TEXT NOT NULL,
+	musig_partial_sig_hex_maker TEXT,
+	musig_partial_sig_hex_taker TEXT,
+	escrow_psbt_hex TEXT NOT NULL,
+	escrow_psbt_txid TEXT NOT NULL,
+	signed_escrow_psbt_hex_maker TEXT,
+	signed_escrow_psbt_hex_taker TEXT,
+	escrow_psbt_is_confirmed INTEGER,
+	maker_happy INTEGER,
+	taker_happy INTEGER,
+	escrow_winner_robohash TEXT,
+	escrow_taproot_pk_coordinator TEXT,
+	escrow_amount_maker_sat INTEGER,
+	escrow_amount_taker_sat INTEGER,
+	escrow_fee_per_participant INTEGER,
+	escrow_output_descriptor TEXT,
+	escrow_inputs_hex_maker_csv TEXT,
+	escrow_inputs_hex_taker_csv TEXT,
+	payout_transaction_psbt_hex TEXT,
+	script_payout_psbt_hex TEXT,
+	invalid_partial_sigs_maker INTEGER NOT NULL DEFAULT 0,
+	invalid_partial_sigs_taker INTEGER NOT NULL DEFAULT 0,
+	trade_aborted_by_robohash TEXT,
+	trade_state TEXT NOT NULL
+);
+
+-- bonds published as punishment, they are monitored until they confirm
+CREATE TABLE IF NOT EXISTS published_bonds (
+	bond_txid TEXT PRIMARY KEY,
+	offer_id TEXT NOT NULL,
+	robohash TEXT NOT NULL,
+	bond_tx_hex TEXT NOT NULL,
+	bond_address TEXT NOT NULL,
+	cpfp_tx_hex TEXT,
+	cpfp_feerate_sat_vb REAL,
+	cpfp_block_height INTEGER,
+	status TEXT NOT NULL,
+	compensation_address TEXT,
+	compensation_txid TEXT,
+	compensation_amount_sat INTEGER,
+	published_ts INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
@@ -0,0 +1,15 @@
+-- the escrow inputs of the traders are stored as json array of EscrowInput (taptrade-protocol)
+-- instead of bincode serialized, hex encoded csv.
+ALTER TABLE active_maker_offers RENAME COLUMN escrow_inputs_hex_maker_csv TO escrow_inputs_maker_json;
+ALTER TABLE taken_offers RENAME COLUMN escrow_inputs_hex_maker_csv TO escrow_inputs_maker_json;
+ALTER TABLE taken_offers RENAME COLUMN escrow_inputs_hex_taker_csv TO escrow_inputs_taker_json;
+
+-- offers created by protocol version 1 traders still contain the csv encoding. Offers in the orderbook
+-- and trades still waiting for the signed escrow psbts need the inputs and can't be continued.
+DELETE FROM active_maker_offers WHERE escrow_inputs_maker_json NOT LIKE '[%';
+DELETE FROM taken_offers
+WHERE (signed_escrow_psbt_hex_maker IS NULL OR signed_escrow_psbt_hex_taker IS NULL)
+	AND (escrow_inputs_maker_json NOT LIKE '[%' OR escrow_inputs_taker_json NOT LIKE '[%');
+-- trades with both signed escrow psbts don't use the inputs anymore
+UPDATE taken_offers SET escrow_inputs_maker_json = NULL WHERE escrow_inputs_maker_json NOT LIKE '[%';
+UPDATE taken_offers SET escrow_inputs_taker_json = NULL WHERE escrow_inputs_taker_json NOT LIKE '[%';
@@ -1,66 +1,49 @@
 use super::*;
-/// Validates the timestamp of an offer duration.
-///
-/// This function takes an offer duration timestamp as input and validates it against the current time.
-/// It checks if the offer duration is within a valid range, which is between the current time plus 3 hours
-/// and the current time plus 7 days. If the offer duration is too short or too long, it returns a validation error.
-///
-/// # Arguments
-///
-/// * `offer_duration_ts` - The offer duration timestamp to validate.
-///
-/// # Returns
-///
-/// * `Result<(), ValidationError>` - An empty result if the offer duration is valid, or a validation error if it is not.
-///
-/// # Example
-///
-/// ```
-/// use coordinator::communication::communication_utils::validate_timestamp;
-/// use std::time::{SystemTime, UNIX_EPOCH};
-///
-/// // Get the current time
-/// let now = SystemTime::now();
-/// // Convert the current time to a UNIX timestamp
-/// let unix_timestamp = now
-///     .duration_since(UNIX_EPOCH)
-///     .expect("Time went backwards")
-///     .as_secs();
-///
-/// // Use a timestamp that is within the valid range (current time + 4 hours)
-/// let offer_duration_ts = unix_timestamp + 4 * 3600;
-/// let result = validate_timestamp(offer_duration_ts);
-/// assert!(result.is_ok());
-/// ```
-///
-/// # Errors
-///
-/// This function can return the following errors:
-///
-/// * `ValidationError` - If the offer duration is too short or too long.
-///
-/// # Panics
-///
-/// This function may panic if the system time goes backwards during the calculation of the current time.
-///
-/// # Safety
-///
-/// This function is safe to use.
-pub fn validate_timestamp(offer_duration_ts: u64) -> Result<(), ValidationError> {
-	// Get the current time
-	let now = SystemTime::now();
-	// Convert the current time to a UNIX timestamp
-	let unix_timestamp = now
-		.duration_since(UNIX_EPOCH)
-		.expect("Time went backwards")
-		.as_secs();
-	if offer_duration_ts < unix_timestamp + 10800 {
-		return Err(ValidationError::new("Offer duration too short"));
+use axum::{
+	extract::{Request, State},
+	middleware::Next,
+};
+
+/// handshake check of the network passed by the trader, coordinator and trader have to run on the same network.
+/// Returns the reason sent to the trader on mismatch.
+pub fn check_trader_network(
+	coordinator_network: Network,
+	trader_network: &str,
+) -> Result<(), String> {
+	match Network::from_str(trader_network) {
+		Ok(network) if network == coordinator_network => Ok(()),
+		_ => Err(format!(
+			"Network mismatch: coordinator runs on {}, trader on {}",
+			coordinator_network, trader_network
+		)),
 	}
-	if offer_duration_ts > unix_timestamp + 604800 {
-		return Err(ValidationError::new("Offer duration too long"));
+}
+
+/// middleware rejecting all requests of traders on another network than the coordinator,
+/// the trader sends its network in the NETWORK_HEADER with every request
+pub async fn check_network_header(
+	State(coordinator_network): State<Network>,
+	request: Request,
+	next: Next,
+) -> Response {
+	match request
+		.headers()
+		.get(NETWORK_HEADER)
+		.and_then(|network| network.to_str().ok())
+	{
+		Some(trader_network) => {
+			if let Err(e) = check_trader_network(coordinator_network, trader_network) {
+				return error_response(ErrorResponse::new(ErrorCode::NetworkMismatch, e));
+			}
+		}
+		None => {
+			return error_response(ErrorResponse::new(
+				ErrorCode::InvalidRequest,
+				format!("Missing {} header", NETWORK_HEADER),
+			))
+		}
 	}
-	Ok(())
+	next.run(request).await
 }
 
 // ANYHOW ERROR HANDLING
@@ -72,11 +55,10 @@ pub struct AppError(anyhow::Error);
 // Tell axum how to convert `AppError` into a response.
 impl IntoResponse for AppError {
 	fn into_response(self) -> Response {
-		(
-			StatusCode::INTERNAL_SERVER_ERROR,
+		error_response(ErrorResponse::new(
+			ErrorCode::Internal,
 			format!("Something went wrong: {}", self.0),
-		)
-			.into_response()
+		))
 	}
 }
 
@@ -1,22 +1,79 @@
+use super::*;
+
+/// responds with the json ErrorResponse body and the http status of its error code
+pub fn error_response(error: ErrorResponse) -> Response {
+	let status_code =
+		StatusCode::from_u16(error.code.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+	if status_code.is_server_error() {
+		error!("{:?} error response: {}", error.code, error.message);
+	} else {
+		info!("{:?} error response: {}", error.code, error.message);
+	}
+	(status_code, Json(error)).into_response()
+}
+
 #[derive(Debug)]
 pub enum BondError {
 	InvalidBond(String),
+	InvalidEscrowInputs(String),
 	BondNotFound,
 	CoordinatorError(String),
 }
 
+impl From<BondError> for ErrorResponse {
+	fn from(error: BondError) -> Self {
+		match error {
+			BondError::InvalidBond(e) => ErrorResponse::new(ErrorCode::InvalidBond, e),
+			BondError::InvalidEscrowInputs(e) => {
+				ErrorResponse::new(ErrorCode::InvalidEscrowInputs, e)
+			}
+			BondError::BondNotFound => {
+				ErrorResponse::new(ErrorCode::BondNotFound, "Bond requirements not found")
+			}
+			BondError::CoordinatorError(e) => ErrorResponse::new(ErrorCode::Internal, e),
+		}
+	}
+}
+
 #[derive(Debug)]
 pub enum FetchOffersError {
 	NoOffersAvailable,
 	Database(String),
 }
 
+impl IntoResponse for FetchOffersError {
+	fn into_response(self) -> Response {
+		match self {
+			// not an error, there is just nothing to return (yet)
+			FetchOffersError::NoOffersAvailable => StatusCode::NO_CONTENT.into_response(),
+			FetchOffersError::Database(e) => {
+				error_response(ErrorResponse::new(ErrorCode::Database, e))
+			}
+		}
+	}
+}
+
 #[derive(Debug)]
 pub enum FetchEscrowConfirmationError {
 	NotFound,
+	TradeAborted(String),
 	Database(String),
 }
 
+impl From<FetchEscrowConfirmationError> for ErrorResponse {
+	fn from(error: FetchEscrowConfirmationError) -> Self {
+		match error {
+			FetchEscrowConfirmationError::NotFound => {
+				ErrorResponse::new(ErrorCode::NotFound, "Trade not found")
+			}
+			FetchEscrowConfirmationError::TradeAborted(e) => {
+				ErrorResponse::new(ErrorCode::TradeAborted, e)
+			}
+			FetchEscrowConfirmationError::Database(e) => ErrorResponse::new(ErrorCode::Database, e),
+		}
+	}
+}
+
 #[derive(Debug)]
 pub enum RequestError {
 	Database(String),
@@ -25,4 +82,54 @@ pub enum RequestError {
 	NotFound,
 	PsbtAlreadySubmitted,
 	PsbtInvalid(String),
+	PartialSigInvalid(String),
+	Unauthorized(String),
+	TradeAborted(String),
+	TradeStateConflict(String),
+}
+
+impl From<RequestError> for ErrorResponse {
+	fn from(error: RequestError) -> Self {
+		match error {
+			RequestError::Database(e) => ErrorResponse::new(ErrorCode::Database, e),
+			RequestError::NotConfirmed => ErrorResponse::new(
+				ErrorCode::EscrowNotConfirmed,
+				"Escrow transaction not confirmed yet",
+			),
+			RequestError::CoordinatorError(e) => ErrorResponse::new(ErrorCode::Internal, e),
+			RequestError::NotFound => ErrorResponse::new(ErrorCode::NotFound, "Trade not found"),
+			RequestError::PsbtAlreadySubmitted => ErrorResponse::new(
+				ErrorCode::PsbtAlreadySubmitted,
+				"Signed escrow psbt already submitted",
+			),
+			RequestError::PsbtInvalid(e) => ErrorResponse::new(ErrorCode::PsbtInvalid, e),
+			RequestError::PartialSigInvalid(e) => {
+				ErrorResponse::new(ErrorCode::PartialSigInvalid, e)
+			}
+			RequestError::Unauthorized(e) => ErrorResponse::new(ErrorCode::Unauthorized, e),
+			RequestError::TradeAborted(e) => ErrorResponse::new(ErrorCode::TradeAborted, e),
+			RequestError::TradeStateConflict(e) => {
+				ErrorResponse::new(ErrorCode::TradeStateConflict, e)
+			}
+		}
+	}
+}
+
+// the handler errors are returned as ErrorResponse json body
+impl IntoResponse for BondError {
+	fn into_response(self) -> Response {
+		error_response(self.into())
+	}
+}
+
+impl IntoResponse for FetchEscrowConfirmationError {
+	fn into_response(self) -> Response {
+		error_response(self.into())
+	}
+}
+
+impl IntoResponse for RequestError {
+	fn into_response(self) -> Response {
+		error_response(self.into())
+	}
 }
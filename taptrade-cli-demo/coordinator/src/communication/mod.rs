@@ -1,9 +1,12 @@
-pub mod api;
 pub mod communication_utils;
 pub mod handler_errors;
+pub mod signed_request;
 
-use self::communication_utils::*;
+use self::{communication_utils::*, signed_request::SignedRequest};
 use super::*;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures_util::Stream;
+use std::convert::Infallible;
 
 //
 // Axum handler functions
@@ -13,8 +16,11 @@ async fn receive_order(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
 	Json(offer): Json<OfferRequest>,
 ) -> Result<Response, AppError> {
-	if offer.validate().is_err() {
-		Ok(StatusCode::BAD_REQUEST.into_response())
+	if let Err(e) = offer.validate() {
+		Ok(error_response(ErrorResponse::new(
+			ErrorCode::InvalidRequest,
+			e.to_string(),
+		)))
 	} else {
 		let bond_requirements = process_order(coordinator, &offer).await?;
 		Ok(Json(bond_requirements).into_response())
@@ -30,18 +36,7 @@ async fn submit_maker_bond(
 
 	match handle_maker_bond(&payload, coordinator).await {
 		Ok(offer_activated_response) => Ok(Json(offer_activated_response).into_response()),
-		Err(BondError::BondNotFound) => {
-			info!("Bond requirements not found in database");
-			Ok(StatusCode::NOT_FOUND.into_response())
-		}
-		Err(BondError::InvalidBond(e)) => {
-			warn!("Invalid bond submission: {e}");
-			Ok(StatusCode::NOT_ACCEPTABLE.into_response())
-		}
-		Err(BondError::CoordinatorError(e)) => {
-			error!("Coordinator error on bond submission: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
@@ -51,14 +46,9 @@ async fn fetch_available_offers(
 	Json(payload): Json<OffersRequest>,
 ) -> Result<Response, AppError> {
 	debug!("\n\nReceived offer request: {:?}", payload);
-
 	match get_public_offers(&payload, coordinator).await {
 		Ok(offers) => Ok(Json(offers).into_response()),
-		Err(FetchOffersError::NoOffersAvailable) => Ok(StatusCode::NO_CONTENT.into_response()),
-		Err(FetchOffersError::Database(e)) => {
-			error!("Database error fetching offers: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
@@ -72,35 +62,20 @@ async fn submit_taker_bond(
 
 	match handle_taker_bond(&payload, coordinator).await {
 		Ok(offer_taken_response) => Ok(Json(offer_taken_response).into_response()),
-		Err(BondError::BondNotFound) => {
-			info!("Bond requirements not found in database");
-			Ok(StatusCode::NOT_FOUND.into_response())
-		}
-		Err(BondError::InvalidBond(e)) => {
-			warn!("Invalid bond submission: {e}");
-			Ok(StatusCode::NOT_ACCEPTABLE.into_response())
-		}
-		Err(BondError::CoordinatorError(e)) => {
-			error!("Coordinator error on bond submission: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
 /// gets polled by the maker and returns the escrow psbt in case the offer has been taken
 async fn request_offer_status_maker(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
-	Json(payload): Json<OfferTakenRequest>,
+	SignedRequest(payload): SignedRequest<OfferTakenRequest>,
 ) -> Result<Response, AppError> {
 	debug!("\n\nReceived offer status request: {:?}", payload);
 
 	match get_offer_status_maker(&payload, coordinator).await {
 		Ok(offer_taken_response) => Ok(Json(offer_taken_response).into_response()),
-		Err(FetchOffersError::NoOffersAvailable) => Ok(StatusCode::NO_CONTENT.into_response()),
-		Err(FetchOffersError::Database(e)) => {
-			error!("Database error fetching offer status maker: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
@@ -110,19 +85,12 @@ async fn request_offer_status_maker(
 /// Once the coordinator has received both partial signed PSBTs he can assemble them together to a transaction and publish it to the bitcoin network.
 async fn submit_escrow_psbt(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
-	Json(payload): Json<PsbtSubmissionRequest>,
+	SignedRequest(payload): SignedRequest<PsbtSubmissionRequest>,
 ) -> Result<Response, AppError> {
 	debug!("\n\nReceived signed escrow psbt: {:?}", payload);
 	match handle_signed_escrow_psbt(&payload, coordinator).await {
 		Ok(()) => Ok(StatusCode::OK.into_response()),
-		Err(RequestError::PsbtAlreadySubmitted) => {
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
-		Err(RequestError::PsbtInvalid(e)) => {
-			warn!("Invalid PSBT: {e}");
-			Ok(StatusCode::NOT_ACCEPTABLE.into_response())
-		}
-		_ => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
@@ -131,45 +99,23 @@ async fn submit_escrow_psbt(
 /// In theory this polling mechanism could also be replaced by the traders scanning the blockchain themself so they could also see once the tx is confirmed.
 async fn poll_escrow_confirmation(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
-	Json(payload): Json<OfferTakenRequest>,
+	SignedRequest(payload): SignedRequest<OfferTakenRequest>,
 ) -> Result<Response, AppError> {
 	match fetch_escrow_confirmation_status(&payload, coordinator).await {
 		Ok(true) => Ok(StatusCode::OK.into_response()),
 		Ok(false) => Ok(StatusCode::ACCEPTED.into_response()),
-		Err(FetchEscrowConfirmationError::NotFound) => {
-			info!("Escrow confirmation check transaction not found");
-			Ok(StatusCode::NOT_FOUND.into_response())
-		}
-		Err(FetchEscrowConfirmationError::Database(e)) => {
-			error!("Database error fetching escrow confirmation: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
 /// gets called if the trader is happy and does not want to initiate escrow
 async fn submit_obligation_confirmation(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
-	Json(payload): Json<OfferTakenRequest>,
+	SignedRequest(payload): SignedRequest<OfferTakenRequest>,
 ) -> Result<Response, AppError> {
 	match handle_obligation_confirmation(&payload, coordinator).await {
 		Ok(_) => Ok(StatusCode::OK.into_response()),
-		Err(RequestError::NotFound) => {
-			info!("Offer for obligation confirmation not found");
-			Ok(StatusCode::NOT_FOUND.into_response())
-		}
-		Err(RequestError::NotConfirmed) => {
-			info!("Offer for obligation confirmation not confirmed");
-			Ok(StatusCode::NOT_ACCEPTABLE.into_response())
-		}
-		Err(RequestError::Database(e)) => {
-			error!("Database error fetching obligation confirmation: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
-		_ => {
-			error!("Unknown error handling obligation confirmation");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
@@ -179,26 +125,11 @@ async fn submit_obligation_confirmation(
 /// before timeout ends, just sets the maker unhappy and escrow onging flag in the db
 async fn request_escrow(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
-	Json(payload): Json<TradeObligationsUnsatisfied>,
+	SignedRequest(payload): SignedRequest<TradeObligationsUnsatisfied>,
 ) -> Result<Response, AppError> {
 	match initiate_escrow(&payload, coordinator).await {
 		Ok(_) => Ok(StatusCode::OK.into_response()),
-		Err(RequestError::NotConfirmed) => {
-			info!("Offer tx for escrow initiation not confirmed");
-			Ok(StatusCode::NOT_ACCEPTABLE.into_response())
-		}
-		Err(RequestError::NotFound) => {
-			info!("Offer for escrow initiation not found");
-			Ok(StatusCode::NOT_FOUND.into_response())
-		}
-		Err(RequestError::Database(e)) => {
-			error!("Database error fetching obligation confirmation: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
-		_ => {
-			error!("Unknown error handling request_escrow()");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
@@ -208,35 +139,22 @@ async fn request_escrow(
 /// endpoint can return 201 and the escrow mediation logic will get executed (tbd).
 async fn poll_final_payout(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
-	Json(payload): Json<OfferTakenRequest>,
+	SignedRequest(payload): SignedRequest<OfferTakenRequest>,
 ) -> Result<Response, AppError> {
 	let response = match handle_final_payout(&payload, coordinator).await {
 		Ok(PayoutProcessingResult::NotReady) => Ok(StatusCode::ACCEPTED.into_response()),
-		Ok(PayoutProcessingResult::LostEscrow) => Ok(StatusCode::GONE.into_response()),
+		Ok(PayoutProcessingResult::LostEscrow) => Ok(error_response(ErrorResponse::new(
+			ErrorCode::EscrowLost,
+			"The escrow got awarded to the other trader",
+		))),
 		Ok(PayoutProcessingResult::ReadyPSBT(psbt_and_nonce)) => {
 			Ok(Json(psbt_and_nonce).into_response())
 		}
-		Ok(PayoutProcessingResult::DecidingEscrow) => Ok(StatusCode::CREATED.into_response()),
-		Err(RequestError::NotConfirmed) => {
-			info!("Offer tx for final payout not confirmed");
-			Ok(StatusCode::NOT_ACCEPTABLE.into_response())
-		}
-		Err(RequestError::NotFound) => {
-			info!("Offer for final payout not found");
-			Ok(StatusCode::NOT_FOUND.into_response())
-		}
-		Err(RequestError::Database(e)) => {
-			error!("Database error fetching final payout: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
-		Err(RequestError::CoordinatorError(e)) => {
-			error!("Coordinator error handling final payout: {e}");
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
-		e => {
-			error!("Unknown error handling poll_final_payout(): {:?}", e);
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+		Ok(PayoutProcessingResult::ReadyScriptPSBT(script_payout_psbt)) => {
+			Ok(Json(script_payout_psbt).into_response())
 		}
+		Ok(PayoutProcessingResult::DecidingEscrow) => Ok(StatusCode::CREATED.into_response()),
+		Err(e) => Ok(e.into_response()),
 	};
 	debug!("\nPayout response: {:?}", response);
 	response
@@ -245,7 +163,7 @@ async fn poll_final_payout(
 /// recieves the partial signature for the keyspend payout transaction
 async fn submit_payout_signature(
 	Extension(coordinator): Extension<Arc<Coordinator>>,
-	Json(payload): Json<PayoutSignatureRequest>,
+	SignedRequest(payload): SignedRequest<PayoutSignatureRequest>,
 ) -> Result<Response, AppError> {
 	match handle_payout_signature(&payload, coordinator).await {
 		// received both sigs, published final tx
@@ -254,42 +172,99 @@ async fn submit_payout_signature(
 		// this was the first signature
 		Ok(false) => Ok(StatusCode::ACCEPTED.into_response()),
 
-		e => {
-			error!("Unknown error handling submit_payout_signature(): {:?}", e);
-			Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
-		}
+		// e.g. the signature did not verify against the individual pubkey and nonce of the trader
+		Err(e) => Ok(e.into_response()),
+	}
+}
+
+/// receives the request of a maker to cancel its offer, removes it from the orderbook
+/// which also stops the monitoring of its bond
+async fn cancel_offer(
+	Extension(coordinator): Extension<Arc<Coordinator>>,
+	Json(payload): Json<OfferCancellationRequest>,
+) -> Result<Response, AppError> {
+	match handle_offer_cancellation(&payload, coordinator).await {
+		Ok(()) => Ok(StatusCode::OK.into_response()),
+		Err(e) => Ok(e.into_response()),
 	}
 }
 
+/// server sent events stream of a trade, pushes the progress of the trade (taken, escrow broadcast/confirmed,
+/// happiness, payout ready, dispute decided, aborted) so the trader only has to poll as fallback
+async fn subscribe_trade_events(
+	Extension(coordinator): Extension<Arc<Coordinator>>,
+	SignedRequest(payload): SignedRequest<OfferTakenRequest>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+	debug!(
+		"Trader subscribed to events of offer {}",
+		payload.offer_id_hex
+	);
+	Sse::new(coordinator.trade_events.subscribe(&payload.offer_id_hex))
+		.keep_alive(KeepAlive::default())
+}
+
+/// tells the trader which protocol version the coordinator speaks
+async fn add_protocol_version_header(mut response: Response) -> Response {
+	response.headers_mut().insert(
+		PROTOCOL_VERSION_HEADER,
+		axum::http::HeaderValue::from(PROTOCOL_VERSION),
+	);
+	response
+}
+
 /// testing endpoint
 async fn test_api() -> &'static str {
 	"Hello, World!"
 }
 
 pub async fn api_server(coordinator: Arc<Coordinator>) -> Result<()> {
+	let network = coordinator.coordinator_wallet.network;
 	let app = Router::new()
 		.route("/test", get(test_api))
-		.route("/create-offer", post(receive_order))
-		.route("/submit-maker-bond", post(submit_maker_bond))
-		.route("/fetch-available-offers", post(fetch_available_offers))
-		.route("/submit-taker-bond", post(submit_taker_bond))
-		.route("/request-offer-status", post(request_offer_status_maker))
-		.route("/submit-escrow-psbt", post(submit_escrow_psbt))
-		.route("/poll-escrow-confirmation", post(poll_escrow_confirmation))
+		.route(endpoints::CREATE_OFFER, post(receive_order))
+		.route(endpoints::SUBMIT_MAKER_BOND, post(submit_maker_bond))
+		.route(
+			endpoints::FETCH_AVAILABLE_OFFERS,
+			post(fetch_available_offers),
+		)
+		.route(endpoints::SUBMIT_TAKER_BOND, post(submit_taker_bond))
 		.route(
-			"/submit-obligation-confirmation",
+			endpoints::REQUEST_OFFER_STATUS,
+			post(request_offer_status_maker),
+		)
+		.route(endpoints::SUBMIT_ESCROW_PSBT, post(submit_escrow_psbt))
+		.route(
+			endpoints::POLL_ESCROW_CONFIRMATION,
+			post(poll_escrow_confirmation),
+		)
+		.route(
+			endpoints::SUBMIT_OBLIGATION_CONFIRMATION,
 			post(submit_obligation_confirmation),
 		)
-		.route("/request-escrow", post(request_escrow))
-		.route("/poll-final-payout", post(poll_final_payout))
-		.route("/submit-payout-signature", post(submit_payout_signature))
+		.route(endpoints::REQUEST_ESCROW, post(request_escrow))
+		.route(endpoints::POLL_FINAL_PAYOUT, post(poll_final_payout))
+		.route(
+			endpoints::SUBMIT_PAYOUT_SIGNATURE,
+			post(submit_payout_signature),
+		)
+		.route(endpoints::CANCEL_OFFER, post(cancel_offer))
+		.route(endpoints::TRADE_EVENTS, post(subscribe_trade_events))
+		// the coordinator refuses to talk to traders on another network
+		.layer(axum::middleware::from_fn_with_state(
+			network,
+			check_network_header,
+		))
+		.layer(axum::middleware::map_response(add_protocol_version_header))
 		.layer(Extension(coordinator));
 	// add other routes here
 
 	let port: u16 = env::var("PORT")
 		.unwrap_or_else(|_| "9999".to_string())
 		.parse()?;
-	info!("Coordinator is listening on port {}", port);
+	info!(
+		"Coordinator is listening on port {} (protocol version {})",
+		port, PROTOCOL_VERSION
+	);
 	let addr = SocketAddr::from(([127, 0, 0, 1], port));
 	let tcp = TcpListener::bind(&addr).await.unwrap();
 	axum::serve(tcp, app).await?;
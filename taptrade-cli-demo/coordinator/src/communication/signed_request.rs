@@ -0,0 +1,246 @@
+use super::*;
+use axum::{async_trait, body::Bytes, extract::FromRequest, extract::Request};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+
+/// signed requests older (or newer) than this are rejected, within this window every signature is only
+/// accepted once so captured requests can't be replayed
+const MAX_REQUEST_AGE_SECS: u64 = 300;
+
+/// requests of a trader acting on a trade, the trader is identified by the robohash and offer id
+/// and has to sign the request with the taproot key it registered with its bond
+pub trait TraderRequest {
+	fn offer_id_hex(&self) -> &str;
+	fn robohash_hex(&self) -> &str;
+}
+
+/// axum extractor for json requests signed by the trader. The signature in the SIGNATURE_HEADER is
+/// verified against the taproot pubkey of the trader before the handler runs.
+pub struct SignedRequest<T>(pub T);
+
+/// signatures of the requests accepted within the request age window, keyed by taproot pubkey and signature
+#[derive(Default)]
+pub struct SeenRequestSignatures {
+	signatures: std::sync::Mutex<HashMap<(String, String), u64>>,
+}
+
+impl SeenRequestSignatures {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// records the signature of a request with the given timestamp, returns false if it was already used.
+	/// Signatures outside of the request age window are forgotten, requests with them are rejected anyways.
+	pub fn insert(
+		&self,
+		taproot_pubkey_hex: &str,
+		signature_hex: &str,
+		timestamp: u64,
+		now: u64,
+	) -> bool {
+		let mut signatures = self.signatures.lock().unwrap_or_else(|e| e.into_inner());
+		signatures
+			.retain(|_, seen_timestamp| now.abs_diff(*seen_timestamp) <= MAX_REQUEST_AGE_SECS);
+		signatures
+			.insert(
+				(taproot_pubkey_hex.to_string(), signature_hex.to_string()),
+				timestamp,
+			)
+			.is_none()
+	}
+}
+
+/// the message the trader signs with its taproot key, commits to the endpoint, the timestamp and the body
+pub fn request_signature_message(
+	path: &str,
+	timestamp: u64,
+	body: &[u8],
+) -> Result<bdk::bitcoin::secp256k1::Message> {
+	let mut hasher = Sha256::new();
+	hasher.update(request_signature_preimage(path, timestamp, body));
+	Ok(bdk::bitcoin::secp256k1::Message::from_slice(
+		&hasher.finalize(),
+	)?)
+}
+
+/// verifies the schnorr signature of the request against the taproot key of the trader
+pub fn verify_request_signature(
+	path: &str,
+	timestamp: u64,
+	body: &[u8],
+	taproot_pubkey_hex: &str,
+	signature_hex: &str,
+) -> Result<()> {
+	let taproot_pubkey =
+		XOnlyPublicKey::from_str(taproot_pubkey_hex).context("Error parsing trader taproot pk")?;
+	let signature = bdk::bitcoin::secp256k1::schnorr::Signature::from_str(signature_hex)
+		.context("Error parsing request signature")?;
+	secp256k1::Secp256k1::verification_only()
+		.verify_schnorr(
+			&signature,
+			&request_signature_message(path, timestamp, body)?,
+			&taproot_pubkey,
+		)
+		.context("Invalid request signature")?;
+	Ok(())
+}
+
+fn header_value<'a>(req: &'a Request, name: &str) -> Option<&'a str> {
+	req.headers()
+		.get(name)
+		.and_then(|value| value.to_str().ok())
+}
+
+fn missing_header(name: &str) -> Response {
+	error_response(ErrorResponse::new(
+		ErrorCode::Unauthorized,
+		format!("Missing {} header", name),
+	))
+}
+
+#[async_trait]
+impl<T, S> FromRequest<S> for SignedRequest<T>
+where
+	T: DeserializeOwned + TraderRequest + Send,
+	S: Send + Sync,
+{
+	type Rejection = Response;
+
+	async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+		let coordinator = req
+			.extensions()
+			.get::<Arc<Coordinator>>()
+			.cloned()
+			.ok_or_else(|| {
+				error_response(ErrorResponse::new(
+					ErrorCode::Internal,
+					"Coordinator not available",
+				))
+			})?;
+		let path = req.uri().path().to_string();
+		let signature_hex = header_value(&req, SIGNATURE_HEADER)
+			.ok_or_else(|| missing_header(SIGNATURE_HEADER))?
+			.to_string();
+		let timestamp: u64 = header_value(&req, TIMESTAMP_HEADER)
+			.ok_or_else(|| missing_header(TIMESTAMP_HEADER))?
+			.parse()
+			.map_err(|_| {
+				error_response(ErrorResponse::new(
+					ErrorCode::Unauthorized,
+					"Invalid timestamp",
+				))
+			})?;
+
+		let now = unix_timestamp_now().map_err(|e| {
+			error_response(ErrorResponse::new(
+				ErrorCode::Internal,
+				format!("Error getting current time: {e}"),
+			))
+		})?;
+		if now.abs_diff(timestamp) > MAX_REQUEST_AGE_SECS {
+			return Err(error_response(ErrorResponse::new(
+				ErrorCode::Unauthorized,
+				"Request signature expired",
+			)));
+		}
+
+		let body = Bytes::from_request(req, state)
+			.await
+			.map_err(IntoResponse::into_response)?;
+		let payload: T = serde_json::from_slice(&body).map_err(|e| {
+			error_response(ErrorResponse::new(ErrorCode::InvalidRequest, e.to_string()))
+		})?;
+
+		let taproot_pubkey_hex = match coordinator
+			.coordinator_db
+			.fetch_trader_taproot_pubkey(payload.offer_id_hex(), payload.robohash_hex())
+			.await
+		{
+			Ok(Some(taproot_pubkey_hex)) => taproot_pubkey_hex,
+			Ok(None) => {
+				return Err(error_response(ErrorResponse::new(
+					ErrorCode::NotFound,
+					"Trader not found",
+				)))
+			}
+			Err(e) => {
+				return Err(error_response(ErrorResponse::new(
+					ErrorCode::Database,
+					format!("Error fetching trader taproot pubkey: {e}"),
+				)))
+			}
+		};
+		if let Err(e) =
+			verify_request_signature(&path, timestamp, &body, &taproot_pubkey_hex, &signature_hex)
+		{
+			warn!(
+				"Rejected unauthenticated request to {} for offer {}: {:#}",
+				path,
+				payload.offer_id_hex(),
+				e
+			);
+			return Err(error_response(ErrorResponse::new(
+				ErrorCode::Unauthorized,
+				format!("{:#}", e),
+			)));
+		}
+		// the signature was verified, so it parses and its canonical encoding identifies the request
+		let signature = bdk::bitcoin::secp256k1::schnorr::Signature::from_str(&signature_hex)
+			.map_err(|e| {
+				error_response(ErrorResponse::new(ErrorCode::Unauthorized, e.to_string()))
+			})?;
+		if !coordinator.seen_request_signatures.insert(
+			&taproot_pubkey_hex,
+			&signature.to_string(),
+			timestamp,
+			now,
+		) {
+			warn!(
+				"Rejected replayed request to {} for offer {}",
+				path,
+				payload.offer_id_hex()
+			);
+			return Err(error_response(ErrorResponse::new(
+				ErrorCode::Unauthorized,
+				"Request already processed",
+			)));
+		}
+		Ok(SignedRequest(payload))
+	}
+}
+
+impl TraderRequest for OfferTakenRequest {
+	fn offer_id_hex(&self) -> &str {
+		&self.offer_id_hex
+	}
+	fn robohash_hex(&self) -> &str {
+		&self.robohash_hex
+	}
+}
+
+impl TraderRequest for PsbtSubmissionRequest {
+	fn offer_id_hex(&self) -> &str {
+		&self.offer_id_hex
+	}
+	fn robohash_hex(&self) -> &str {
+		&self.robohash_hex
+	}
+}
+
+impl TraderRequest for TradeObligationsUnsatisfied {
+	fn offer_id_hex(&self) -> &str {
+		&self.offer_id_hex
+	}
+	fn robohash_hex(&self) -> &str {
+		&self.robohash_hex
+	}
+}
+
+impl TraderRequest for PayoutSignatureRequest {
+	fn offer_id_hex(&self) -> &str {
+		&self.offer_id_hex
+	}
+	fn robohash_hex(&self) -> &str {
+		&self.robohash_hex
+	}
+}
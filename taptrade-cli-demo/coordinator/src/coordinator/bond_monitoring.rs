@@ -1,4 +1,4 @@
-// we create an async function that loops trough the sqlite db table active_maker_offers and
+// we create an async function that loops trough the sqlite db tables active_maker_offers and taken_offers and
 // continoously verifies the bond inputs (mempool and chain), maybe with some caching in a hashmap to
 // prevent querying the db all the time.
 // Also needs to implement punishment logic in case a fraud is detected.
@@ -21,6 +21,7 @@ pub struct MonitoringBond {
 	pub robot: Vec<u8>,
 	pub requirements: BondRequirements,
 	pub table: Table,
+	pub escrow_txid: Option<String>, // set for bonds of taken offers
 }
 
 impl MonitoringBond {
@@ -39,21 +40,78 @@ impl MonitoringBond {
 		Ok(())
 	}
 
-	// the current implementation only publishes the bond and removes the offer from the db
-	// in a more advanced implementation we could increase the transaction fee (cpfp) and
-	// continue monitoring the bond transaction until a confirmation happens for maximum pain
-	// in case the trader is actively malicious and did not just accidentally invalidate the bond
-	// we could directly forward bond sats to the other parties payout address in case it is a taken trade
+	// the bond inputs of a taken offer get spent by the escrow transaction, so a violation is expected
+	// once the escrow transaction is in the mempool or mined. Only inputs spent by the escrow tx are ignored,
+	// a bond input spent by any other transaction is still a violation. If the escrow tx gets double spent
+	// it drops out of the mempool and the bond violation will be detected again.
+	async fn is_spent_by_escrow_tx(&self, coordinator: &Coordinator) -> Result<bool> {
+		match &self.escrow_txid {
+			Some(escrow_txid) => {
+				coordinator
+					.coordinator_wallet
+					.is_bond_spent_by_escrow_tx(&self.bond_tx_hex, escrow_txid)
+					.await
+			}
+			None => Ok(false),
+		}
+	}
+
+	// returns the payout address of the honest counterparty if the bond got forfeited in a taken trade
+	// and forwarding of punished bonds is enabled
+	async fn get_compensation_address(&self, coordinator: &Coordinator) -> Result<Option<String>> {
+		if self.table != Table::ActiveTrades {
+			return Ok(None);
+		}
+		match env::var("COMPENSATION_ENABLED")
+			.unwrap_or_else(|_| "0".to_string())
+			.as_str()
+		{
+			"1" => {
+				coordinator
+					.coordinator_db
+					.fetch_compensation_address(&self.trade_id_hex, &hex::encode(&self.robot))
+					.await
+			}
+			"0" => Ok(None),
+			_ => Err(anyhow!("Invalid COMPENSATION_ENABLED env var")),
+		}
+	}
+
+	// publishes the bond and removes the offer from the db (or aborts the trade if the offer is already taken).
+	// The published bond is recorded so monitor_published_bonds() can bump it with a cpfp child until it confirms.
+	// In a taken trade the cpfp child can forward the bond sats to the payout address of the other trader.
 	async fn punish(&self, coordinator: &Coordinator) -> Result<()> {
+		let compensation_address = self.get_compensation_address(coordinator).await?;
+
+		// record the bond before publishing so it gets rebroadcasted if publishing fails
+		coordinator
+			.coordinator_db
+			.insert_published_bond(self, compensation_address.as_deref())
+			.await
+			.context("Error recording published bond in db")?;
+
 		// publish bond
 		debug!("Publishing violating bond tx: {}", self.bond_tx_hex);
-		coordinator
+		// the bond can't be published if a conflicting transaction of the trader is already in the mempool,
+		// we still remove the offer so the honest counterparty isn't stuck in the trade
+		if let Err(e) = coordinator
 			.coordinator_wallet
-			.publish_bond_tx_hex(&self.bond_tx_hex)?; // can be made async with esplora backend if we figure out the compilation error of bdk
+			.publish_bond_tx_hex(&self.bond_tx_hex)
+		{
+			error!("Publishing violating bond tx failed: {}", e);
+		} // can be made async with esplora backend if we figure out the compilation error of bdk
 
-		// remove offer from db/orderbook
+		// remove offer from db/orderbook or abort the taken trade
 		self.remove_from_db_tables(&coordinator.coordinator_db)
 			.await?;
+		if self.table == Table::ActiveTrades {
+			coordinator.trade_events.publish(
+				&self.trade_id_hex,
+				TradeEventKind::Aborted {
+					violating_robohash_hex: hex::encode(&self.robot),
+				},
+			);
+		}
 		Ok(())
 	}
 }
@@ -63,12 +121,25 @@ impl MonitoringBond {
 pub async fn monitor_bonds(coordinator: Arc<Coordinator>) -> Result<()> {
 	let coordinator_db = Arc::clone(&coordinator.coordinator_db);
 	let coordinator_wallet = Arc::clone(&coordinator.coordinator_wallet);
+	let mut chain_events = coordinator.chain_notifications.subscribe();
 
 	loop {
-		// sleep for a while
-		tokio::time::sleep(tokio::time::Duration::from_secs(15)).await;
+		// sleep for a while or until a new transaction or block arrives
+		chain_events
+			.wait_for_transaction(tokio::time::Duration::from_secs(15))
+			.await;
 		// fetch all bonds
 		let bonds = Arc::new(coordinator_db.fetch_all_bonds().await?);
+		// only transactions spending bond inputs wake up the next pass early
+		coordinator.chain_notifications.watch_outpoints(
+			bonds
+				.iter()
+				.filter_map(|bond| {
+					deserialize::<Transaction>(&hex::decode(&bond.bond_tx_hex).ok()?).ok()
+				})
+				.flat_map(|bond_tx| bond_tx.input.into_iter().map(|input| input.previous_output))
+				.collect(),
+		);
 		if bonds.is_empty() {
 			continue;
 		}
@@ -78,6 +149,13 @@ pub async fn monitor_bonds(coordinator: Arc<Coordinator>) -> Result<()> {
 		debug!("Monitoring active bonds: {}", bonds.len());
 		// verify all bonds and initiate punishment if necessary
 		for (_, (bond, error)) in validation_results {
+			if bond.is_spent_by_escrow_tx(&coordinator).await? {
+				trace!(
+					"Bond inputs of trade {} spent by the escrow transaction",
+					bond.trade_id_hex
+				);
+				continue;
+			}
 			warn!("Bond validation failed: {:?}", error);
 			match env::var("PUNISHMENT_ENABLED")
 				.unwrap_or_else(|_| "0".to_string())
@@ -0,0 +1,299 @@
+// keeps track of the published bonds of punished traders until they are confirmed. The bond output is spent
+// with a cpfp child which gets replaced with a higher feerate for every block the bond doesn't confirm in.
+// If the bond gets evicted from the mempool it is rebroadcasted until it either confirms or a conflicting
+// transaction of the trader gets mined. If compensation is enabled the child of a bond forfeited in a taken
+// trade pays the bond output (minus fees) to the wronged trader, the punishment is finished once it confirmed.
+use super::*;
+use bdk::FeeRate;
+
+/// the feerate of the cpfp child package gets increased by this factor for every block the bond is not mined in
+const CPFP_FEERATE_BUMP_FACTOR: f32 = 1.25;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PunishmentStatus {
+	Pending,
+	Confirmed,
+	// the trader got a conflicting transaction mined, the bond can't be confirmed anymore
+	Conflicted,
+}
+
+impl PunishmentStatus {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			PunishmentStatus::Pending => "pending",
+			PunishmentStatus::Confirmed => "confirmed",
+			PunishmentStatus::Conflicted => "conflicted",
+		}
+	}
+}
+
+impl FromStr for PunishmentStatus {
+	type Err = anyhow::Error;
+
+	fn from_str(status: &str) -> Result<Self> {
+		match status {
+			"pending" => Ok(PunishmentStatus::Pending),
+			"confirmed" => Ok(PunishmentStatus::Confirmed),
+			"conflicted" => Ok(PunishmentStatus::Conflicted),
+			_ => Err(anyhow!("Invalid punishment status: {}", status)),
+		}
+	}
+}
+
+/// a bond published as punishment, stored in the published_bonds table
+#[derive(Debug, Clone)]
+pub struct PublishedBond {
+	pub bond_txid: String,
+	pub offer_id_hex: String,
+	pub robohash_hex: String,
+	pub bond_tx_hex: String,
+	pub bond_address: String,
+	pub cpfp_tx_hex: Option<String>,
+	pub cpfp_feerate_sat_vb: Option<f32>,
+	pub cpfp_block_height: Option<u64>,
+	pub status: PunishmentStatus,
+	// payout address of the wronged trader the bond output gets forwarded to
+	pub compensation_address: Option<String>,
+	pub compensation_txid: Option<String>,
+}
+
+impl PublishedBond {
+	// returns the package feerate the next cpfp child should pay, None if the current child is sufficient
+	fn next_cpfp_feerate(&self, coordinator: &Coordinator, block_height: u64) -> Option<FeeRate> {
+		let estimated_feerate = get_punishment_feerate(&coordinator.coordinator_wallet.backend);
+		match (self.cpfp_feerate_sat_vb, self.cpfp_block_height) {
+			(Some(feerate), Some(cpfp_block_height)) if block_height > cpfp_block_height => {
+				Some(FeeRate::from_sat_per_vb(
+					(feerate * CPFP_FEERATE_BUMP_FACTOR).max(estimated_feerate.as_sat_per_vb()),
+				))
+			}
+			(Some(_), Some(_)) => None,
+			_ => Some(estimated_feerate),
+		}
+	}
+
+	// broadcasts a new cpfp child for the bond and stores it in the db
+	async fn bump(
+		&self,
+		coordinator: &Coordinator,
+		feerate: FeeRate,
+		block_height: u64,
+	) -> Result<()> {
+		let cpfp_child = coordinator
+			.coordinator_wallet
+			.broadcast_cpfp_child(
+				&self.bond_tx_hex,
+				&self.bond_address,
+				feerate,
+				self.compensation_address.as_deref(),
+			)
+			.await?;
+		info!(
+			"Punishment of robot {}: broadcasted cpfp child for bond {} with package feerate {:.1} sat/vb",
+			self.robohash_hex, self.bond_txid, cpfp_child.package_feerate_sat_vb
+		);
+		coordinator
+			.coordinator_db
+			.update_published_bond_cpfp(&self.bond_txid, &cpfp_child, block_height)
+			.await
+	}
+
+	// rebroadcasts the bond and its cpfp child in case they dropped out of the mempool
+	fn rebroadcast(&self, coordinator: &Coordinator) {
+		let wallet = &coordinator.coordinator_wallet;
+		if let Err(e) = wallet.publish_bond_tx_hex(&self.bond_tx_hex) {
+			warn!(
+				"Punishment of robot {}: rebroadcasting bond {} failed: {}",
+				self.robohash_hex, self.bond_txid, e
+			);
+			return;
+		}
+		if let Some(cpfp_tx_hex) = &self.cpfp_tx_hex {
+			if let Err(e) = wallet.rebroadcast_cpfp_child(cpfp_tx_hex) {
+				warn!(
+					"Punishment of robot {}: rebroadcasting cpfp child of bond {} failed: {}",
+					self.robohash_hex, self.bond_txid, e
+				);
+			}
+		}
+	}
+
+	// replaces the cpfp child if the bond did not confirm since the last child got broadcasted
+	async fn bump_if_required(&self, coordinator: &Coordinator, block_height: u64) {
+		if let Some(feerate) = self.next_cpfp_feerate(coordinator, block_height) {
+			if let Err(e) = self.bump(coordinator, feerate, block_height).await {
+				warn!(
+					"Punishment of robot {}: bumping bond {} failed: {}",
+					self.robohash_hex, self.bond_txid, e
+				);
+			}
+		}
+	}
+
+	// once the bond is confirmed the cpfp child forwarding the bond output to the wronged trader has to confirm
+	async fn process_compensation(
+		&self,
+		coordinator: &Coordinator,
+		compensation_address: &str,
+		block_height: u64,
+	) -> Result<()> {
+		let compensation_tx_status = match &self.cpfp_tx_hex {
+			Some(cpfp_tx_hex) => coordinator
+				.coordinator_wallet
+				.get_punishment_tx_status(cpfp_tx_hex)?,
+			None => PunishmentTxStatus::Missing,
+		};
+		match compensation_tx_status {
+			PunishmentTxStatus::Confirmed => {
+				info!(
+					"Punishment of robot {} succeeded: bond {} of offer {} confirmed and forwarded to {} in compensation tx {}",
+					self.robohash_hex,
+					self.bond_txid,
+					self.offer_id_hex,
+					compensation_address,
+					self.compensation_txid.as_deref().unwrap_or("unknown")
+				);
+				self.set_status(coordinator, PunishmentStatus::Confirmed)
+					.await?;
+			}
+			PunishmentTxStatus::InMempool => self.bump_if_required(coordinator, block_height).await,
+			PunishmentTxStatus::Missing => {
+				// the bond confirmed without its child, a new child only pays for itself
+				if let Err(e) = self
+					.bump(
+						coordinator,
+						get_punishment_feerate(&coordinator.coordinator_wallet.backend),
+						block_height,
+					)
+					.await
+				{
+					warn!(
+						"Punishment of robot {}: broadcasting compensation tx for bond {} failed: {}",
+						self.robohash_hex, self.bond_txid, e
+					);
+				}
+			}
+			PunishmentTxStatus::Conflicted => {
+				// an earlier child got mined before it was replaced by the stored one
+				let mined_compensation_tx = match &self.cpfp_tx_hex {
+					Some(cpfp_tx_hex) => {
+						coordinator
+							.coordinator_wallet
+							.find_mined_compensation_tx(cpfp_tx_hex, compensation_address)
+							.await?
+					}
+					None => None,
+				};
+				match mined_compensation_tx {
+					Some((compensation_txid, compensation_amount_sat)) => {
+						info!(
+							"Punishment of robot {} succeeded: bond {} of offer {} confirmed and forwarded to {} in replaced compensation tx {}",
+							self.robohash_hex,
+							self.bond_txid,
+							self.offer_id_hex,
+							compensation_address,
+							compensation_txid
+						);
+						coordinator
+							.coordinator_db
+							.update_published_bond_compensation(
+								&self.bond_txid,
+								&compensation_txid.to_string(),
+								compensation_amount_sat,
+							)
+							.await?;
+					}
+					None => warn!(
+						"Punishment of robot {}: bond {} confirmed but its output got spent by another transaction than compensation tx {}",
+						self.robohash_hex,
+						self.bond_txid,
+						self.compensation_txid.as_deref().unwrap_or("unknown")
+					),
+				}
+				self.set_status(coordinator, PunishmentStatus::Confirmed)
+					.await?;
+			}
+		}
+		Ok(())
+	}
+
+	async fn set_status(&self, coordinator: &Coordinator, status: PunishmentStatus) -> Result<()> {
+		coordinator
+			.coordinator_db
+			.set_published_bond_status(&self.bond_txid, &status)
+			.await
+	}
+
+	// checks the onchain state of the bond and rebroadcasts or bumps it if necessary
+	async fn process(&self, coordinator: &Coordinator, block_height: u64) -> Result<()> {
+		match coordinator
+			.coordinator_wallet
+			.get_punishment_tx_status(&self.bond_tx_hex)?
+		{
+			PunishmentTxStatus::Confirmed => match &self.compensation_address {
+				Some(compensation_address) => {
+					self.process_compensation(coordinator, compensation_address, block_height)
+						.await?
+				}
+				None => {
+					info!(
+						"Punishment of robot {} succeeded: bond {} of offer {} confirmed",
+						self.robohash_hex, self.bond_txid, self.offer_id_hex
+					);
+					self.set_status(coordinator, PunishmentStatus::Confirmed)
+						.await?;
+				}
+			},
+			PunishmentTxStatus::Conflicted => {
+				warn!(
+					"Punishment of robot {} failed: a conflicting transaction of bond {} of offer {} got mined",
+					self.robohash_hex, self.bond_txid, self.offer_id_hex
+				);
+				self.set_status(coordinator, PunishmentStatus::Conflicted)
+					.await?;
+			}
+			PunishmentTxStatus::InMempool => self.bump_if_required(coordinator, block_height).await,
+			PunishmentTxStatus::Missing => {
+				debug!(
+					"Punishment of robot {}: bond {} not in mempool, rebroadcasting",
+					self.robohash_hex, self.bond_txid
+				);
+				self.rebroadcast(coordinator);
+			}
+		}
+		Ok(())
+	}
+}
+
+/// this function periodically fetches all pending published bonds from the database and
+/// rebroadcasts or fee bumps them until they either confirm or a conflicting transaction gets mined
+pub async fn monitor_published_bonds(coordinator: Arc<Coordinator>) -> Result<()> {
+	let coordinator_db = Arc::clone(&coordinator.coordinator_db);
+	let mut chain_events = coordinator.chain_notifications.subscribe();
+
+	loop {
+		// sleep for a while or until a new block arrives
+		chain_events
+			.wait_for_block(tokio::time::Duration::from_secs(30))
+			.await;
+		let published_bonds = coordinator_db.fetch_pending_published_bonds().await?;
+		if published_bonds.is_empty() {
+			continue;
+		}
+		let block_height = coordinator
+			.coordinator_wallet
+			.chain_backend
+			.get_block_height()?;
+		debug!("Monitoring published bonds: {}", published_bonds.len());
+		for published_bond in published_bonds {
+			// a failing bond (e.g. a cpfp broadcast error) must not starve the following bonds,
+			// it is retried in the next pass
+			if let Err(e) = published_bond.process(&coordinator, block_height).await {
+				error!(
+					"Processing published bond {} of robot {} failed: {:#}",
+					published_bond.bond_txid, published_bond.robohash_hex, e
+				);
+			}
+		}
+	}
+}
@@ -0,0 +1,148 @@
+// Optional zmq subscriber for the rawtx and hashblock notifications of bitcoin core (zmqpubrawtx, zmqpubhashblock).
+// The notifications wake up the monitoring tasks as soon as a block or a transaction spending one of the
+// watched bond inputs arrives, without zmq (or if the connection is lost) the monitors keep polling on their
+// fixed interval.
+use super::*;
+use std::{collections::HashSet, sync::RwLock, time::Duration};
+use tokio::sync::watch;
+use zeromq::{Socket, SocketRecv, SubSocket};
+
+/// broadcasts new transaction and block events received over zmq to the monitoring tasks
+pub struct ChainNotifications {
+	transactions: watch::Sender<()>,
+	blocks: watch::Sender<()>,
+	// inputs of the monitored bonds, other mempool transactions don't wake up the transaction monitors
+	watched_outpoints: RwLock<HashSet<OutPoint>>,
+}
+
+/// receiver of a single monitoring task, multiple events between two waits are coalesced into one wake up
+pub struct ChainEvents {
+	transactions: watch::Receiver<()>,
+	blocks: watch::Receiver<()>,
+}
+
+impl ChainNotifications {
+	pub fn new() -> Self {
+		Self {
+			transactions: watch::channel(()).0,
+			blocks: watch::channel(()).0,
+			watched_outpoints: RwLock::new(HashSet::new()),
+		}
+	}
+
+	pub fn subscribe(&self) -> ChainEvents {
+		ChainEvents {
+			transactions: self.transactions.subscribe(),
+			blocks: self.blocks.subscribe(),
+		}
+	}
+
+	/// replaces the outpoints whose spending transactions wake up the transaction monitors
+	pub fn watch_outpoints(&self, outpoints: HashSet<OutPoint>) {
+		*self
+			.watched_outpoints
+			.write()
+			.unwrap_or_else(|e| e.into_inner()) = outpoints;
+	}
+
+	fn notify_transaction(&self) {
+		self.transactions.send_replace(());
+	}
+
+	// every mempool transaction would otherwise trigger a full bond validation pass
+	fn notify_raw_transaction(&self, raw_tx: &[u8]) {
+		let tx: Transaction = match deserialize(raw_tx) {
+			Ok(tx) => tx,
+			Err(e) => {
+				debug!("Ignoring undecodable zmq rawtx notification: {}", e);
+				return;
+			}
+		};
+		let spends_watched_outpoint = {
+			let watched_outpoints = self
+				.watched_outpoints
+				.read()
+				.unwrap_or_else(|e| e.into_inner());
+			tx.input
+				.iter()
+				.any(|input| watched_outpoints.contains(&input.previous_output))
+		};
+		if spends_watched_outpoint {
+			trace!(
+				"Received zmq notification of tx {} spending a watched outpoint",
+				tx.txid()
+			);
+			self.notify_transaction();
+		}
+	}
+
+	// a block can contain transactions never seen in the mempool (out of band mining)
+	// so the transaction monitors are woken up as well
+	fn notify_block(&self) {
+		self.blocks.send_replace(());
+		self.transactions.send_replace(());
+	}
+}
+
+impl Default for ChainNotifications {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// waits until the receiver gets notified, never returns if the sender is gone so the polling interval applies
+async fn changed(receiver: &mut watch::Receiver<()>) {
+	if receiver.changed().await.is_err() {
+		std::future::pending::<()>().await;
+	}
+}
+
+impl ChainEvents {
+	/// waits until a new transaction or block arrived or the poll interval passed
+	pub async fn wait_for_transaction(&mut self, poll_interval: Duration) {
+		tokio::select! {
+			_ = tokio::time::sleep(poll_interval) => (),
+			_ = changed(&mut self.transactions) => trace!("Woken up by zmq transaction notification"),
+		}
+	}
+
+	/// waits until a new block arrived or the poll interval passed
+	pub async fn wait_for_block(&mut self, poll_interval: Duration) {
+		tokio::select! {
+			_ = tokio::time::sleep(poll_interval) => (),
+			_ = changed(&mut self.blocks) => trace!("Woken up by zmq block notification"),
+		}
+	}
+}
+
+/// subscribes to the rawtx and hashblock topics of the bitcoin core zmq publisher at the endpoint
+/// (e.g. tcp://127.0.0.1:28332) and forwards them to the monitoring tasks
+pub async fn run_zmq_subscriber(
+	zmq_endpoint: &str,
+	notifications: Arc<ChainNotifications>,
+) -> Result<()> {
+	let mut socket = SubSocket::new();
+	socket
+		.connect(zmq_endpoint)
+		.await
+		.with_context(|| format!("Connecting to zmq endpoint {} failed", zmq_endpoint))?;
+	socket.subscribe("rawtx").await?;
+	socket.subscribe("hashblock").await?;
+	info!("Subscribed to zmq notifications at {}", zmq_endpoint);
+
+	loop {
+		// multipart message: topic, body, sequence number
+		let message = socket.recv().await?;
+		match message.get(0).map(|topic| topic.as_ref()) {
+			Some(b"rawtx") => match message.get(1) {
+				Some(raw_tx) => notifications.notify_raw_transaction(raw_tx),
+				None => debug!("Ignoring zmq rawtx notification without body"),
+			},
+			Some(b"hashblock") => {
+				trace!("Received zmq block notification");
+				notifications.notify_block();
+			}
+			topic => debug!("Ignoring zmq message with topic {:?}", topic),
+		}
+	}
+}
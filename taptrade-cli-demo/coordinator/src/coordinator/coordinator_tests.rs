@@ -0,0 +1,343 @@
+use super::chain_notifications::*;
+use super::*;
+use std::time::Duration;
+use zeromq::{PubSocket, Socket, SocketSend, ZmqMessage};
+
+// local zmq publisher standing in for the bitcoin core zmq interface
+async fn start_zmq_publisher_stub() -> (PubSocket, String) {
+	let mut publisher = PubSocket::new();
+	let endpoint = publisher.bind("tcp://127.0.0.1:0").await.unwrap();
+	(publisher, endpoint.to_string())
+}
+
+// notification as sent by bitcoin core: topic, body, little endian sequence number
+fn zmq_notification(topic: &str, body: Vec<u8>) -> ZmqMessage {
+	let mut message = ZmqMessage::from(topic);
+	message.push_back(body.into());
+	message.push_back(0u32.to_le_bytes().to_vec().into());
+	message
+}
+
+// serialized transaction spending the outpoint
+fn spending_tx(outpoint: OutPoint) -> Vec<u8> {
+	serialize(&Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: vec![bdk::bitcoin::TxIn {
+			previous_output: outpoint,
+			..Default::default()
+		}],
+		output: vec![],
+	})
+}
+
+#[tokio::test]
+async fn test_zmq_notifications_wake_up_monitors() {
+	let (mut publisher, endpoint) = start_zmq_publisher_stub().await;
+	let notifications = Arc::new(ChainNotifications::new());
+	let mut chain_events = notifications.subscribe();
+	let notifications_ref = Arc::clone(&notifications);
+	let subscriber =
+		tokio::spawn(async move { run_zmq_subscriber(&endpoint, notifications_ref).await });
+
+	// the monitors would only poll every hour without notifications
+	let poll_interval = Duration::from_secs(3600);
+	let short_timeout = Duration::from_millis(200);
+
+	// the subscription takes a moment to reach the publisher, earlier messages get dropped
+	let block_notified = async {
+		loop {
+			publisher
+				.send(zmq_notification("hashblock", vec![0; 32]))
+				.await
+				.unwrap();
+			if tokio::time::timeout(short_timeout, chain_events.wait_for_block(poll_interval))
+				.await
+				.is_ok()
+			{
+				break;
+			}
+		}
+	};
+	tokio::time::timeout(Duration::from_secs(10), block_notified)
+		.await
+		.expect("No block notification received");
+
+	// a block also wakes up the transaction monitors
+	assert!(tokio::time::timeout(
+		short_timeout,
+		chain_events.wait_for_transaction(poll_interval)
+	)
+	.await
+	.is_ok());
+	// consume block notifications still in flight
+	let _ = tokio::time::timeout(short_timeout, chain_events.wait_for_block(poll_interval)).await;
+	let _ = tokio::time::timeout(
+		short_timeout,
+		chain_events.wait_for_transaction(poll_interval),
+	)
+	.await;
+
+	// only transactions spending a watched bond input wake up the transaction monitors
+	let watched_outpoint = OutPoint::new(Txid::all_zeros(), 0);
+	notifications.watch_outpoints([watched_outpoint].into_iter().collect());
+	publisher
+		.send(zmq_notification(
+			"rawtx",
+			spending_tx(OutPoint::new(Txid::all_zeros(), 1)),
+		))
+		.await
+		.unwrap();
+	publisher
+		.send(zmq_notification("rawtx", vec![2, 0, 0, 0]))
+		.await
+		.unwrap();
+	assert!(tokio::time::timeout(
+		short_timeout,
+		chain_events.wait_for_transaction(poll_interval)
+	)
+	.await
+	.is_err());
+	publisher
+		.send(zmq_notification("rawtx", spending_tx(watched_outpoint)))
+		.await
+		.unwrap();
+	assert!(tokio::time::timeout(
+		Duration::from_secs(5),
+		chain_events.wait_for_transaction(poll_interval)
+	)
+	.await
+	.is_ok());
+	assert!(
+		tokio::time::timeout(short_timeout, chain_events.wait_for_block(poll_interval))
+			.await
+			.is_err()
+	);
+
+	// without notifications the monitors fall back to polling
+	assert!(tokio::time::timeout(
+		short_timeout,
+		chain_events.wait_for_block(Duration::from_millis(10))
+	)
+	.await
+	.is_ok());
+
+	subscriber.abort();
+}
+
+#[test]
+fn test_check_trader_network() {
+	assert!(check_trader_network(Network::Regtest, "regtest").is_ok());
+	assert!(check_trader_network(Network::Bitcoin, "bitcoin").is_ok());
+	let mismatch = check_trader_network(Network::Testnet, "signet").unwrap_err();
+	assert_eq!(
+		mismatch,
+		"Network mismatch: coordinator runs on testnet, trader on signet"
+	);
+	assert!(check_trader_network(Network::Regtest, "not a network").is_err());
+}
+
+#[tokio::test]
+async fn test_network_checked_on_every_route() {
+	use axum::body::Body;
+	use tower::ServiceExt;
+
+	let app = Router::new()
+		.route(
+			endpoints::SUBMIT_TAKER_BOND,
+			post(|| async { "taker bond" }),
+		)
+		.route(endpoints::POLL_FINAL_PAYOUT, post(|| async { "payout" }))
+		.layer(axum::middleware::from_fn_with_state(
+			Network::Regtest,
+			check_network_header,
+		));
+	let request = |path: &str, network: Option<&str>| {
+		let mut request = axum::http::Request::post(path);
+		if let Some(network) = network {
+			request = request.header(NETWORK_HEADER, network);
+		}
+		request.body(Body::empty()).unwrap()
+	};
+
+	// a taker on another network is rejected
+	let response = app
+		.clone()
+		.oneshot(request(endpoints::SUBMIT_TAKER_BOND, Some("signet")))
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+		.await
+		.unwrap();
+	let error: ErrorResponse = serde_json::from_slice(&body).unwrap();
+	assert_eq!(error.code, ErrorCode::NetworkMismatch);
+
+	// also on the later trade endpoints and without network header
+	let response = app
+		.clone()
+		.oneshot(request(endpoints::POLL_FINAL_PAYOUT, Some("bitcoin")))
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+	let response = app
+		.clone()
+		.oneshot(request(endpoints::SUBMIT_TAKER_BOND, None))
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+	let response = app
+		.oneshot(request(endpoints::SUBMIT_TAKER_BOND, Some("regtest")))
+		.await
+		.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[test]
+fn test_verify_request_signature() {
+	use crate::communication::signed_request::*;
+
+	let secp = secp256k1::Secp256k1::new();
+	let keypair = secp256k1::KeyPair::new(&secp, &mut rand::thread_rng());
+	let taproot_pubkey_hex = keypair.x_only_public_key().0.to_string();
+	let body = br#"{"robohash_hex":"a1a1","offer_id_hex":"offer"}"#;
+	let message = request_signature_message("/request-escrow", 1700000000, body).unwrap();
+	let signature_hex = secp.sign_schnorr(&message, &keypair).to_string();
+
+	assert!(verify_request_signature(
+		"/request-escrow",
+		1700000000,
+		body,
+		&taproot_pubkey_hex,
+		&signature_hex
+	)
+	.is_ok());
+	// the signature can't be reused for another endpoint, timestamp or body
+	assert!(verify_request_signature(
+		"/poll-final-payout",
+		1700000000,
+		body,
+		&taproot_pubkey_hex,
+		&signature_hex
+	)
+	.is_err());
+	assert!(verify_request_signature(
+		"/request-escrow",
+		1700000001,
+		body,
+		&taproot_pubkey_hex,
+		&signature_hex
+	)
+	.is_err());
+	assert!(verify_request_signature(
+		"/request-escrow",
+		1700000000,
+		br#"{"robohash_hex":"b2b2","offer_id_hex":"offer"}"#,
+		&taproot_pubkey_hex,
+		&signature_hex
+	)
+	.is_err());
+	// a signature of another key is rejected
+	let other_keypair = secp256k1::KeyPair::new(&secp, &mut rand::thread_rng());
+	assert!(verify_request_signature(
+		"/request-escrow",
+		1700000000,
+		body,
+		&other_keypair.x_only_public_key().0.to_string(),
+		&signature_hex
+	)
+	.is_err());
+}
+
+#[test]
+fn test_replayed_request_signature_rejected() {
+	use crate::communication::signed_request::*;
+
+	let seen_signatures = SeenRequestSignatures::new();
+	assert!(seen_signatures.insert("pubkey", "signature", 1700000000, 1700000000));
+	// the same signed request is rejected for as long as its timestamp is accepted
+	assert!(!seen_signatures.insert("pubkey", "signature", 1700000000, 1700000001));
+	assert!(!seen_signatures.insert("pubkey", "signature", 1700000000, 1700000300));
+	// other signatures of the trader are accepted
+	assert!(seen_signatures.insert("pubkey", "other_signature", 1700000001, 1700000001));
+	assert!(seen_signatures.insert("other_pubkey", "signature", 1700000000, 1700000001));
+	// expired signatures are forgotten, the request age check rejects them
+	assert!(seen_signatures.insert("pubkey", "signature", 1700000000, 1700000301));
+}
+
+#[tokio::test]
+async fn test_trade_event_stream_of_single_trade() {
+	use super::trade_events::*;
+	use futures_util::StreamExt;
+
+	let trade_events = TradeEvents::new();
+	let mut stream = Box::pin(trade_events.subscribe("offer"));
+
+	// events of other trades are not pushed to the subscriber
+	trade_events.publish("other_offer", TradeEventKind::Taken);
+	trade_events.publish(
+		"offer",
+		TradeEventKind::TraderHappy {
+			robohash_hex: "a1a1".to_string(),
+		},
+	);
+	let event = tokio::time::timeout(Duration::from_secs(1), stream.next())
+		.await
+		.expect("trade event not received")
+		.unwrap()
+		.unwrap();
+	let event = format!("{:?}", event);
+	assert!(event.contains("event: trader_happy"));
+	assert!(event.contains("robohash_hex") && event.contains("a1a1"));
+
+	// nothing else is pending for this trade
+	assert!(
+		tokio::time::timeout(Duration::from_millis(100), stream.next())
+			.await
+			.is_err()
+	);
+}
+
+#[tokio::test]
+async fn test_error_responses_have_code_and_status() {
+	async fn error_body(response: Response) -> (StatusCode, ErrorResponse) {
+		let status = response.status();
+		let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+			.await
+			.unwrap();
+		(status, serde_json::from_slice(&body).unwrap())
+	}
+
+	let (status, body) = error_body(RequestError::PsbtAlreadySubmitted.into_response()).await;
+	assert_eq!(status, StatusCode::CONFLICT);
+	assert_eq!(body.code, ErrorCode::PsbtAlreadySubmitted);
+	assert!(!body.retryable);
+
+	let (status, body) = error_body(RequestError::NotConfirmed.into_response()).await;
+	assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+	assert_eq!(body.code, ErrorCode::EscrowNotConfirmed);
+	assert!(body.retryable);
+
+	let (status, body) =
+		error_body(BondError::InvalidBond("bond too small".to_string()).into_response()).await;
+	assert_eq!(status, StatusCode::NOT_ACCEPTABLE);
+	assert_eq!(body.code, ErrorCode::InvalidBond);
+	assert_eq!(body.message, "bond too small");
+
+	let (status, body) =
+		error_body(FetchEscrowConfirmationError::Database("db locked".to_string()).into_response())
+			.await;
+	assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+	assert_eq!(body.code, ErrorCode::Database);
+	assert!(body.retryable);
+
+	// no offers is no error and has no body
+	let response = FetchOffersError::NoOffersAvailable.into_response();
+	assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+	assert_eq!(
+		serde_json::to_string(&ErrorResponse::new(ErrorCode::TradeAborted, "aborted")).unwrap(),
+		r#"{"code":"trade_aborted","message":"aborted","retryable":false}"#
+	);
+}
@@ -1,8 +1,10 @@
 use super::*;
+use sha2::{Digest, Sha256};
 
 #[derive(Debug)]
 pub enum PayoutProcessingResult {
 	ReadyPSBT(PayoutResponse),
+	ReadyScriptPSBT(ScriptPayoutResponse),
 	NotReady,
 	LostEscrow,
 	DecidingEscrow,
@@ -19,6 +21,16 @@ pub struct PayoutData {
 	pub aggregated_musig_pubkey_ctx_hex: String,
 }
 
+/// data required to construct the script path payout transaction paying the whole
+/// escrow output to the winner of an escrow (dispute) case
+#[derive(Debug)]
+pub struct ScriptPayoutData {
+	pub escrow_output_descriptor: Descriptor<XOnlyPublicKey>,
+	pub payout_address_winner: Address,
+	pub taproot_pk_winner: XOnlyPublicKey,
+	pub taproot_pk_coordinator: XOnlyPublicKey,
+}
+
 /// KeyspendContext contains all data neccessary to create the
 /// signed keyspend payout transaction
 #[derive(Debug, Clone)]
@@ -29,27 +41,28 @@ pub struct KeyspendContext {
 	pub keyspend_psbt: PartiallySignedTransaction,
 }
 
+/// PartialSigVerificationData contains everything needed to verify a single
+/// partial signature submitted by one of the traders before it gets aggregated
+#[derive(Debug)]
+pub struct PartialSigVerificationData {
+	pub agg_keyspend_pk: KeyAggContext,
+	pub agg_nonce: MusigAggNonce,
+	pub signer_pk: MuSig2PubKey,
+	pub signer_nonce: MusigPubNonce,
+	pub keyspend_sighash: [u8; 32],
+}
+
 /// takes two hex encoded pub musig nonces (serialized according to musig2 crate) and
 /// aggregates them into a single MusigAggNonce
 pub fn agg_hex_musig_nonces(maker_nonce: &str, taker_nonce: &str) -> Result<MusigAggNonce> {
 	// decode the hex strings into MusigPubNonces
 	let musig_pub_nonce_maker = match MusigPubNonce::from_hex(maker_nonce) {
 		Ok(musig_pub_nonce_maker) => musig_pub_nonce_maker,
-		Err(e) => {
-			return Err(anyhow!(
-				"Error decoding maker musig pub nonce: {}",
-				e.to_string()
-			))
-		}
+		Err(e) => return Err(anyhow!("Error decoding maker musig pub nonce: {}", e)),
 	};
 	let musig_pub_nonce_taker = match MusigPubNonce::from_hex(taker_nonce) {
 		Ok(musig_pub_nonce_taker) => musig_pub_nonce_taker,
-		Err(e) => {
-			return Err(anyhow!(
-				"Error decoding taker musig pub nonce: {}",
-				e.to_string()
-			))
-		}
+		Err(e) => return Err(anyhow!("Error decoding taker musig pub nonce: {}", e)),
 	};
 
 	// aggregate the two pub nonces
@@ -87,23 +100,7 @@ impl KeyspendContext {
 		let partial_signatures = vec![partial_maker_sig, partial_taker_sig];
 
 		// obtain the message to sign (taproot key spend signature hash)
-		let msg = {
-			let mut sig_hash_cache = SighashCache::new(keyspend_psbt.unsigned_tx.clone());
-
-			// it should only contain one utxo, the escrow locking UTXO
-			let utxo = keyspend_psbt
-				.iter_funding_utxos()
-				.next()
-				.ok_or(anyhow!("No UTXO found in payout psbt"))??
-				.clone();
-
-			let sighash_type = keyspend_psbt.inputs[0].taproot_hash_ty()?;
-			// get the msg (sighash) to sign with the musig key
-			let binding = sig_hash_cache
-				.taproot_key_spend_signature_hash(0, &Prevouts::All(&[utxo]), sighash_type)
-				.context("Failed to create keyspend sighash")?;
-			binding.to_raw_hash()
-		};
+		let msg = get_keyspend_sighash(&keyspend_psbt)?;
 
 		let agg_sig: LiftedSignature = musig2::aggregate_partial_signatures(
 			&agg_keyspend_pk,
@@ -122,6 +119,77 @@ impl KeyspendContext {
 	}
 }
 
+impl PartialSigVerificationData {
+	/// Create a new PartialSigVerificationData from the hex encoded strings stored for the trade,
+	/// `signer_is_maker` selects which of the two pubkeys and nonces belongs to the signer
+	#[allow(clippy::too_many_arguments)]
+	pub fn from_hex_str(
+		signer_is_maker: bool,
+		maker_nonce: &str,
+		taker_nonce: &str,
+		maker_pk: &str,
+		taker_pk: &str,
+		keyspend_psbt: &str,
+		descriptor: &str,
+	) -> anyhow::Result<Self> {
+		let tweak = get_keyspend_tweak_scalar(descriptor)?;
+		let agg_keyspend_pk = aggregate_musig_pubkeys_with_tweak(maker_pk, taker_pk, tweak)?;
+		let agg_nonce = agg_hex_musig_nonces(maker_nonce, taker_nonce)?;
+		let keyspend_psbt = PartiallySignedTransaction::deserialize(&hex::decode(keyspend_psbt)?)?;
+
+		let (signer_pk, signer_nonce) = if signer_is_maker {
+			(maker_pk, maker_nonce)
+		} else {
+			(taker_pk, taker_nonce)
+		};
+
+		Ok(Self {
+			agg_keyspend_pk,
+			agg_nonce,
+			signer_pk: MuSig2PubKey::from_str(signer_pk)
+				.context("Error parsing signer musig pk")?,
+			signer_nonce: MusigPubNonce::from_hex(signer_nonce)
+				.map_err(|e| anyhow!("Error decoding signer musig pub nonce: {}", e))?,
+			keyspend_sighash: get_keyspend_sighash(&keyspend_psbt)?,
+		})
+	}
+
+	/// verifies the hex encoded partial signature against the individual pubkey and pub nonce of the signer
+	pub fn verify(&self, partial_sig_hex: &str) -> anyhow::Result<()> {
+		let partial_sig = PartialSignature::from_hex(partial_sig_hex)
+			.context("Error decoding partial signature")?;
+		musig2::verify_partial(
+			&self.agg_keyspend_pk,
+			partial_sig,
+			&self.agg_nonce,
+			self.signer_pk,
+			&self.signer_nonce,
+			self.keyspend_sighash,
+		)
+		.context("Partial signature is invalid")?;
+		Ok(())
+	}
+}
+
+/// returns the taproot key spend signature hash of the (single input) keyspend payout psbt
+fn get_keyspend_sighash(keyspend_psbt: &PartiallySignedTransaction) -> Result<[u8; 32]> {
+	let mut sig_hash_cache = SighashCache::new(keyspend_psbt.unsigned_tx.clone());
+
+	// it should only contain one utxo, the escrow locking UTXO
+	let utxo = keyspend_psbt
+		.iter_funding_utxos()
+		.next()
+		.ok_or(anyhow!("No UTXO found in payout psbt"))??
+		.clone();
+
+	let sighash_type = keyspend_psbt.inputs[0].taproot_hash_ty()?;
+	// get the msg (sighash) to sign with the musig key
+	let sighash = sig_hash_cache
+		.taproot_key_spend_signature_hash(0, &Prevouts::All(&[utxo]), sighash_type)
+		.context("Failed to create keyspend sighash")?;
+	Ok(sighash.to_byte_array())
+}
+
 /// get the scalar used to tweak the keyspend output key from the escrow output descriptor
 fn get_keyspend_tweak_scalar(descriptor: &str) -> Result<bdk::bitcoin::secp256k1::Scalar> {
 	let tr_descriptor: Descriptor<XOnlyPublicKey> =
@@ -179,6 +247,7 @@ impl PayoutData {
 		musig_pub_nonce_hex_taker: &str,
 		musig_pk_hex_maker: &str,
 		musig_pk_hex_taker: &str,
+		network: Network,
 	) -> Result<Self> {
 		let tweak = get_keyspend_tweak_scalar(escrow_output_descriptor)?;
 		let aggregated_musig_pubkey_ctx_hex = hex::encode(
@@ -192,11 +261,11 @@ impl PayoutData {
 		Ok(Self {
 			escrow_output_descriptor: Descriptor::from_str(escrow_output_descriptor)?,
 			payout_address_maker: Address::from_str(payout_address_maker)?
-				.require_network(bdk::bitcoin::Network::Regtest)
+				.require_network(network)
 				.context("Maker payout address wrong network")?,
 			payout_address_taker: Address::from_str(payout_address_taker)?
-				.require_network(bdk::bitcoin::Network::Regtest)
-				.context("Taker payout address wrong Network")?,
+				.require_network(network)
+				.context("Taker payout address wrong network")?,
 			payout_amount_maker,
 			payout_amount_taker,
 			agg_musig_nonce,
@@ -205,6 +274,63 @@ impl PayoutData {
 	}
 }
 
+impl ScriptPayoutData {
+	/// assembles the information retrieved from the database into a ScriptPayoutData struct which is used for
+	/// constructing the script path payout transaction co-signed by the coordinator
+	pub fn new_from_strings(
+		escrow_output_descriptor: &str,
+		payout_address_winner: &str,
+		taproot_pk_hex_winner: &str,
+		taproot_pk_hex_coordinator: &str,
+		network: Network,
+	) -> Result<Self> {
+		Ok(Self {
+			escrow_output_descriptor: Descriptor::from_str(escrow_output_descriptor)?,
+			payout_address_winner: Address::from_str(payout_address_winner)?
+				.require_network(network)
+				.context("Winner payout address wrong network")?,
+			taproot_pk_winner: XOnlyPublicKey::from_str(taproot_pk_hex_winner)
+				.context("Error parsing winner taproot pk")?,
+			taproot_pk_coordinator: XOnlyPublicKey::from_str(taproot_pk_hex_coordinator)
+				.context("Error parsing coordinator taproot pk")?,
+		})
+	}
+}
+
+/// the message the maker signs with its taproot key to cancel an offer
+pub fn offer_cancellation_message(offer_id_hex: &str) -> Result<bdk::bitcoin::secp256k1::Message> {
+	let mut hasher = Sha256::new();
+	hasher.update(offer_cancellation_preimage(offer_id_hex));
+	Ok(bdk::bitcoin::secp256k1::Message::from_slice(
+		&hasher.finalize(),
+	)?)
+}
+
+/// verifies the schnorr signature of the offer cancellation message against the taproot key of the maker
+pub fn verify_offer_cancellation_signature(
+	offer_id_hex: &str,
+	taproot_pubkey_hex: &str,
+	signature_hex: &str,
+) -> Result<()> {
+	let taproot_pubkey =
+		XOnlyPublicKey::from_str(taproot_pubkey_hex).context("Error parsing maker taproot pk")?;
+	let signature = bdk::bitcoin::secp256k1::schnorr::Signature::from_str(signature_hex)
+		.context("Error parsing cancellation signature")?;
+	secp256k1::Secp256k1::verification_only()
+		.verify_schnorr(
+			&signature,
+			&offer_cancellation_message(offer_id_hex)?,
+			&taproot_pubkey,
+		)
+		.context("Invalid cancellation signature")?;
+	Ok(())
+}
+
+/// returns the current unix timestamp in seconds
+pub fn unix_timestamp_now() -> Result<u64> {
+	Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
 /// generates a random order id of size `size` bytes and returns it as hex encoded string
 pub fn generate_random_order_id(size: usize) -> String {
 	// Generate `len` random bytes
@@ -31,7 +31,8 @@ fn get_coordinator_cli_input(escrow_case: EscrowCase) -> EscrowWinner {
 	}
 }
 
-pub async fn escrow_cli_loop(database: Arc<CoordinatorDB>) {
+pub async fn escrow_cli_loop(coordinator: Arc<Coordinator>) {
+	let database = &coordinator.coordinator_db;
 	loop {
 		let open_escrows: Vec<EscrowCase> = database
 			.get_open_escrows()
@@ -43,21 +44,24 @@ pub async fn escrow_cli_loop(database: Arc<CoordinatorDB>) {
 			let escrow_input_result =
 				tokio::task::spawn_blocking(move || get_coordinator_cli_input(escrow_clone)).await;
 
-			match escrow_input_result {
-				Ok(EscrowWinner::Maker) => {
-					database
-						.resolve_escrow(&escrow.offer_id, &escrow.maker_id)
-						.await
-						.expect("Database failure, cannot resolve escrow. Restart coordinator.");
+			let winner_robohash_hex = match escrow_input_result {
+				Ok(EscrowWinner::Maker) => &escrow.maker_id,
+				Ok(EscrowWinner::Taker) => &escrow.taker_id,
+				_ => {
+					error!("Escrow resolving cli input error");
+					continue;
 				}
-				Ok(EscrowWinner::Taker) => {
-					database
-						.resolve_escrow(&escrow.offer_id, &escrow.taker_id)
-						.await
-						.expect("Database failure, cannot resolve escrow. Restart coordinator.");
-				}
-				_ => error!("Escrow resolving cli input error"),
-			}
+			};
+			database
+				.resolve_escrow(&escrow.offer_id, winner_robohash_hex)
+				.await
+				.expect("Database failure, cannot resolve escrow. Restart coordinator.");
+			coordinator.trade_events.publish(
+				&escrow.offer_id,
+				TradeEventKind::DisputeDecided {
+					winner_robohash_hex: winner_robohash_hex.clone(),
+				},
+			);
 		}
 		tokio::time::sleep(std::time::Duration::from_secs(5)).await;
 	}
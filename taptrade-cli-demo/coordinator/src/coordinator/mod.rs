@@ -1,12 +1,23 @@
 pub mod bond_monitoring;
+pub mod bond_punishment;
+pub mod chain_notifications;
+#[cfg(test)]
+mod coordinator_tests;
 pub mod coordinator_utils;
 pub mod escrow_cli;
 pub mod mempool_monitoring;
+pub mod offer_expiry;
+pub mod trade_events;
+pub mod trade_state;
 pub mod tx_confirmation_monitoring;
 // pub mod create_taproot;
 
 use super::*;
 
+/// number of invalid partial signatures a trader can submit for the keyspend payout
+/// before the trade is moved into escrow (dispute)
+const MAX_INVALID_PARTIAL_SIGS: i64 = 3;
+
 /// Accepts the request to create a new offer, inserts it in the database and
 /// returns the required bond information to the maker.
 pub async fn process_order(
@@ -64,6 +75,12 @@ pub async fn handle_maker_bond(
 		.await
 		.map_err(|e| BondError::InvalidBond(e.to_string()))?;
 	debug!("\nBond validation successful");
+
+	// check the escrow inputs exist on chain, are unspent and match the passed witness utxos
+	wallet
+		.validate_escrow_inputs(&payload.escrow_inputs)
+		.await
+		.map_err(|e| BondError::InvalidEscrowInputs(format!("{:#}", e)))?;
 	// generates a random offer id to be able to identify the offer
 	let offer_id_hex: String = generate_random_order_id(16); // 16 bytes random offer id, maybe a different system makes more sense later on? (uuid or increasing counter...)
 														 // create address for taker bond
@@ -129,6 +146,11 @@ pub async fn handle_taker_bond(
 
 	debug!("\nTaker bond validation successful");
 
+	wallet
+		.validate_escrow_inputs(&payload.trade_data.escrow_inputs)
+		.await
+		.map_err(|e| BondError::InvalidEscrowInputs(format!("{:#}", e)))?;
+
 	// create the escrow locking transaction
 	let escrow_output_data = wallet
 		.create_escrow_psbt(database, payload)
@@ -144,6 +166,9 @@ pub async fn handle_taker_bond(
 		.add_taker_info_and_move_table(payload, &escrow_output_data)
 		.await
 		.map_err(|e| BondError::CoordinatorError(e.to_string()))?;
+	coordinator
+		.trade_events
+		.publish(&payload.offer.offer_id_hex, TradeEventKind::Taken);
 
 	trace!("Taker information added to database and moved table successfully");
 	Ok(OfferTakenResponse {
@@ -152,9 +177,44 @@ pub async fn handle_taker_bond(
 		escrow_amount_maker_sat: escrow_output_data.escrow_amount_maker_sat,
 		escrow_amount_taker_sat: escrow_output_data.escrow_amount_taker_sat,
 		escrow_fee_sat_per_participant: escrow_output_data.escrow_fee_sat_per_participant,
+		musig_pubkey_compressed_hex_maker: escrow_output_data.musig_pubkey_compressed_hex_maker,
+		musig_pubkey_compressed_hex_taker: escrow_output_data.musig_pubkey_compressed_hex_taker,
 	})
 }
 
+/// verifies the cancellation request is signed with the taproot key of the maker and removes the offer
+/// from the orderbook. Bond monitoring only covers offers in the orderbook so the bond is released with it.
+pub async fn handle_offer_cancellation(
+	payload: &OfferCancellationRequest,
+	coordinator: Arc<Coordinator>,
+) -> Result<(), RequestError> {
+	let database = &coordinator.coordinator_db;
+
+	let taproot_pubkey_hex_maker = database
+		.fetch_maker_taproot_pubkey(&payload.offer_id_hex, &payload.robohash_hex)
+		.await
+		.map_err(|e| RequestError::Database(e.to_string()))?
+		.ok_or(RequestError::NotFound)?;
+
+	verify_offer_cancellation_signature(
+		&payload.offer_id_hex,
+		&taproot_pubkey_hex_maker,
+		&payload.cancellation_signature_hex,
+	)
+	.map_err(|e| RequestError::Unauthorized(format!("{:#}", e)))?;
+
+	// the offer could have been taken in the meantime
+	if !database
+		.delete_offer_from_orderbook(&payload.offer_id_hex, &payload.robohash_hex)
+		.await
+		.map_err(|e| RequestError::Database(e.to_string()))?
+	{
+		return Err(RequestError::NotFound);
+	}
+	info!("Offer {} cancelled by the maker", payload.offer_id_hex);
+	Ok(())
+}
+
 /// gets called by the polling endpoint the maker polls when waiting for an offer taker,
 /// looks in the database if escrow output information is available for the offer id
 /// which means the offer has been taken, returns the escrow locking tx information if
@@ -171,6 +231,8 @@ pub async fn get_offer_status_maker(
 		escrow_amount_taker_sat,
 		escrow_fee_sat_per_participant,
 		escrow_psbt_hex,
+		musig_pubkey_compressed_hex_maker,
+		musig_pubkey_compressed_hex_taker,
 		..
 	} = match database
 		.fetch_escrow_output_information(&payload.offer_id_hex)
@@ -190,6 +252,8 @@ pub async fn get_offer_status_maker(
 		escrow_amount_maker_sat,
 		escrow_amount_taker_sat,
 		escrow_fee_sat_per_participant,
+		musig_pubkey_compressed_hex_maker,
+		musig_pubkey_compressed_hex_taker,
 	})
 }
 
@@ -211,6 +275,17 @@ pub async fn fetch_escrow_confirmation_status(
 		Err(e) => return Err(FetchEscrowConfirmationError::Database(e.to_string())),
 	}
 
+	match database.fetch_trade_aborted_by(&payload.offer_id_hex).await {
+		Ok(Some(robohash_hex)) => {
+			return Err(FetchEscrowConfirmationError::TradeAborted(format!(
+				"Trade aborted, the bond of robot {} got published",
+				robohash_hex
+			)))
+		}
+		Ok(None) => (),
+		Err(e) => return Err(FetchEscrowConfirmationError::Database(e.to_string())),
+	}
+
 	database
 		.fetch_escrow_tx_confirmation_status(&payload.offer_id_hex)
 		.await
@@ -236,10 +311,33 @@ pub async fn handle_signed_escrow_psbt(
 		Err(e) => return Err(RequestError::Database(e.to_string())),
 	};
 
-	wallet
-		.validate_escrow_init_psbt(&payload.signed_psbt_hex)
+	// don't broadcast the escrow transaction of a trade aborted because of a bond violation
+	match database.fetch_trade_aborted_by(&payload.offer_id_hex).await {
+		Ok(Some(robohash_hex)) => {
+			return Err(RequestError::TradeAborted(format!(
+				"Trade aborted, the bond of robot {} got published",
+				robohash_hex
+			)))
+		}
+		Ok(None) => (),
+		Err(e) => return Err(RequestError::Database(e.to_string())),
+	}
+
+	let (unsigned_escrow_psbt, trader_escrow_inputs) = database
+		.fetch_escrow_psbt_and_trader_inputs(&payload.offer_id_hex, &payload.robohash_hex)
 		.await
-		.map_err(|e| RequestError::PsbtInvalid(e.to_string()))?;
+		.map_err(|e| RequestError::Database(e.to_string()))?;
+	let trader_inputs: Vec<OutPoint> = escrow_inputs_to_bdk_input(&trader_escrow_inputs)
+		.map_err(|e| RequestError::CoordinatorError(e.to_string()))?
+		.iter()
+		.map(|input| input.utxo)
+		.collect();
+	validate_escrow_init_psbt(
+		&payload.signed_psbt_hex,
+		&unsigned_escrow_psbt,
+		&trader_inputs,
+	)
+	.map_err(|e| RequestError::PsbtInvalid(format!("{:#}", e)))?;
 
 	match database.insert_signed_escrow_psbt(payload).await {
 		Ok(false) => return Err(RequestError::PsbtAlreadySubmitted),
@@ -261,6 +359,9 @@ pub async fn handle_signed_escrow_psbt(
 		.combine_and_broadcast_escrow_psbt(&maker_psbt, &taker_psbt)
 		.await
 		.map_err(|e| RequestError::PsbtInvalid(e.to_string()))?;
+	coordinator
+		.trade_events
+		.publish(&payload.offer_id_hex, TradeEventKind::EscrowBroadcast);
 
 	Ok(())
 }
@@ -277,6 +378,24 @@ pub async fn handle_obligation_confirmation(
 		.set_trader_happy_field(&payload.offer_id_hex, &payload.robohash_hex, true)
 		.await
 		.map_err(|e| RequestError::Database(e.to_string()))?;
+	coordinator.trade_events.publish(
+		&payload.offer_id_hex,
+		TradeEventKind::TraderHappy {
+			robohash_hex: payload.robohash_hex.clone(),
+		},
+	);
+
+	let trader_happiness = database
+		.fetch_trader_happiness(&payload.offer_id_hex)
+		.await
+		.map_err(|e| RequestError::Database(e.to_string()))?;
+	if trader_happiness.maker_happy.is_some_and(|x| x)
+		&& trader_happiness.taker_happy.is_some_and(|x| x)
+	{
+		coordinator
+			.trade_events
+			.publish(&payload.offer_id_hex, TradeEventKind::PayoutReady);
+	}
 	Ok(())
 }
 
@@ -293,6 +412,12 @@ pub async fn initiate_escrow(
 		.set_trader_happy_field(&payload.offer_id_hex, &payload.robohash_hex, false)
 		.await
 		.map_err(|e| RequestError::Database(e.to_string()))?;
+	coordinator.trade_events.publish(
+		&payload.offer_id_hex,
+		TradeEventKind::EscrowRequested {
+			robohash_hex: payload.robohash_hex.clone(),
+		},
+	);
 
 	Ok(())
 }
@@ -315,7 +440,10 @@ pub async fn handle_final_payout(
 		&& trader_happiness.taker_happy.is_some_and(|x| x)
 	{
 		let escrow_payout_data = database
-			.fetch_payout_data(&payload.offer_id_hex)
+			.fetch_payout_data(
+				&payload.offer_id_hex,
+				coordinator.coordinator_wallet.network,
+			)
 			.await
 			.map_err(|e| RequestError::Database(e.to_string()))?;
 
@@ -326,27 +454,49 @@ pub async fn handle_final_payout(
 		{
 			payout_psbt_hex
 		} else {
+			// only one request assembles the payout, the others are told to poll again
 			if !database
-				.toggle_processing(&payload.offer_id_hex)
+				.transition_trade_state(
+					&payload.offer_id_hex,
+					TradeState::EscrowConfirmed,
+					TradeState::KeyspendPayoutProcessing,
+				)
 				.await
 				.map_err(|e| RequestError::Database(e.to_string()))?
 			{
 				return Ok(PayoutProcessingResult::NotReady);
 			}
-			let payout_keyspend_psbt_hex = coordinator
+			let payout_keyspend_psbt_hex = match coordinator
 				.coordinator_wallet
 				.assemble_keyspend_payout_psbt(&escrow_payout_data)
 				.await
-				.map_err(|e| RequestError::CoordinatorError(e.to_string()))?;
-
-			database
+			{
+				Ok(psbt_hex) => psbt_hex,
+				Err(e) => {
+					// roll back so the payout can be assembled again on the next request
+					database
+						.transition_trade_state(
+							&payload.offer_id_hex,
+							TradeState::KeyspendPayoutProcessing,
+							TradeState::EscrowConfirmed,
+						)
+						.await
+						.map_err(|e| RequestError::Database(e.to_string()))?;
+					return Err(RequestError::CoordinatorError(e.to_string()));
+				}
+			};
+
+			// the trade could have been disputed or aborted while the payout was assembled
+			if !database
 				.insert_keyspend_payout_psbt(&payload.offer_id_hex, &payout_keyspend_psbt_hex)
 				.await
-				.map_err(|e| RequestError::Database(e.to_string()))?;
-			database
-				.toggle_processing(&payload.offer_id_hex)
-				.await
-				.map_err(|e| RequestError::Database(e.to_string()))?;
+				.map_err(|e| RequestError::Database(e.to_string()))?
+			{
+				return Err(RequestError::TradeStateConflict(format!(
+					"Trade of offer {} left the keyspend payout processing state",
+					payload.offer_id_hex
+				)));
+			}
 			payout_keyspend_psbt_hex
 		};
 		return Ok(PayoutProcessingResult::ReadyPSBT(PayoutResponse {
@@ -354,12 +504,8 @@ pub async fn handle_final_payout(
 			agg_musig_nonce_hex: escrow_payout_data.agg_musig_nonce.to_string(),
 			agg_musig_pubkey_ctx_hex: escrow_payout_data.aggregated_musig_pubkey_ctx_hex,
 		}));
-	// at least one trader has not yet submitted the satisfaction request, or a escrow is already ongoing
-	} else if (trader_happiness.maker_happy.is_none() || trader_happiness.taker_happy.is_none())
-		&& !trader_happiness.escrow_ongoing
-	{
-		return Ok(PayoutProcessingResult::NotReady);
 	}
+
 	// if one of them is not happy
 	// open escrow cli on coordinator to decide who will win (chat/dispute is out of scope for this demo)
 	// once decided who will win assemble the correct payout psbt and return it to the according trader
@@ -371,14 +517,75 @@ pub async fn handle_final_payout(
 		Err(e) => return Err(RequestError::Database(e.to_string())),
 	};
 
+	// at least one trader has not yet submitted the satisfaction request and no escrow is ongoing
+	// (resolving an escrow moves the trade out of the disputed state, so the winner has to be checked too)
+	if (trader_happiness.maker_happy.is_none() || trader_happiness.taker_happy.is_none())
+		&& trader_happiness.trade_state != TradeState::Disputed
+		&& potential_escrow_winner.is_none()
+	{
+		return Ok(PayoutProcessingResult::NotReady);
+	}
+
 	if let Some(escrow_winner) = potential_escrow_winner {
 		if escrow_winner == payload.robohash_hex {
-			panic!("Implement wallet.assemble_script_payout_psbt()");
-		// let script_payout_psbt_hex = wallet
-		// 	.assemble_script_payout_psbt(&payload.offer_id_hex, &payload.robohash_hex, is_maker_bool)
-		// 	.await
-		// 	.context("Error assembling payout PSBT")?;
-		// return Ok(PayoutProcessingResult::ReadyPSBT(script_payout_psbt_hex));
+			let script_payout_psbt_hex = if let Some(script_payout_psbt_hex) = database
+				.fetch_script_payout_psbt(&payload.offer_id_hex)
+				.await
+				.map_err(|e| RequestError::Database(e.to_string()))?
+			{
+				script_payout_psbt_hex
+			} else {
+				if !database
+					.transition_trade_state(
+						&payload.offer_id_hex,
+						TradeState::EscrowResolved,
+						TradeState::ScriptPayoutProcessing,
+					)
+					.await
+					.map_err(|e| RequestError::Database(e.to_string()))?
+				{
+					return Ok(PayoutProcessingResult::NotReady);
+				}
+				let script_payout_psbt_hex = match assemble_script_payout(
+					&payload.offer_id_hex,
+					&escrow_winner,
+					&coordinator,
+				)
+				.await
+				{
+					Ok(psbt_hex) => psbt_hex,
+					Err(e) => {
+						// roll back so the payout can be assembled again on the next request
+						database
+							.transition_trade_state(
+								&payload.offer_id_hex,
+								TradeState::ScriptPayoutProcessing,
+								TradeState::EscrowResolved,
+							)
+							.await
+							.map_err(|e| RequestError::Database(e.to_string()))?;
+						return Err(e);
+					}
+				};
+
+				// the trade could have been aborted while the payout was assembled
+				if !database
+					.insert_script_payout_psbt(&payload.offer_id_hex, &script_payout_psbt_hex)
+					.await
+					.map_err(|e| RequestError::Database(e.to_string()))?
+				{
+					return Err(RequestError::TradeStateConflict(format!(
+						"Trade of offer {} left the script payout processing state",
+						payload.offer_id_hex
+					)));
+				}
+				script_payout_psbt_hex
+			};
+			Ok(PayoutProcessingResult::ReadyScriptPSBT(
+				ScriptPayoutResponse {
+					script_payout_psbt_hex,
+				},
+			))
 		} else {
 			// this will be returned to the losing trader
 			Ok(PayoutProcessingResult::LostEscrow)
@@ -390,6 +597,28 @@ pub async fn handle_final_payout(
 	}
 }
 
+/// fetches the script payout data of the escrow winner and assembles the script path payout psbt
+async fn assemble_script_payout(
+	offer_id_hex: &str,
+	escrow_winner: &str,
+	coordinator: &Coordinator,
+) -> Result<String, RequestError> {
+	let script_payout_data = coordinator
+		.coordinator_db
+		.fetch_script_payout_data(
+			offer_id_hex,
+			escrow_winner,
+			coordinator.coordinator_wallet.network,
+		)
+		.await
+		.map_err(|e| RequestError::Database(e.to_string()))?;
+	coordinator
+		.coordinator_wallet
+		.assemble_script_payout_psbt(&script_payout_data)
+		.await
+		.map_err(|e| RequestError::CoordinatorError(e.to_string()))
+}
+
 /// handles the returned partial signatures for the keyspend payout, if both are available it aggregates them,
 /// inserts the signature in the payout tx and broadcasts it
 pub async fn handle_payout_signature(
@@ -399,6 +628,43 @@ pub async fn handle_payout_signature(
 	let database = &coordinator.coordinator_db;
 	check_offer_and_confirmation(&payload.offer_id_hex, &payload.robohash_hex, database).await?;
 
+	// verify the partial signature with the individual pubkey and nonce of the submitting trader
+	// so we can blame the trader before aggregating the signatures
+	let verification_data = database
+		.fetch_partial_sig_verification_data(&payload.offer_id_hex, &payload.robohash_hex)
+		.await
+		.map_err(|e| RequestError::Database(e.to_string()))?
+		.ok_or(RequestError::CoordinatorError(
+			"Keyspend payout psbt has not been assembled yet".to_string(),
+		))?;
+	if let Err(e) = verification_data.verify(&payload.partial_sig_hex) {
+		let invalid_sig_count = database
+			.record_invalid_partial_sig(&payload.offer_id_hex, &payload.robohash_hex)
+			.await
+			.map_err(|e| RequestError::Database(e.to_string()))?;
+		warn!(
+			"Trader {} submitted invalid partial signature #{} for offer {}: {:#}",
+			payload.robohash_hex, invalid_sig_count, payload.offer_id_hex, e
+		);
+		if invalid_sig_count >= MAX_INVALID_PARTIAL_SIGS {
+			warn!(
+				"Too many invalid partial signatures, moving offer {} into escrow",
+				payload.offer_id_hex
+			);
+			database
+				.set_trader_happy_field(&payload.offer_id_hex, &payload.robohash_hex, false)
+				.await
+				.map_err(|e| RequestError::Database(e.to_string()))?;
+			coordinator.trade_events.publish(
+				&payload.offer_id_hex,
+				TradeEventKind::EscrowRequested {
+					robohash_hex: payload.robohash_hex.clone(),
+				},
+			);
+		}
+		return Err(RequestError::PartialSigInvalid(format!("{:#}", e)));
+	}
+
 	database
 		.insert_partial_sig(
 			&payload.partial_sig_hex,
@@ -420,10 +686,9 @@ pub async fn handle_payout_signature(
 	debug!("Keyspend info: {:?}", keyspend_information);
 	trace!(
 		"Keyspend agg sig : {} \n Agg pubk: {}",
-		keyspend_information.agg_sig.to_string(),
-		keyspend_information.agg_keyspend_pk.to_string()
+		keyspend_information.agg_sig,
+		keyspend_information.agg_keyspend_pk
 	);
-	warn!("Use musig2 validate partial sig to validate sigs before using to blame users providing wrong sigs");
 	coordinator
 		.coordinator_wallet
 		.broadcast_keyspend_tx(&keyspend_information)
@@ -0,0 +1,36 @@
+// removes offers from the orderbook once their offer duration passed and maker requests whose bond never arrived.
+// Removing an expired offer from the orderbook also releases the bond from monitoring and the maker can reuse
+// the bond inputs.
+use super::*;
+
+/// time a maker has to submit the bond after requesting a new offer
+const MAKER_BOND_TIMEOUT_SECS: u64 = 3600;
+
+/// this function periodically removes expired offers and stale maker requests from the database
+pub async fn remove_expired_offers(coordinator: Arc<Coordinator>) -> Result<()> {
+	let coordinator_db = Arc::clone(&coordinator.coordinator_db);
+
+	loop {
+		// sleep for a while
+		tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+		let now_ts = unix_timestamp_now()?;
+
+		let expired_offer_ids = coordinator_db.delete_expired_offers(now_ts).await?;
+		for offer_id in expired_offer_ids {
+			info!(
+				"Offer {} expired, removed from orderbook and released bond from monitoring",
+				offer_id
+			);
+		}
+
+		let removed_requests = coordinator_db
+			.delete_stale_maker_requests(now_ts, MAKER_BOND_TIMEOUT_SECS)
+			.await?;
+		if removed_requests > 0 {
+			debug!(
+				"Removed {} maker requests without submitted bond",
+				removed_requests
+			);
+		}
+	}
+}
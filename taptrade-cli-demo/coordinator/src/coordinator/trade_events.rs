@@ -0,0 +1,119 @@
+// Progress events of the trades, pushed to the traders over the /trade-events server sent events stream
+// so they don't have to poll the coordinator. The events only signal that something changed, the traders
+// still fetch the data from the usual endpoints (and fall back to polling if the stream is lost).
+use super::*;
+use axum::response::sse::Event;
+use futures_util::Stream;
+use std::convert::Infallible;
+use tokio::sync::broadcast;
+
+/// number of events buffered for slow subscribers before they lag behind
+const TRADE_EVENT_BUFFER: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TradeEventKind {
+	// the offer of the maker got taken, the escrow psbt can be fetched
+	Taken,
+	// both traders signed the escrow psbt and it got broadcast
+	EscrowBroadcast,
+	// the escrow locking transaction is confirmed, the fiat exchange can begin
+	EscrowConfirmed,
+	// a trader confirmed that its trade obligations are satisfied
+	TraderHappy { robohash_hex: String },
+	// both traders are happy, the keyspend payout can be requested
+	PayoutReady,
+	// a trader requested escrow, the coordinator has to decide the dispute
+	EscrowRequested { robohash_hex: String },
+	// the coordinator decided the dispute, the winner can request the script payout
+	DisputeDecided { winner_robohash_hex: String },
+	// a trader violated its bond, the trade won't continue
+	Aborted { violating_robohash_hex: String },
+}
+
+impl TradeEventKind {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			TradeEventKind::Taken => "taken",
+			TradeEventKind::EscrowBroadcast => "escrow_broadcast",
+			TradeEventKind::EscrowConfirmed => "escrow_confirmed",
+			TradeEventKind::TraderHappy { .. } => "trader_happy",
+			TradeEventKind::PayoutReady => "payout_ready",
+			TradeEventKind::EscrowRequested { .. } => "escrow_requested",
+			TradeEventKind::DisputeDecided { .. } => "dispute_decided",
+			TradeEventKind::Aborted { .. } => "aborted",
+		}
+	}
+
+	fn to_sse_event(&self) -> Event {
+		Event::default()
+			.event(self.as_str())
+			.json_data(self)
+			.unwrap_or_else(|_| Event::default().event(self.as_str()))
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct TradeEvent {
+	pub offer_id_hex: String,
+	pub kind: TradeEventKind,
+}
+
+/// fans out the trade events to all subscribed event streams
+pub struct TradeEvents {
+	sender: broadcast::Sender<TradeEvent>,
+}
+
+impl TradeEvents {
+	pub fn new() -> Self {
+		Self {
+			sender: broadcast::channel(TRADE_EVENT_BUFFER).0,
+		}
+	}
+
+	/// publishes an event of the trade, dropped if no trader is subscribed
+	pub fn publish(&self, offer_id_hex: &str, kind: TradeEventKind) {
+		trace!("Trade event for offer {}: {:?}", offer_id_hex, kind);
+		let _ = self.sender.send(TradeEvent {
+			offer_id_hex: offer_id_hex.to_string(),
+			kind,
+		});
+	}
+
+	/// returns the receiver of all trade events, used by subscribe() and the tests
+	pub fn receiver(&self) -> broadcast::Receiver<TradeEvent> {
+		self.sender.subscribe()
+	}
+
+	/// stream of the server sent events of a single trade. If the subscriber lags behind a lagged
+	/// event is sent so the trader fetches the current state from the polling endpoints.
+	pub fn subscribe(
+		&self,
+		offer_id_hex: &str,
+	) -> impl Stream<Item = Result<Event, Infallible>> + Send + 'static {
+		let offer_id_hex = offer_id_hex.to_string();
+		futures_util::stream::unfold(self.receiver(), move |mut receiver| {
+			let offer_id_hex = offer_id_hex.clone();
+			async move {
+				loop {
+					match receiver.recv().await {
+						Ok(event) if event.offer_id_hex == offer_id_hex => {
+							return Some((Ok(event.kind.to_sse_event()), receiver))
+						}
+						Ok(_) => continue,
+						Err(broadcast::error::RecvError::Lagged(_)) => {
+							return Some((Ok(Event::default().event("lagged")), receiver))
+						}
+						Err(broadcast::error::RecvError::Closed) => return None,
+					}
+				}
+			}
+		})
+	}
+}
+
+impl Default for TradeEvents {
+	fn default() -> Self {
+		Self::new()
+	}
+}
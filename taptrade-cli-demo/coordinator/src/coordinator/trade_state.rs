@@ -0,0 +1,123 @@
+use super::*;
+
+/// the state of a taken offer (trade), stored in the trade_state column of the taken_offers table.
+/// Transitions are enforced by the CoordinatorDB so a trade can only move forward or roll back
+/// an interrupted processing step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeState {
+	// the offer got taken, waiting for the signed escrow psbts and the escrow tx confirmation
+	EscrowPending,
+	// the escrow locking tx is confirmed, the traders exchange fiat
+	EscrowConfirmed,
+	// both traders are happy, the coordinator is assembling the keyspend payout psbt
+	KeyspendPayoutProcessing,
+	// the keyspend payout psbt is stored, waiting for the partial signatures of the traders
+	KeyspendPayoutReady,
+	// a trader requested escrow, waiting for the coordinator to decide the winner
+	Disputed,
+	// the coordinator decided the escrow winner
+	EscrowResolved,
+	// the coordinator is assembling the script path payout psbt for the escrow winner
+	ScriptPayoutProcessing,
+	// the script path payout psbt is stored and can be fetched by the escrow winner
+	ScriptPayoutReady,
+	// a trader violated its bond, the trade won't continue
+	Aborted,
+}
+
+impl TradeState {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			TradeState::EscrowPending => "escrow_pending",
+			TradeState::EscrowConfirmed => "escrow_confirmed",
+			TradeState::KeyspendPayoutProcessing => "keyspend_payout_processing",
+			TradeState::KeyspendPayoutReady => "keyspend_payout_ready",
+			TradeState::Disputed => "disputed",
+			TradeState::EscrowResolved => "escrow_resolved",
+			TradeState::ScriptPayoutProcessing => "script_payout_processing",
+			TradeState::ScriptPayoutReady => "script_payout_ready",
+			TradeState::Aborted => "aborted",
+		}
+	}
+
+	/// returns true if a trade in this state is allowed to move into the next state.
+	/// The processing states can roll back to the state they started from if the payout
+	/// assembly failed or the coordinator restarted in between.
+	pub fn can_transition_to(&self, next: TradeState) -> bool {
+		use TradeState::*;
+		match (self, next) {
+			// a bond violation aborts the trade in any state
+			(Aborted, _) => false,
+			(_, Aborted) => true,
+			(EscrowPending, EscrowConfirmed) => true,
+			(EscrowConfirmed, KeyspendPayoutProcessing | Disputed) => true,
+			(KeyspendPayoutProcessing, KeyspendPayoutReady | EscrowConfirmed | Disputed) => true,
+			(KeyspendPayoutReady, Disputed) => true,
+			(Disputed, EscrowResolved) => true,
+			(EscrowResolved, ScriptPayoutProcessing) => true,
+			(ScriptPayoutProcessing, ScriptPayoutReady | EscrowResolved) => true,
+			_ => false,
+		}
+	}
+}
+
+impl FromStr for TradeState {
+	type Err = anyhow::Error;
+
+	fn from_str(state: &str) -> Result<Self> {
+		match state {
+			"escrow_pending" => Ok(TradeState::EscrowPending),
+			"escrow_confirmed" => Ok(TradeState::EscrowConfirmed),
+			"keyspend_payout_processing" => Ok(TradeState::KeyspendPayoutProcessing),
+			"keyspend_payout_ready" => Ok(TradeState::KeyspendPayoutReady),
+			"disputed" => Ok(TradeState::Disputed),
+			"escrow_resolved" => Ok(TradeState::EscrowResolved),
+			"script_payout_processing" => Ok(TradeState::ScriptPayoutProcessing),
+			"script_payout_ready" => Ok(TradeState::ScriptPayoutReady),
+			"aborted" => Ok(TradeState::Aborted),
+			_ => Err(anyhow!("Invalid trade state: {}", state)),
+		}
+	}
+}
+
+impl fmt::Display for TradeState {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.as_str())
+	}
+}
+
+/// runs once on startup before the api server accepts requests. Payouts interrupted by a crash
+/// are resumed if their psbt got stored, otherwise rolled back so the traders can request them again.
+/// Escrow transactions of pending trades that have both signed psbts are broadcast again in case
+/// the coordinator stopped before broadcasting them.
+pub async fn recover_in_flight_trades(coordinator: Arc<Coordinator>) -> Result<()> {
+	let database = &coordinator.coordinator_db;
+
+	for (offer_id, state) in database.recover_interrupted_payouts().await? {
+		info!(
+			"Recovered interrupted payout of offer {}, now {}",
+			offer_id, state
+		);
+	}
+
+	for offer_id in database
+		.fetch_trades_in_state(TradeState::EscrowPending)
+		.await?
+	{
+		if let Some((maker_psbt, taker_psbt)) =
+			database.fetch_both_signed_escrow_psbts(&offer_id).await?
+		{
+			if let Err(e) = coordinator
+				.coordinator_wallet
+				.combine_and_broadcast_escrow_psbt(&maker_psbt, &taker_psbt)
+				.await
+			{
+				debug!(
+					"Rebroadcasting escrow tx of offer {} failed (probably already known): {:#}",
+					offer_id, e
+				);
+			}
+		}
+	}
+	Ok(())
+}
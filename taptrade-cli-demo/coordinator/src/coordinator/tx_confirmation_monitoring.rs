@@ -1,6 +1,6 @@
 use super::*;
 
-/// fetches confirmations of the txids in unconfirmed_txids using core rpc
+/// fetches confirmations of the txids in unconfirmed_txids using the chain backend
 fn get_confirmations(
 	unconfirmed_txids: Vec<String>,
 	coordinator: Arc<Coordinator>,
@@ -8,45 +8,22 @@ fn get_confirmations(
 	let mut now_confirmed_txs = Vec::new();
 	for txid in unconfirmed_txids {
 		let txid_struct = Txid::from_str(&txid)?;
-		let tx_info = match coordinator
+		let confirmations = match coordinator
 			.coordinator_wallet
-			.json_rpc_client
-			.as_ref()
-			.get_raw_transaction_info(&txid_struct, None)
+			.chain_backend
+			.get_confirmations(&txid_struct)
 		{
-			Ok(tx_info) => tx_info,
-			Err(e) => match e {
-				CoreRpcError::JsonRpc(e) => {
-					if let JsonRpcError::Rpc(rpc_error) = e {
-						if rpc_error.code == -5 {
-							trace!("Escrow transaction {} not yet found in mempool", &txid);
-							continue;
-						} else {
-							return Err(anyhow!(
-								"Error fetching transaction info for {}: {:?}",
-								&txid,
-								rpc_error
-							));
-						}
-					} else {
-						return Err(anyhow!(
-							"Error fetching transaction info for {}: {:?}",
-							&txid,
-							e
-						));
-					}
-				}
-				_ => {
-					error!("Error fetching transaction info for {}: {:?}", &txid, e);
-					return Err(anyhow!(
-						"Error fetching transaction info for {}: {:?}",
-						&txid,
-						e
-					));
-				}
-			},
+			Ok(Some(confirmations)) => confirmations,
+			Ok(None) => {
+				trace!("Escrow transaction {} not yet found in mempool", &txid);
+				continue;
+			}
+			Err(e) => {
+				error!("Error fetching transaction info for {}: {:?}", &txid, e);
+				return Err(e);
+			}
 		};
-		if let Some(confirmations) = tx_info.confirmations {
+		if confirmations > 0 {
 			debug!(
 				"Transaction {} is now confirmed with {} confirmations",
 				&txid, confirmations
@@ -60,11 +37,15 @@ fn get_confirmations(
 }
 
 /// pulls txids of unconfirmed escrow transactions from the database, checks
-/// for confirmations using the bitcoin core rpc api and updates the database
+/// for confirmations using the chain backend and updates the database
 /// entries with the confirmation
 pub async fn update_transaction_confirmations(coordinator: Arc<Coordinator>) {
+	let mut chain_events = coordinator.chain_notifications.subscribe();
 	loop {
-		tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+		// sleep for a while or until a new block arrives
+		chain_events
+			.wait_for_block(std::time::Duration::from_secs(30))
+			.await;
 		trace!("Checking for transaction confirmations");
 		let unconfirmed_transactions = match coordinator
 			.coordinator_db
@@ -82,7 +63,7 @@ pub async fn update_transaction_confirmations(coordinator: Arc<Coordinator>) {
 			continue;
 		}
 		let coordinator_clone = Arc::clone(&coordinator);
-		// spawn blocking because the core rpc call is blocking
+		// spawn blocking because the chain backend calls are blocking
 		let newly_confirmed_txids = match tokio::task::spawn_blocking(move || {
 			get_confirmations(unconfirmed_transactions, coordinator_clone)
 		})
@@ -101,12 +82,19 @@ pub async fn update_transaction_confirmations(coordinator: Arc<Coordinator>) {
 			}
 		};
 		if !newly_confirmed_txids.is_empty() {
-			if let Err(e) = coordinator
+			match coordinator
 				.coordinator_db
 				.confirm_bond_txids(newly_confirmed_txids)
 				.await
 			{
-				error!("Error updating bond confirmations in db: {:?}", e);
+				Ok(confirmed_offers) => {
+					for offer_id in confirmed_offers {
+						coordinator
+							.trade_events
+							.publish(&offer_id, TradeEventKind::EscrowConfirmed);
+					}
+				}
+				Err(e) => error!("Error updating bond confirmations in db: {:?}", e),
 			}
 		}
 	}
@@ -0,0 +1,29 @@
+-- brings a database created by the coordinator before the schema was versioned and before trades
+-- got an explicit trade state to the baseline schema of migration 0001. Only executed if taken_offers
+-- still contains the escrow_ongoing and processing flags.
+
+-- requests awaiting a bond are short lived and can't be completed by traders of this protocol version,
+-- the table is recreated by 0001 with the request creation timestamp used for their expiry
+DROP TABLE maker_requests;
+
+ALTER TABLE taken_offers ADD COLUMN escrow_inputs_hex_maker_csv TEXT;
+ALTER TABLE taken_offers ADD COLUMN escrow_inputs_hex_taker_csv TEXT;
+ALTER TABLE taken_offers ADD COLUMN script_payout_psbt_hex TEXT;
+ALTER TABLE taken_offers ADD COLUMN invalid_partial_sigs_maker INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE taken_offers ADD COLUMN invalid_partial_sigs_taker INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE taken_offers ADD COLUMN trade_aborted_by_robohash TEXT;
+ALTER TABLE taken_offers ADD COLUMN trade_state TEXT NOT NULL DEFAULT 'escrow_pending';
+
+-- processing was the lock held while the keyspend payout got assembled, trades still holding it
+-- are rolled back or moved forward by the startup recovery of interrupted payouts
+UPDATE taken_offers SET trade_state = CASE
+	WHEN escrow_winner_robohash IS NOT NULL THEN 'escrow_resolved'
+	WHEN escrow_ongoing = 1 THEN 'disputed'
+	WHEN processing = 1 THEN 'keyspend_payout_processing'
+	WHEN payout_transaction_psbt_hex IS NOT NULL THEN 'keyspend_payout_ready'
+	WHEN escrow_psbt_is_confirmed = 1 THEN 'escrow_confirmed'
+	ELSE 'escrow_pending'
+END;
+
+ALTER TABLE taken_offers DROP COLUMN escrow_ongoing;
+ALTER TABLE taken_offers DROP COLUMN processing;
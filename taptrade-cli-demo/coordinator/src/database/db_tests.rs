@@ -24,6 +24,183 @@ async fn test_init() -> Result<()> {
 	Ok(())
 }
 
+// creates a database file from the fixture sql, without running the migrations
+async fn create_fixture_database(db_path: &std::path::Path, fixture_sql: &str) -> Result<()> {
+	let connection_string = format!("sqlite:{}?mode=rwc", db_path.display());
+	let db_pool = SqlitePoolOptions::new().connect(&connection_string).await?;
+	sqlx::raw_sql(fixture_sql).execute(&db_pool).await?;
+	db_pool.close().await;
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_unversioned_database() -> Result<()> {
+	let db_dir = tempfile::tempdir()?;
+	let db_path = db_dir.path().join("coordinator.db");
+	create_fixture_database(&db_path, include_str!("fixtures/unversioned_db.sql")).await?;
+
+	let database = CoordinatorDB::open(db_path.to_str().unwrap()).await?;
+	assert_eq!(database.schema_version().await?, 2);
+
+	// the escrow input columns got renamed, offers with csv encoded inputs are removed from the orderbook
+	let escrow_inputs: String = sqlx::query_scalar(
+		"SELECT escrow_inputs_maker_json FROM active_maker_offers WHERE offer_id = 'json_offer'",
+	)
+	.fetch_one(&*database.db_pool)
+	.await?;
+	assert_eq!(escrow_inputs, "[]");
+	assert!(database
+		.fetch_taker_bond_requirements("active_offer")
+		.await
+		.is_err());
+
+	// trades still waiting for a signed escrow psbt can't validate it without the inputs, trades with
+	// both signed escrow psbts continue without them
+	assert!(database
+		.fetch_trade_state("csv_unsigned_offer")
+		.await
+		.is_err());
+	let escrow_inputs: (Option<String>, Option<String>) = sqlx::query_as(
+		"SELECT escrow_inputs_maker_json, escrow_inputs_taker_json FROM taken_offers WHERE offer_id = 'csv_signed_offer'",
+	)
+	.fetch_one(&*database.db_pool)
+	.await?;
+	assert_eq!(escrow_inputs, (None, None));
+
+	// the other trades survive the migration and can continue
+	let bond_requirements = database
+		.fetch_bond_requirements(&"a3f1".to_string())
+		.await?;
+	assert_eq!(bond_requirements.bond_address, "1BondAddress");
+	assert_eq!(
+		database
+			.fetch_taker_bond_requirements("json_offer")
+			.await?
+			.locking_amount_sat,
+		600
+	);
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::EscrowPending
+	);
+	database
+		.confirm_bond_txids(vec!["escrowPsbtTxid".to_string()])
+		.await?;
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::EscrowConfirmed
+	);
+	drop(database);
+
+	// opening an already migrated database again doesn't change it
+	let database = CoordinatorDB::open(db_path.to_str().unwrap()).await?;
+	assert_eq!(database.schema_version().await?, 2);
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::EscrowConfirmed
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_migrate_legacy_database() -> Result<()> {
+	let db_dir = tempfile::tempdir()?;
+	let db_path = db_dir.path().join("coordinator.db");
+	create_fixture_database(&db_path, include_str!("fixtures/legacy_db.sql")).await?;
+
+	let database = CoordinatorDB::open(db_path.to_str().unwrap()).await?;
+	assert_eq!(database.schema_version().await?, 2);
+
+	// the escrow_ongoing and processing flags are mapped to the trade state
+	for (offer_id, trade_state) in [
+		("pending_offer", TradeState::EscrowPending),
+		("confirmed_offer", TradeState::EscrowConfirmed),
+		("processing_offer", TradeState::KeyspendPayoutProcessing),
+		("disputed_offer", TradeState::Disputed),
+		("resolved_offer", TradeState::EscrowResolved),
+	] {
+		assert_eq!(database.fetch_trade_state(offer_id).await?, trade_state);
+	}
+	assert_eq!(database.get_open_escrows().await?.len(), 1);
+	assert_eq!(
+		database.fetch_escrow_result("resolved_offer").await?,
+		Some("b2b2".to_string())
+	);
+
+	// the payout interrupted by the upgrade is resumed with the stored payout psbt
+	assert_eq!(
+		database.recover_interrupted_payouts().await?,
+		vec![(
+			"processing_offer".to_string(),
+			TradeState::KeyspendPayoutReady
+		)]
+	);
+
+	// the trades continue with the columns added after the legacy schema
+	database
+		.confirm_bond_txids(vec!["pendingEscrowTxid".to_string()])
+		.await?;
+	assert_eq!(
+		database.fetch_trade_state("pending_offer").await?,
+		TradeState::EscrowConfirmed
+	);
+	assert_eq!(
+		database
+			.record_invalid_partial_sig("confirmed_offer", "a1a1")
+			.await?,
+		1
+	);
+	assert_eq!(
+		database.fetch_trade_aborted_by("confirmed_offer").await?,
+		None
+	);
+
+	// the removed flags don't block inserting new trades, legacy maker requests and the orderbook
+	// offers with csv escrow inputs are dropped
+	insert_taken_offer(&database, "new_offer").await?;
+	assert!(database
+		.fetch_bond_requirements(&"a3f1".to_string())
+		.await
+		.is_err());
+	assert!(database
+		.fetch_taker_bond_requirements("active_offer")
+		.await
+		.is_err());
+	drop(database);
+
+	// opening the adopted database again doesn't change it
+	let database = CoordinatorDB::open(db_path.to_str().unwrap()).await?;
+	assert_eq!(database.schema_version().await?, 2);
+	assert_eq!(
+		database.fetch_trade_state("disputed_offer").await?,
+		TradeState::Disputed
+	);
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_refuse_newer_schema_version() -> Result<()> {
+	let db_dir = tempfile::tempdir()?;
+	let db_path = db_dir.path().join("coordinator.db");
+	let database = CoordinatorDB::open(db_path.to_str().unwrap()).await?;
+
+	// simulate a migration applied by a newer coordinator version
+	sqlx::query(
+		"INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+		VALUES (9999, 'from the future', 1, X'00', 0)",
+	)
+	.execute(&*database.db_pool)
+	.await?;
+	database.db_pool.close().await;
+
+	let result = CoordinatorDB::open(db_path.to_str().unwrap()).await;
+	assert!(result
+		.unwrap_err()
+		.to_string()
+		.contains("newer than the latest version"));
+	Ok(())
+}
+
 #[tokio::test]
 async fn test_insert_new_maker_request() -> Result<()> {
 	let database = create_coordinator().await?;
@@ -35,6 +212,7 @@ async fn test_insert_new_maker_request() -> Result<()> {
 		amount_satoshi: 1000,
 		bond_ratio: 50,
 		offer_duration_ts: 1234567890,
+		network: "regtest".to_string(),
 	};
 
 	let bond_requirement_response = BondRequirementResponse {
@@ -204,7 +382,18 @@ async fn test_move_offer_to_active() -> Result<()> {
 
 	// Create a sample BondSubmissionRequest
 	let bond_submission_request = BondSubmissionRequest {
-		bdk_psbt_inputs_hex_csv: "l33t,DEADBEEF".to_string(),
+		escrow_inputs: vec![EscrowInput {
+			outpoint: "8f2fcbba6e1b0e5b1ee2a3f5e1b5f3a7a3ad4bbad6b6a0f53c0f0d0e6c6e7b1a:0"
+				.to_string(),
+			witness_utxo: WitnessUtxo {
+				value_sat: 100000,
+				script_pubkey_hex:
+					"5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c"
+						.to_string(),
+			},
+			tap_internal_key_hex: None,
+			tap_key_origins: vec![],
+		}],
 		client_change_address: "tb1p5yh969z6fgatg0mvcyvggd08fujnat8890vcdud277q06rr9xgmqwfdkcx"
 			.to_string(),
 		robohash_hex: robohash_hex.to_string(),
@@ -247,6 +436,18 @@ async fn test_move_offer_to_active() -> Result<()> {
 	assert_eq!(active_offer.10, "musigPubNonceHex".to_string());
 	assert_eq!(active_offer.11, "musigPubkeyHex".to_string());
 
+	// the escrow inputs are stored as json
+	let escrow_inputs_json: String = sqlx::query_scalar(
+		"SELECT escrow_inputs_maker_json FROM active_maker_offers WHERE offer_id = ?",
+	)
+	.bind("sample_offer_id")
+	.fetch_one(&*database.db_pool)
+	.await?;
+	assert_eq!(
+		serde_json::from_str::<Vec<EscrowInput>>(&escrow_inputs_json)?,
+		bond_submission_request.escrow_inputs
+	);
+
 	Ok(())
 }
 
@@ -294,7 +495,7 @@ async fn test_move_offer_to_active() -> Result<()> {
 // 	for offer in offers {
 // 		sqlx::query(
 //         "INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts, bond_address, bond_amount_sat,
-//         bond_tx_hex, payout_address, change_address_maker, escrow_inputs_hex_maker_csv, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
+//         bond_tx_hex, payout_address, change_address_maker, escrow_inputs_maker_json, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
 //         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
 //     )
 //     .bind(offer.0)
@@ -352,7 +553,7 @@ async fn test_fetch_taker_bond_requirements() -> Result<()> {
 
 	sqlx::query(
     "INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts, bond_address, bond_amount_sat,
-    bond_tx_hex, payout_address, change_address_maker, escrow_inputs_hex_maker_csv, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
+    bond_tx_hex, payout_address, change_address_maker, escrow_inputs_maker_json, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
 	)
 	.bind(offer_id_hex)
@@ -366,7 +567,7 @@ async fn test_fetch_taker_bond_requirements() -> Result<()> {
 	.bind("signedBondHex")
 	.bind("1PayoutAddress")
 	.bind("1ChangeAddressMaker")
-	.bind("escrowInputsHexMakerCSV,PSBT1,PSBT2")
+	.bind("escrowInputsMakerJson")
 	.bind("taprootPubkeyHexMaker")
 	.bind("musigPubNonceHex")
 	.bind("musigPubkeyHex")
@@ -384,6 +585,152 @@ async fn test_fetch_taker_bond_requirements() -> Result<()> {
 	Ok(())
 }
 
+#[tokio::test]
+async fn test_cancel_offer_in_orderbook() -> Result<()> {
+	let database = create_coordinator().await?;
+
+	// Insert a test entry into active_maker_offers
+	let offer_id_hex = "offer_id_1";
+	let robohash_hex = "a3f1f1f0e2f3f4f5";
+
+	sqlx::query(
+    "INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts, bond_address, bond_amount_sat,
+    bond_tx_hex, payout_address, change_address_maker, escrow_inputs_maker_json, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
+    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+	)
+	.bind(offer_id_hex)
+	.bind(hex::decode(robohash_hex).unwrap())
+	.bind(true) // is_buy_order
+	.bind(1500) // amount_sat
+	.bind(50) // bond_ratio
+	.bind(1234567890) // offer_duration_ts
+	.bind("1BondAddress")
+	.bind(100)
+	.bind("signedBondHex")
+	.bind("1PayoutAddress")
+	.bind("1ChangeAddressMaker")
+	.bind("escrowInputsMakerJson")
+	.bind("taprootPubkeyHexMaker")
+	.bind("musigPubNonceHex")
+	.bind("musigPubkeyHex")
+	.bind("1TakerBondAddress")
+	.execute(&*database.db_pool)
+	.await?;
+
+	// only the maker of the offer can cancel it
+	assert_eq!(
+		database
+			.fetch_maker_taproot_pubkey(offer_id_hex, "b3f1f1f0e2f3f4f5")
+			.await?,
+		None
+	);
+	assert!(
+		!database
+			.delete_offer_from_orderbook(offer_id_hex, "b3f1f1f0e2f3f4f5")
+			.await?
+	);
+	assert_eq!(
+		database
+			.fetch_maker_taproot_pubkey(offer_id_hex, robohash_hex)
+			.await?,
+		Some("taprootPubkeyHexMaker".to_string())
+	);
+
+	// the offer is removed from the orderbook and so from bond monitoring
+	assert!(
+		database
+			.delete_offer_from_orderbook(offer_id_hex, robohash_hex)
+			.await?
+	);
+	assert!(database.fetch_all_bonds().await?.is_empty());
+	assert!(
+		!database
+			.delete_offer_from_orderbook(offer_id_hex, robohash_hex)
+			.await?
+	);
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_delete_expired_offers_and_stale_requests() -> Result<()> {
+	let database = create_coordinator().await?;
+	let now_ts = 1_800_000_000;
+
+	for (offer_id_hex, offer_duration_ts) in [
+		("expired_offer", now_ts - 1),
+		("active_offer", now_ts + 3600),
+	] {
+		sqlx::query(
+		"INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts, bond_address, bond_amount_sat,
+		bond_tx_hex, payout_address, change_address_maker, escrow_inputs_maker_json, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		)
+		.bind(offer_id_hex)
+		.bind(hex::decode("a3f1f1f0e2f3f4f5").unwrap())
+		.bind(true) // is_buy_order
+		.bind(1500) // amount_sat
+		.bind(50) // bond_ratio
+		.bind(offer_duration_ts)
+		.bind("1BondAddress")
+		.bind(100)
+		.bind("signedBondHex")
+		.bind("1PayoutAddress")
+		.bind("1ChangeAddressMaker")
+		.bind("escrowInputsMakerJson")
+		.bind("taprootPubkeyHexMaker")
+		.bind("musigPubNonceHex")
+		.bind("musigPubkeyHex")
+		.bind("1TakerBondAddress")
+		.execute(&*database.db_pool)
+		.await?;
+	}
+
+	// (robohash, offer_duration_ts, request_created_ts)
+	let maker_requests = [
+		("a1", now_ts + 3600, now_ts - 60),   // waiting for the bond
+		("a2", now_ts + 3600, now_ts - 7200), // bond never arrived
+		("a3", now_ts - 1, now_ts - 60),      // offer duration passed
+	];
+	for (robohash_hex, offer_duration_ts, request_created_ts) in maker_requests {
+		sqlx::query(
+			"INSERT INTO maker_requests (robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts, bond_address,
+			bond_amount_sat, escrow_locking_input_amount_without_trade_sum, request_created_ts)
+			VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+		)
+		.bind(hex::decode(robohash_hex).unwrap())
+		.bind(true)
+		.bind(1500)
+		.bind(50)
+		.bind(offer_duration_ts)
+		.bind("1BondAddress")
+		.bind(100)
+		.bind(1000)
+		.bind(request_created_ts)
+		.execute(&*database.db_pool)
+		.await?;
+	}
+
+	let expired_offer_ids = database.delete_expired_offers(now_ts as u64).await?;
+	assert_eq!(expired_offer_ids, vec!["expired_offer".to_string()]);
+
+	// the bond of the expired offer is not monitored anymore
+	let monitored_bonds = database.fetch_all_bonds().await?;
+	assert_eq!(monitored_bonds.len(), 1);
+	assert_eq!(monitored_bonds[0].trade_id_hex, "active_offer");
+
+	let removed_requests = database
+		.delete_stale_maker_requests(now_ts as u64, 3600)
+		.await?;
+	assert_eq!(removed_requests, 2);
+	let remaining_request = sqlx::query_as::<_, (Vec<u8>,)>("SELECT robohash FROM maker_requests")
+		.fetch_all(&*database.db_pool)
+		.await?;
+	assert_eq!(remaining_request, vec![(hex::decode("a1").unwrap(),)]);
+
+	Ok(())
+}
+
 #[tokio::test]
 async fn test_fetch_and_delete_offer_from_public_offers_table() -> Result<()> {
 	let database = create_coordinator().await?;
@@ -400,7 +747,7 @@ async fn test_fetch_and_delete_offer_from_public_offers_table() -> Result<()> {
 	let bond_tx_hex = "signedBondHex".to_string();
 	let payout_address = "1PayoutAddress".to_string();
 	let change_address_maker = "1ChangeAddressMaker".to_string();
-	let escrow_inputs_hex_maker_csv = "escrowInputsHexMakerCSV,PSBT1,PSBT2".to_string();
+	let escrow_inputs_maker_json = "escrowInputsMakerJson".to_string();
 	let taproot_pubkey_hex_maker = "taprootPubkeyHexMaker".to_string();
 	let musig_pub_nonce_hex = "musigPubNonceHex".to_string();
 	let musig_pubkey_hex = "musigPubkeyHex".to_string();
@@ -408,7 +755,7 @@ async fn test_fetch_and_delete_offer_from_public_offers_table() -> Result<()> {
 
 	sqlx::query(
     "INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts, bond_address, bond_amount_sat,
-    bond_tx_hex, payout_address, change_address_maker, escrow_inputs_hex_maker_csv, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
+    bond_tx_hex, payout_address, change_address_maker, escrow_inputs_maker_json, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address)
     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
     )
       .bind(offer_id_hex)
@@ -422,7 +769,7 @@ async fn test_fetch_and_delete_offer_from_public_offers_table() -> Result<()> {
       .bind(bond_tx_hex.clone())
       .bind(payout_address.clone())
       .bind(change_address_maker.clone())
-      .bind(escrow_inputs_hex_maker_csv.clone())
+      .bind(escrow_inputs_maker_json.clone())
       .bind(taproot_pubkey_hex_maker.clone())
       .bind(musig_pub_nonce_hex.clone())
       .bind(musig_pubkey_hex.clone())
@@ -447,6 +794,7 @@ async fn test_fetch_and_delete_offer_from_public_offers_table() -> Result<()> {
 	assert_eq!(result.payout_address_maker, payout_address);
 	assert_eq!(result.musig_pub_nonce_hex_maker, musig_pub_nonce_hex);
 	assert_eq!(result.musig_pubkey_hex_maker, musig_pubkey_hex);
+	assert_eq!(result.escrow_inputs_maker_json, escrow_inputs_maker_json);
 
 	// Verify the deletion
 	let remaining_offers =
@@ -460,3 +808,469 @@ async fn test_fetch_and_delete_offer_from_public_offers_table() -> Result<()> {
 
 	Ok(())
 }
+
+// inserts a taken offer with dummy trade data whose escrow transaction is not confirmed yet
+async fn insert_taken_offer(database: &CoordinatorDB, offer_id_hex: &str) -> Result<()> {
+	sqlx::query(
+		"INSERT INTO taken_offers (offer_id, robohash_maker, robohash_taker, is_buy_order, amount_sat, bond_ratio,
+		offer_duration_ts, bond_address_maker, bond_address_taker, bond_amount_sat, bond_tx_hex_maker, bond_tx_hex_taker,
+		payout_address_maker, taproot_xonly_pubkey_hex_maker, payout_address_taker, taproot_xonly_pubkey_hex_taker,
+		musig_pub_nonce_hex_maker, musig_pubkey_compressed_hex_maker, musig_pub_nonce_hex_taker, musig_pubkey_compressed_hex_taker,
+		escrow_psbt_hex, escrow_psbt_txid, escrow_psbt_is_confirmed, trade_state)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+	)
+	.bind(offer_id_hex)
+	.bind(hex::decode("a1a1").unwrap())
+	.bind(hex::decode("b2b2").unwrap())
+	.bind(true)
+	.bind(1500) // amount_sat
+	.bind(50) // bond_ratio
+	.bind(1234567890) // offer_duration_ts
+	.bind("1BondAddressMaker")
+	.bind("1BondAddressTaker")
+	.bind(750) // bond_amount_sat
+	.bind("bondTxHexMaker")
+	.bind("bondTxHexTaker")
+	.bind("1PayoutAddressMaker")
+	.bind("taprootPubkeyHexMaker")
+	.bind("1PayoutAddressTaker")
+	.bind("taprootPubkeyHexTaker")
+	.bind("musigPubNonceHexMaker")
+	.bind("musigPubkeyHexMaker")
+	.bind("musigPubNonceHexTaker")
+	.bind("musigPubkeyHexTaker")
+	.bind("escrowPsbtHex")
+	.bind("escrowPsbtTxid")
+	.bind(0) // escrow_psbt_is_confirmed
+	.bind(TradeState::EscrowPending.as_str())
+	.execute(&*database.db_pool)
+	.await?;
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_monitor_and_abort_taken_offer_bonds() -> Result<()> {
+	let database = create_coordinator().await?;
+	insert_taken_offer(&database, "taken_offer").await?;
+
+	// both bonds are monitored until the escrow tx is confirmed
+	let monitored_bonds = database.fetch_all_bonds().await?;
+	assert_eq!(monitored_bonds.len(), 2);
+	let maker_bond = &monitored_bonds[0];
+	let taker_bond = &monitored_bonds[1];
+	assert_eq!(maker_bond.table, Table::ActiveTrades);
+	assert_eq!(maker_bond.robot, hex::decode("a1a1").unwrap());
+	assert_eq!(maker_bond.bond_tx_hex, "bondTxHexMaker");
+	assert_eq!(maker_bond.requirements.bond_address, "1BondAddressMaker");
+	assert_eq!(maker_bond.escrow_txid, Some("escrowPsbtTxid".to_string()));
+	assert_eq!(taker_bond.robot, hex::decode("b2b2").unwrap());
+	assert_eq!(taker_bond.bond_tx_hex, "bondTxHexTaker");
+	assert_eq!(taker_bond.requirements.bond_address, "1BondAddressTaker");
+	assert_eq!(taker_bond.requirements.locking_amount_sat, 750);
+	assert_eq!(taker_bond.requirements.min_input_sum_sat, 1500);
+
+	// requests of both traders are authenticated with the taproot key they registered
+	assert_eq!(
+		database
+			.fetch_trader_taproot_pubkey("taken_offer", "a1a1")
+			.await?,
+		Some("taprootPubkeyHexMaker".to_string())
+	);
+	assert_eq!(
+		database
+			.fetch_trader_taproot_pubkey("taken_offer", "b2b2")
+			.await?,
+		Some("taprootPubkeyHexTaker".to_string())
+	);
+	assert_eq!(
+		database
+			.fetch_trader_taproot_pubkey("taken_offer", "c3c3")
+			.await?,
+		None
+	);
+
+	// a violation of the taker bond aborts the trade and ends the monitoring
+	assert_eq!(database.fetch_trade_aborted_by("taken_offer").await?, None);
+	database.remove_violating_bond(taker_bond).await?;
+	assert_eq!(
+		database.fetch_trade_aborted_by("taken_offer").await?,
+		Some("b2b2".to_string())
+	);
+	assert!(database.fetch_all_bonds().await?.is_empty());
+
+	// bonds of trades with confirmed escrow tx are not monitored anymore
+	insert_taken_offer(&database, "confirmed_offer").await?;
+	database
+		.confirm_bond_txids(vec!["escrowPsbtTxid".to_string()])
+		.await?;
+	assert!(database.fetch_all_bonds().await?.is_empty());
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_payout_psbt_not_stored_after_lost_transition() -> Result<()> {
+	let database = create_coordinator().await?;
+	insert_taken_offer(&database, "taken_offer").await?;
+	database
+		.confirm_bond_txids(vec!["escrowPsbtTxid".to_string()])
+		.await?;
+
+	// the trade gets disputed while the keyspend payout is assembled
+	database
+		.set_trade_state("taken_offer", TradeState::KeyspendPayoutProcessing)
+		.await?;
+	database
+		.set_trade_state("taken_offer", TradeState::Disputed)
+		.await?;
+	assert!(
+		!database
+			.insert_keyspend_payout_psbt("taken_offer", "payoutPsbtHex")
+			.await?
+	);
+	assert!(database
+		.fetch_keyspend_payout_psbt("taken_offer")
+		.await?
+		.is_none());
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::Disputed
+	);
+
+	// the trade gets aborted while the script payout is assembled
+	database.resolve_escrow("taken_offer", "a1a1").await?;
+	database
+		.set_trade_state("taken_offer", TradeState::ScriptPayoutProcessing)
+		.await?;
+	database
+		.set_trade_state("taken_offer", TradeState::Aborted)
+		.await?;
+	assert!(
+		!database
+			.insert_script_payout_psbt("taken_offer", "scriptPayoutPsbtHex")
+			.await?
+	);
+	assert!(database
+		.fetch_script_payout_psbt("taken_offer")
+		.await?
+		.is_none());
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::Aborted
+	);
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_trade_state_transitions_and_recovery() -> Result<()> {
+	let database = create_coordinator().await?;
+	insert_taken_offer(&database, "taken_offer").await?;
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::EscrowPending
+	);
+
+	// the payout can't be assembled before the escrow tx is confirmed
+	assert!(database
+		.transition_trade_state(
+			"taken_offer",
+			TradeState::EscrowPending,
+			TradeState::KeyspendPayoutProcessing
+		)
+		.await
+		.is_err());
+	database
+		.confirm_bond_txids(vec!["escrowPsbtTxid".to_string()])
+		.await?;
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::EscrowConfirmed
+	);
+
+	// only the first payout request gets to assemble the payout
+	assert!(
+		database
+			.transition_trade_state(
+				"taken_offer",
+				TradeState::EscrowConfirmed,
+				TradeState::KeyspendPayoutProcessing
+			)
+			.await?
+	);
+	assert!(
+		!database
+			.transition_trade_state(
+				"taken_offer",
+				TradeState::EscrowConfirmed,
+				TradeState::KeyspendPayoutProcessing
+			)
+			.await?
+	);
+
+	// a crash before the payout psbt got stored is rolled back on startup
+	assert_eq!(
+		database.recover_interrupted_payouts().await?,
+		vec![("taken_offer".to_string(), TradeState::EscrowConfirmed)]
+	);
+
+	// storing the payout psbt moves the trade to keyspend payout ready
+	database
+		.set_trade_state("taken_offer", TradeState::KeyspendPayoutProcessing)
+		.await?;
+	assert!(
+		database
+			.insert_keyspend_payout_psbt("taken_offer", "payoutPsbtHex")
+			.await?
+	);
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::KeyspendPayoutReady
+	);
+	assert!(database.recover_interrupted_payouts().await?.is_empty());
+
+	// a trade left in processing with a stored payout psbt is resumed on startup
+	sqlx::query("UPDATE taken_offers SET trade_state = ? WHERE offer_id = ?")
+		.bind(TradeState::KeyspendPayoutProcessing.as_str())
+		.bind("taken_offer")
+		.execute(&*database.db_pool)
+		.await?;
+	assert_eq!(
+		database.recover_interrupted_payouts().await?,
+		vec![("taken_offer".to_string(), TradeState::KeyspendPayoutReady)]
+	);
+	assert!(database.recover_interrupted_payouts().await?.is_empty());
+
+	// disputes show up as open escrows until the coordinator decides the winner
+	database
+		.set_trade_state("taken_offer", TradeState::Disputed)
+		.await?;
+	assert_eq!(database.get_open_escrows().await?.len(), 1);
+	database.resolve_escrow("taken_offer", "a1a1").await?;
+	assert!(database.get_open_escrows().await?.is_empty());
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::EscrowResolved
+	);
+	assert!(database
+		.set_trade_state("taken_offer", TradeState::Disputed)
+		.await
+		.is_err());
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_set_trader_happy_field() -> Result<()> {
+	let database = create_coordinator().await?;
+	insert_taken_offer(&database, "taken_offer").await?;
+
+	// escrow can't be requested before the escrow tx is confirmed, the flag stays unset
+	assert!(database
+		.set_trader_happy_field("taken_offer", "a1a1", false)
+		.await
+		.is_err());
+	let happiness = database.fetch_trader_happiness("taken_offer").await?;
+	assert_eq!(happiness.maker_happy, None);
+	assert_eq!(happiness.trade_state, TradeState::EscrowPending);
+
+	database
+		.confirm_bond_txids(vec!["escrowPsbtTxid".to_string()])
+		.await?;
+	database
+		.set_trader_happy_field("taken_offer", "a1a1", true)
+		.await?;
+
+	// the taker requests escrow, the flag and the trade state change together
+	database
+		.set_trader_happy_field("taken_offer", "b2b2", false)
+		.await?;
+	let happiness = database.fetch_trader_happiness("taken_offer").await?;
+	assert_eq!(happiness.maker_happy, Some(true));
+	assert_eq!(happiness.taker_happy, Some(false));
+	assert_eq!(happiness.trade_state, TradeState::Disputed);
+
+	// the disputing trader can't flip back to happy, the maker can still request escrow
+	assert!(database
+		.set_trader_happy_field("taken_offer", "b2b2", true)
+		.await
+		.is_err());
+	database
+		.set_trader_happy_field("taken_offer", "a1a1", false)
+		.await?;
+	let happiness = database.fetch_trader_happiness("taken_offer").await?;
+	assert_eq!(happiness.maker_happy, Some(false));
+	assert_eq!(happiness.taker_happy, Some(false));
+
+	// nothing changes once the escrow is resolved
+	database.resolve_escrow("taken_offer", "a1a1").await?;
+	assert!(database
+		.set_trader_happy_field("taken_offer", "b2b2", false)
+		.await
+		.is_err());
+	assert_eq!(
+		database.fetch_trade_state("taken_offer").await?,
+		TradeState::EscrowResolved
+	);
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_record_published_bond() -> Result<()> {
+	let database = create_coordinator().await?;
+	let bond_tx_hex = "020000000001010127a9d96655011fca55dc2667f30b98655e46da98d0f84df676b53d7fb380140000000000010000000250c3000000000000225120a12e5d145a4a3ab43f6cc1188435e74f253eace72bd986f1aaf780fd0c653236aa900000000000002251207dd0d1650cdc22537709e35620f3b5cc3249b305bda1209ba4e5e01bc3ad2d8c014010e19c8b915624bd4aa0ba4d094d26ca031a6f2d8f23fe51372c7ea50e05f3caf81c7e139f6fed3e9ffd20c03d79f78542acb3d8aed664898f1c4b2909c2188c00000000";
+	let bond_txid = deserialize::<Transaction>(&hex::decode(bond_tx_hex)?)?
+		.txid()
+		.to_string();
+	let violating_bond = MonitoringBond {
+		bond_tx_hex: bond_tx_hex.to_string(),
+		trade_id_hex: "offer_id_1".to_string(),
+		robot: hex::decode("a1a1").unwrap(),
+		requirements: BondRequirements {
+			bond_address: "bcrt1p5yh969z2fgatg0mvcyvggd08fujna88890vcdud277q06rr9xgmqwfdkcx"
+				.to_string(),
+			locking_amount_sat: 50000,
+			min_input_sum_sat: 100000,
+		},
+		table: Table::Orderbook,
+		escrow_txid: None,
+	};
+
+	// recording the same bond twice keeps a single entry
+	database
+		.insert_published_bond(&violating_bond, None)
+		.await?;
+	database
+		.insert_published_bond(&violating_bond, None)
+		.await?;
+	let pending_bonds = database.fetch_pending_published_bonds().await?;
+	assert_eq!(pending_bonds.len(), 1);
+	assert_eq!(pending_bonds[0].bond_txid, bond_txid);
+	assert_eq!(pending_bonds[0].robohash_hex, "a1a1");
+	assert_eq!(pending_bonds[0].offer_id_hex, "offer_id_1");
+	assert_eq!(pending_bonds[0].status, PunishmentStatus::Pending);
+	assert!(pending_bonds[0].cpfp_tx_hex.is_none());
+
+	let cpfp_child = CpfpChild {
+		txid: "cpfpChildTxid".to_string(),
+		tx_hex: "cpfpChildTxHex".to_string(),
+		package_feerate_sat_vb: 42.5,
+		output_amount_sat: 49000,
+	};
+	database
+		.update_published_bond_cpfp(&bond_txid, &cpfp_child, 120)
+		.await?;
+	let pending_bonds = database.fetch_pending_published_bonds().await?;
+	assert_eq!(
+		pending_bonds[0].cpfp_tx_hex,
+		Some("cpfpChildTxHex".to_string())
+	);
+	assert_eq!(pending_bonds[0].cpfp_feerate_sat_vb, Some(42.5));
+	assert_eq!(pending_bonds[0].cpfp_block_height, Some(120));
+	// the child is no compensation tx if the bond is not forwarded
+	assert_eq!(pending_bonds[0].compensation_txid, None);
+
+	// confirmed punishments are not monitored anymore
+	database
+		.set_published_bond_status(&bond_txid, &PunishmentStatus::Confirmed)
+		.await?;
+	assert!(database.fetch_pending_published_bonds().await?.is_empty());
+
+	Ok(())
+}
+
+#[tokio::test]
+async fn test_compensate_wronged_trader() -> Result<()> {
+	let database = create_coordinator().await?;
+	insert_taken_offer(&database, "taken_offer").await?;
+
+	// the payout address of the counterparty of the violating trader gets compensated
+	assert_eq!(
+		database
+			.fetch_compensation_address("taken_offer", "b2b2")
+			.await?,
+		Some("1PayoutAddressMaker".to_string())
+	);
+	assert_eq!(
+		database
+			.fetch_compensation_address("taken_offer", "a1a1")
+			.await?,
+		Some("1PayoutAddressTaker".to_string())
+	);
+
+	let bond_tx_hex = "020000000001010127a9d96655011fca55dc2667f30b98655e46da98d0f84df676b53d7fb380140000000000010000000250c3000000000000225120a12e5d145a4a3ab43f6cc1188435e74f253eace72bd986f1aaf780fd0c653236aa900000000000002251207dd0d1650cdc22537709e35620f3b5cc3249b305bda1209ba4e5e01bc3ad2d8c014010e19c8b915624bd4aa0ba4d094d26ca031a6f2d8f23fe51372c7ea50e05f3caf81c7e139f6fed3e9ffd20c03d79f78542acb3d8aed664898f1c4b2909c2188c00000000";
+	let violating_bond = MonitoringBond {
+		bond_tx_hex: bond_tx_hex.to_string(),
+		trade_id_hex: "taken_offer".to_string(),
+		robot: hex::decode("b2b2").unwrap(),
+		requirements: BondRequirements {
+			bond_address: "1BondAddressTaker".to_string(),
+			locking_amount_sat: 750,
+			min_input_sum_sat: 1500,
+		},
+		table: Table::ActiveTrades,
+		escrow_txid: Some("escrowPsbtTxid".to_string()),
+	};
+	database
+		.insert_published_bond(&violating_bond, Some("1PayoutAddressMaker"))
+		.await?;
+	database.remove_violating_bond(&violating_bond).await?;
+
+	// the maker can't be compensated for a violation of its own bond after the trade got aborted
+	assert_eq!(
+		database
+			.fetch_compensation_address("taken_offer", "a1a1")
+			.await?,
+		None
+	);
+
+	// the cpfp child forwarding the bond is recorded as compensation tx of the bond
+	let compensation_tx = CpfpChild {
+		txid: "compensationTxid".to_string(),
+		tx_hex: "compensationTxHex".to_string(),
+		package_feerate_sat_vb: 40.0,
+		output_amount_sat: 700,
+	};
+	let bond_txid = deserialize::<Transaction>(&hex::decode(bond_tx_hex)?)?
+		.txid()
+		.to_string();
+	database
+		.update_published_bond_cpfp(&bond_txid, &compensation_tx, 120)
+		.await?;
+	let pending_bonds = database.fetch_pending_published_bonds().await?;
+	assert_eq!(
+		pending_bonds[0].compensation_address,
+		Some("1PayoutAddressMaker".to_string())
+	);
+	assert_eq!(
+		pending_bonds[0].compensation_txid,
+		Some("compensationTxid".to_string())
+	);
+	let compensation_amount_sat =
+		sqlx::query("SELECT compensation_amount_sat FROM published_bonds WHERE bond_txid = ?")
+			.bind(&bond_txid)
+			.fetch_one(&*database.db_pool)
+			.await?
+			.try_get::<i64, _>("compensation_amount_sat")?;
+	assert_eq!(compensation_amount_sat, 700);
+
+	// an earlier child got mined instead of the stored one, it is recorded as compensation tx
+	database
+		.update_published_bond_compensation(&bond_txid, "minedCompensationTxid", 720)
+		.await?;
+	let pending_bonds = database.fetch_pending_published_bonds().await?;
+	assert_eq!(
+		pending_bonds[0].compensation_txid,
+		Some("minedCompensationTxid".to_string())
+	);
+	let compensation_amount_sat =
+		sqlx::query("SELECT compensation_amount_sat FROM published_bonds WHERE bond_txid = ?")
+			.bind(&bond_txid)
+			.fetch_one(&*database.db_pool)
+			.await?
+			.try_get::<i64, _>("compensation_amount_sat")?;
+	assert_eq!(compensation_amount_sat, 720);
+
+	Ok(())
+}
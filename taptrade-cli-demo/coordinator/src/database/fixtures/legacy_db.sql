@@ -0,0 +1,116 @@
+-- a coordinator database as created by CoordinatorDB::init before trades had an explicit trade state
+-- (escrow_ongoing and processing flags, no _sqlx_migrations table), with taken offers in several stages
+
+CREATE TABLE maker_requests (
+	robohash BLOB PRIMARY KEY,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	escrow_locking_input_amount_without_trade_sum INTEGER NOT NULL
+);
+
+CREATE TABLE active_maker_offers (
+	offer_id TEXT PRIMARY KEY,
+	robohash BLOB,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	escrow_locking_input_amount_without_trade_sum INTEGER,
+	bond_tx_hex TEXT NOT NULL,
+	payout_address TEXT NOT NULL,
+	change_address_maker TEXT NOT NULL,
+	escrow_inputs_hex_maker_csv TEXT NOT NULL,
+	taproot_pubkey_hex_maker TEXT NOT NULL,
+	musig_pub_nonce_hex TEXT NOT NULL,
+	musig_pubkey_hex TEXT NOT NULL,
+	taker_bond_address TEXT
+);
+
+CREATE TABLE taken_offers (
+	offer_id TEXT PRIMARY KEY,
+	robohash_maker BLOB,
+	robohash_taker BLOB,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address_maker TEXT NOT NULL,
+	bond_address_taker TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	bond_tx_hex_maker TEXT NOT NULL,
+	bond_tx_hex_taker TEXT NOT NULL,
+	payout_address_maker TEXT NOT NULL,
+	taproot_xonly_pubkey_hex_maker TEXT NOT NULL,
+	payout_address_taker TEXT NOT NULL,
+	taproot_xonly_pubkey_hex_taker TEXT NOT NULL,
+	musig_pub_nonce_hex_maker TEXT NOT NULL,
+	musig_pubkey_compressed_hex_maker TEXT NOT NULL,
+	musig_pub_nonce_hex_taker TEXT NOT NULL,
+	musig_pubkey_compressed_hex_taker TEXT NOT NULL,
+	musig_partial_sig_hex_maker TEXT,
+	musig_partial_sig_hex_taker TEXT,
+	escrow_psbt_hex TEXT NOT NULL,
+	escrow_psbt_txid TEXT NOT NULL,
+	signed_escrow_psbt_hex_maker TEXT,
+	signed_escrow_psbt_hex_taker TEXT,
+	escrow_psbt_is_confirmed INTEGER,
+	maker_happy INTEGER,
+	taker_happy INTEGER,
+	escrow_ongoing INTEGER NOT NULL,
+	escrow_winner_robohash TEXT,
+	escrow_taproot_pk_coordinator TEXT,
+	escrow_amount_maker_sat INTEGER,
+	escrow_amount_taker_sat INTEGER,
+	escrow_fee_per_participant INTEGER,
+	escrow_output_descriptor TEXT,
+	payout_transaction_psbt_hex TEXT,
+	processing INTEGER NOT NULL
+);
+
+INSERT INTO maker_requests (robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts,
+	bond_address, bond_amount_sat, escrow_locking_input_amount_without_trade_sum)
+VALUES (X'a3f1', 1, 1000, 50, 1234567890, '1BondAddress', 500, 1500);
+
+INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio,
+	offer_duration_ts, bond_address, bond_amount_sat, escrow_locking_input_amount_without_trade_sum,
+	bond_tx_hex, payout_address, change_address_maker, escrow_inputs_hex_maker_csv,
+	taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex)
+VALUES ('active_offer', X'a3f1', 1, 1000, 50, 1234567890, '1BondAddress', 500, 1500,
+	'bondTxHex', '1PayoutAddress', '1ChangeAddress', 'escrowInputsCsv',
+	'taprootPubkeyHex', 'musigPubNonceHex', 'musigPubkeyHex');
+
+-- offer_id, escrow_psbt_is_confirmed, maker_happy, taker_happy, escrow_ongoing, escrow_winner_robohash,
+-- payout_transaction_psbt_hex and processing differ between the trades
+INSERT INTO taken_offers (offer_id, robohash_maker, robohash_taker, is_buy_order, amount_sat, bond_ratio,
+	offer_duration_ts, bond_address_maker, bond_address_taker, bond_amount_sat, bond_tx_hex_maker, bond_tx_hex_taker,
+	payout_address_maker, taproot_xonly_pubkey_hex_maker, payout_address_taker, taproot_xonly_pubkey_hex_taker,
+	musig_pub_nonce_hex_maker, musig_pubkey_compressed_hex_maker, musig_pub_nonce_hex_taker, musig_pubkey_compressed_hex_taker,
+	escrow_psbt_hex, escrow_psbt_txid, escrow_psbt_is_confirmed, maker_happy, taker_happy, escrow_ongoing,
+	escrow_winner_robohash, payout_transaction_psbt_hex, processing)
+VALUES
+	('pending_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'pendingEscrowTxid', 0, NULL, NULL, 0, NULL, NULL, 0),
+	('confirmed_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'confirmedEscrowTxid', 1, 1, NULL, 0, NULL, NULL, 0),
+	('processing_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'processingEscrowTxid', 1, 1, 1, 0, NULL, 'payoutPsbtHex', 1),
+	('disputed_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'disputedEscrowTxid', 1, 1, NULL, 1, NULL, NULL, 0),
+	('resolved_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'resolvedEscrowTxid', 1, NULL, 1, 0, 'b2b2', NULL, 0);
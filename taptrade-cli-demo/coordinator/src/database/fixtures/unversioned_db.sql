@@ -0,0 +1,151 @@
+-- a coordinator database as created by CoordinatorDB::init before the schema was versioned
+-- (no _sqlx_migrations table), with one offer in each stage of the trade
+
+-- requests of makers awaiting submission of their bond
+CREATE TABLE maker_requests (
+	robohash BLOB PRIMARY KEY,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	escrow_locking_input_amount_without_trade_sum INTEGER NOT NULL,
+	request_created_ts INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
+
+-- offers that are active in the orderbook awaiting a taker
+CREATE TABLE active_maker_offers (
+	offer_id TEXT PRIMARY KEY,
+	robohash BLOB,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	escrow_locking_input_amount_without_trade_sum INTEGER,
+	bond_tx_hex TEXT NOT NULL,
+	payout_address TEXT NOT NULL,
+	change_address_maker TEXT NOT NULL,
+	escrow_inputs_hex_maker_csv TEXT NOT NULL,
+	taproot_pubkey_hex_maker TEXT NOT NULL,
+	musig_pub_nonce_hex TEXT NOT NULL,
+	musig_pubkey_hex TEXT NOT NULL,
+	taker_bond_address TEXT
+);
+
+-- offers that are taken and are in the trade process,
+-- escrow_psbt_is_confirmed will be set 1 once the escrow psbt is confirmed onchain
+CREATE TABLE taken_offers (
+	offer_id TEXT PRIMARY KEY,
+	robohash_maker BLOB,
+	robohash_taker BLOB,
+	is_buy_order INTEGER,
+	amount_sat INTEGER NOT NULL,
+	bond_ratio INTEGER NOT NULL,
+	offer_duration_ts INTEGER NOT NULL,
+	bond_address_maker TEXT NOT NULL,
+	bond_address_taker TEXT NOT NULL,
+	bond_amount_sat INTEGER NOT NULL,
+	bond_tx_hex_maker TEXT NOT NULL,
+	bond_tx_hex_taker TEXT NOT NULL,
+	payout_address_maker TEXT NOT NULL,
+	taproot_xonly_pubkey_hex_maker TEXT NOT NULL,
+	payout_address_taker TEXT NOT NULL,
+	taproot_xonly_pubkey_hex_taker TEXT NOT NULL,
+	musig_pub_nonce_hex_maker TEXT NOT NULL,
+	musig_pubkey_compressed_hex_maker TEXT NOT NULL,
+	musig_pub_nonce_hex_taker TEXT NOT NULL,
+	musig_pubkey_compressed_hex_taker TEXT NOT NULL,
+	musig_partial_sig_hex_maker TEXT,
+	musig_partial_sig_hex_taker TEXT,
+	escrow_psbt_hex TEXT NOT NULL,
+	escrow_psbt_txid TEXT NOT NULL,
+	signed_escrow_psbt_hex_maker TEXT,
+	signed_escrow_psbt_hex_taker TEXT,
+	escrow_psbt_is_confirmed INTEGER,
+	maker_happy INTEGER,
+	taker_happy INTEGER,
+	escrow_winner_robohash TEXT,
+	escrow_taproot_pk_coordinator TEXT,
+	escrow_amount_maker_sat INTEGER,
+	escrow_amount_taker_sat INTEGER,
+	escrow_fee_per_participant INTEGER,
+	escrow_output_descriptor TEXT,
+	escrow_inputs_hex_maker_csv TEXT,
+	escrow_inputs_hex_taker_csv TEXT,
+	payout_transaction_psbt_hex TEXT,
+	script_payout_psbt_hex TEXT,
+	invalid_partial_sigs_maker INTEGER NOT NULL DEFAULT 0,
+	invalid_partial_sigs_taker INTEGER NOT NULL DEFAULT 0,
+	trade_aborted_by_robohash TEXT,
+	trade_state TEXT NOT NULL
+);
+
+-- bonds published as punishment, they are monitored until they confirm
+CREATE TABLE published_bonds (
+	bond_txid TEXT PRIMARY KEY,
+	offer_id TEXT NOT NULL,
+	robohash TEXT NOT NULL,
+	bond_tx_hex TEXT NOT NULL,
+	bond_address TEXT NOT NULL,
+	cpfp_tx_hex TEXT,
+	cpfp_feerate_sat_vb REAL,
+	cpfp_block_height INTEGER,
+	status TEXT NOT NULL,
+	compensation_address TEXT,
+	compensation_txid TEXT,
+	compensation_amount_sat INTEGER,
+	published_ts INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+);
+
+INSERT INTO maker_requests (robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts,
+	bond_address, bond_amount_sat, escrow_locking_input_amount_without_trade_sum)
+VALUES (X'a3f1', 1, 1000, 50, 1234567890, '1BondAddress', 500, 1500);
+
+INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio,
+	offer_duration_ts, bond_address, bond_amount_sat, escrow_locking_input_amount_without_trade_sum,
+	bond_tx_hex, payout_address, change_address_maker, escrow_inputs_hex_maker_csv,
+	taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex)
+VALUES ('active_offer', X'a3f1', 1, 1000, 50, 1234567890, '1BondAddress', 500, 1500,
+	'bondTxHex', '1PayoutAddress', '1ChangeAddress', 'escrowInputsCsv',
+	'taprootPubkeyHex', 'musigPubNonceHex', 'musigPubkeyHex');
+
+INSERT INTO taken_offers (offer_id, robohash_maker, robohash_taker, is_buy_order, amount_sat, bond_ratio,
+	offer_duration_ts, bond_address_maker, bond_address_taker, bond_amount_sat, bond_tx_hex_maker, bond_tx_hex_taker,
+	payout_address_maker, taproot_xonly_pubkey_hex_maker, payout_address_taker, taproot_xonly_pubkey_hex_taker,
+	musig_pub_nonce_hex_maker, musig_pubkey_compressed_hex_maker, musig_pub_nonce_hex_taker, musig_pubkey_compressed_hex_taker,
+	escrow_psbt_hex, escrow_psbt_txid, escrow_psbt_is_confirmed, trade_state)
+VALUES ('taken_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'escrowPsbtTxid', 0, 'escrow_pending');
+
+-- an offer with json escrow inputs and trades with csv escrow inputs of protocol version 1 traders,
+-- before and after both signed escrow psbts got submitted
+INSERT INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat, bond_ratio,
+	offer_duration_ts, bond_address, bond_amount_sat, escrow_locking_input_amount_without_trade_sum,
+	bond_tx_hex, payout_address, change_address_maker, escrow_inputs_hex_maker_csv,
+	taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex)
+VALUES ('json_offer', X'a4f2', 1, 1000, 50, 1234567890, '1BondAddress', 600, 1500,
+	'bondTxHex', '1PayoutAddress', '1ChangeAddress', '[]',
+	'taprootPubkeyHex', 'musigPubNonceHex', 'musigPubkeyHex');
+
+INSERT INTO taken_offers (offer_id, robohash_maker, robohash_taker, is_buy_order, amount_sat, bond_ratio,
+	offer_duration_ts, bond_address_maker, bond_address_taker, bond_amount_sat, bond_tx_hex_maker, bond_tx_hex_taker,
+	payout_address_maker, taproot_xonly_pubkey_hex_maker, payout_address_taker, taproot_xonly_pubkey_hex_taker,
+	musig_pub_nonce_hex_maker, musig_pubkey_compressed_hex_maker, musig_pub_nonce_hex_taker, musig_pubkey_compressed_hex_taker,
+	escrow_psbt_hex, escrow_psbt_txid, escrow_psbt_is_confirmed, signed_escrow_psbt_hex_maker, signed_escrow_psbt_hex_taker,
+	escrow_inputs_hex_maker_csv, escrow_inputs_hex_taker_csv, trade_state)
+VALUES
+	('csv_unsigned_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'unsignedEscrowTxid', 0, 'signedPsbtHexMaker', NULL,
+	'escrowInputsCsvMaker', 'escrowInputsCsvTaker', 'escrow_pending'),
+	('csv_signed_offer', X'a1a1', X'b2b2', 1, 1500, 50, 1234567890, '1BondAddressMaker', '1BondAddressTaker', 750,
+	'bondTxHexMaker', 'bondTxHexTaker', '1PayoutAddressMaker', 'taprootPubkeyHexMaker', '1PayoutAddressTaker',
+	'taprootPubkeyHexTaker', 'musigPubNonceHexMaker', 'musigPubkeyHexMaker', 'musigPubNonceHexTaker',
+	'musigPubkeyHexTaker', 'escrowPsbtHex', 'signedEscrowTxid', 0, 'signedPsbtHexMaker', 'signedPsbtHexTaker',
+	'escrowInputsCsvMaker', 'escrowInputsCsvTaker', 'escrow_pending');
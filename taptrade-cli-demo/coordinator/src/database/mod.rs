@@ -38,12 +38,56 @@ struct AwaitingTakerOffer {
 	taproot_pubkey_hex_maker: String,
 	musig_pub_nonce_hex_maker: String,
 	musig_pubkey_hex_maker: String,
+	escrow_inputs_maker_json: String,
 }
 
 pub struct TraderHappiness {
 	pub maker_happy: Option<bool>,
 	pub taker_happy: Option<bool>,
-	pub escrow_ongoing: bool,
+	pub trade_state: TradeState,
+}
+
+/// versioned schema migrations in the migrations directory, embedded at compile time.
+/// Applied versions are recorded in the _sqlx_migrations table.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// returns the latest applied migration version, 0 for a new or not yet versioned database
+async fn fetch_schema_version(db_pool: &Pool<Sqlite>) -> Result<i64> {
+	let is_versioned = sqlx::query(
+		"SELECT name FROM sqlite_master WHERE type='table' AND name='_sqlx_migrations'",
+	)
+	.fetch_optional(db_pool)
+	.await?
+	.is_some();
+	if !is_versioned {
+		return Ok(0);
+	}
+	let row = sqlx::query("SELECT MAX(version) AS version FROM _sqlx_migrations WHERE success = 1")
+		.fetch_one(db_pool)
+		.await?;
+	Ok(row.try_get::<Option<i64>, _>("version")?.unwrap_or(0))
+}
+
+/// adopts a database created before the trade state was introduced (escrow_ongoing and processing flags
+/// in taken_offers) by altering it into the baseline schema of the first migration. Migration 0001 only
+/// creates missing tables, so it can't detect or change the columns of these legacy tables itself.
+async fn adopt_legacy_schema(db_pool: &Pool<Sqlite>) -> Result<()> {
+	let is_legacy_schema = sqlx::query(
+		"SELECT name FROM pragma_table_info('taken_offers') WHERE name = 'escrow_ongoing'",
+	)
+	.fetch_optional(db_pool)
+	.await?
+	.is_some();
+	if !is_legacy_schema {
+		return Ok(());
+	}
+	info!("Adopting coordinator database created before the trade state was introduced");
+	let mut db_tx = db_pool.begin().await?;
+	sqlx::raw_sql(include_str!("adopt_legacy_schema.sql"))
+		.execute(&mut *db_tx)
+		.await?;
+	db_tx.commit().await?;
+	Ok(())
 }
 
 fn bool_to_sql_int(flag: bool) -> Option<i64> {
@@ -61,7 +105,11 @@ impl CoordinatorDB {
 		debug!("coordinator db path: {}", env::var("DATABASE_PATH")?);
 		let db_path =
 			env::var("DATABASE_PATH").context("Parsing DATABASE_PATH from .env failed")?;
+		Self::open(&db_path).await
+	}
 
+	/// connects to the database at db_path and migrates it to the latest schema version
+	pub async fn open(db_path: &str) -> Result<Self> {
 		// Add the `?mode=rwc` parameter to create the database if it doesn't exist
 		let connection_string = format!("sqlite:{}?mode=rwc", db_path);
 
@@ -70,102 +118,38 @@ impl CoordinatorDB {
 			.await
 			.map_err(|e| anyhow!("Failed to connect to SQLite database: {}", e))?;
 
-		// Create the trades table if it doesn't exist
-		// this table contains requests of makers awaiting submission of bond
-		sqlx::query(
-			// robohash is hash as bytes
-			"CREATE TABLE IF NOT EXISTS maker_requests (
-					robohash BLOB PRIMARY KEY,
-					is_buy_order INTEGER,
-					amount_sat INTEGER NOT NULL,
-					bond_ratio INTEGER NOT NULL,
-					offer_duration_ts INTEGER NOT NULL,
-					bond_address TEXT NOT NULL,
-					bond_amount_sat INTEGER NOT NULL,
-					escrow_locking_input_amount_without_trade_sum INTEGER NOT NULL
-				)",
-		)
-		.execute(&db_pool)
-		.await?;
-
-		// this table contains offers that are active in the orderbook awaiting a taker
-		sqlx::query(
-			// robohash is hash as bytes
-			"CREATE TABLE IF NOT EXISTS active_maker_offers (
-				offer_id TEXT PRIMARY KEY,
-				robohash BLOB,
-				is_buy_order INTEGER,
-				amount_sat INTEGER NOT NULL,
-				bond_ratio INTEGER NOT NULL,
-				offer_duration_ts INTEGER NOT NULL,
-				bond_address TEXT NOT NULL,
-				bond_amount_sat INTEGER NOT NULL,
-				escrow_locking_input_amount_without_trade_sum INTEGER,
-				bond_tx_hex TEXT NOT NULL,
-				payout_address TEXT NOT NULL,
-				change_address_maker TEXT NOT NULL,
-				escrow_inputs_hex_maker_csv TEXT NOT NULL,
-				taproot_pubkey_hex_maker TEXT NOT NULL,
-				musig_pub_nonce_hex TEXT NOT NULL,
-				musig_pubkey_hex TEXT NOT NULL,
-				taker_bond_address TEXT
-			)",
-		)
-		.execute(&db_pool)
-		.await?;
-
-		// this table contains offers that are taken and are in the trade process
-		sqlx::query(
-			"CREATE TABLE IF NOT EXISTS taken_offers (
-				offer_id TEXT PRIMARY KEY,
-				robohash_maker BLOB,
-				robohash_taker BLOB,
-				is_buy_order INTEGER,
-				amount_sat INTEGER NOT NULL,
-				bond_ratio INTEGER NOT NULL,
-				offer_duration_ts INTEGER NOT NULL,
-				bond_address_maker TEXT NOT NULL,
-				bond_address_taker TEXT NOT NULL,
-				bond_amount_sat INTEGER NOT NULL,
-				bond_tx_hex_maker TEXT NOT NULL,
-				bond_tx_hex_taker TEXT NOT NULL,
-				payout_address_maker TEXT NOT NULL,
-				taproot_xonly_pubkey_hex_maker TEXT NOT NULL,
-				payout_address_taker TEXT NOT NULL,
-				taproot_xonly_pubkey_hex_taker TEXT NOT NULL,
-				musig_pub_nonce_hex_maker TEXT NOT NULL,
-				musig_pubkey_compressed_hex_maker TEXT NOT NULL,
-				musig_pub_nonce_hex_taker TEXT NOT NULL,
-				musig_pubkey_compressed_hex_taker TEXT NOT NULL,
-				musig_partial_sig_hex_maker TEXT,
-				musig_partial_sig_hex_taker TEXT,
-				escrow_psbt_hex TEXT NOT NULL,
-				escrow_psbt_txid TEXT NOT NULL,
-				signed_escrow_psbt_hex_maker TEXT,
-				signed_escrow_psbt_hex_taker TEXT,
-				escrow_psbt_is_confirmed INTEGER,
-				maker_happy INTEGER,
-				taker_happy INTEGER,
-				escrow_ongoing INTEGER NOT NULL,
-				escrow_winner_robohash TEXT,
-				escrow_taproot_pk_coordinator TEXT,
-				escrow_amount_maker_sat INTEGER,
-				escrow_amount_taker_sat INTEGER,
-				escrow_fee_per_participant INTEGER,
-				escrow_output_descriptor TEXT,
-				payout_transaction_psbt_hex TEXT,
-				processing INTEGER NOT NULL
-			)", // escrow_psbt_is_confirmed will be set 1 once the escrow psbt is confirmed onchain
-		)
-		.execute(&db_pool)
-		.await?;
-		debug!("Database initialized");
+		// refuse to touch a database migrated by a newer coordinator version
+		let latest_known_version = MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0);
+		let schema_version = fetch_schema_version(&db_pool).await?;
+		if schema_version > latest_known_version {
+			return Err(anyhow!(
+				"Database schema version {} is newer than the latest version {} known to this coordinator, refusing to start",
+				schema_version,
+				latest_known_version
+			));
+		}
+		adopt_legacy_schema(&db_pool)
+			.await
+			.context("Adopting the legacy coordinator database failed")?;
+		MIGRATOR
+			.run(&db_pool)
+			.await
+			.context("Migrating the coordinator database failed")?;
+		debug!(
+			"Database initialized, schema version {}",
+			fetch_schema_version(&db_pool).await?
+		);
 		let shared_db_pool = Arc::new(db_pool);
 		Ok(Self {
 			db_pool: shared_db_pool,
 		})
 	}
 
+	/// returns the version of the latest migration applied to the database
+	pub async fn schema_version(&self) -> Result<i64> {
+		fetch_schema_version(&self.db_pool).await
+	}
+
 	/// insert a new maker request to create an offer in the table
 	pub async fn insert_new_maker_request(
 		&self,
@@ -259,7 +243,7 @@ impl CoordinatorDB {
 		sqlx::query(
 			"INSERT OR REPLACE INTO active_maker_offers (offer_id, robohash, is_buy_order, amount_sat,
 					bond_ratio, offer_duration_ts, bond_address, bond_amount_sat, bond_tx_hex, payout_address, taproot_pubkey_hex_maker, musig_pub_nonce_hex, musig_pubkey_hex, taker_bond_address,
-					change_address_maker, escrow_inputs_hex_maker_csv, escrow_locking_input_amount_without_trade_sum)
+					change_address_maker, escrow_inputs_maker_json, escrow_locking_input_amount_without_trade_sum)
 					VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
 		)
 		.bind(offer_id)
@@ -277,7 +261,7 @@ impl CoordinatorDB {
 		.bind(data.musig_pubkey_hex.clone())
 		.bind(taker_bond_address)
 		.bind(data.client_change_address.clone())
-		.bind(data.bdk_psbt_inputs_hex_csv.clone())
+		.bind(serde_json::to_string(&data.escrow_inputs)?)
 		.bind(remaining_offer_information.escrow_locking_input_amount_without_trade_sum as i64)
 		.execute(&*self.db_pool)
 		.await?;
@@ -296,11 +280,12 @@ impl CoordinatorDB {
 			requested_offer
 		);
 		let fetched_offers = sqlx::query_as::<_, (String, i64, i64, String, i64)> (
-            "SELECT offer_id, amount_sat, bond_amount_sat, taker_bond_address, escrow_locking_input_amount_without_trade_sum FROM active_maker_offers WHERE is_buy_order = ? AND amount_sat BETWEEN ? AND ?",
+            "SELECT offer_id, amount_sat, bond_amount_sat, taker_bond_address, escrow_locking_input_amount_without_trade_sum FROM active_maker_offers WHERE is_buy_order = ? AND amount_sat BETWEEN ? AND ? AND offer_duration_ts > ?",
         )
         .bind(requested_offer.buy_offers)
         .bind(requested_offer.amount_min_sat as i64)
         .bind(requested_offer.amount_max_sat as i64)
+        .bind(unix_timestamp_now()? as i64)
         .fetch_all(&*self.db_pool)
         .await?;
 
@@ -347,14 +332,84 @@ impl CoordinatorDB {
 		})
 	}
 
+	/// fetches the taproot pubkey of the maker of an offer in the orderbook, None if there is no offer
+	/// with this id created by the robohash
+	pub async fn fetch_maker_taproot_pubkey(
+		&self,
+		offer_id_hex: &str,
+		robohash_hex: &str,
+	) -> Result<Option<String>> {
+		let row = sqlx::query(
+			"SELECT taproot_pubkey_hex_maker FROM active_maker_offers WHERE offer_id = ? AND robohash = ?",
+		)
+		.bind(offer_id_hex)
+		.bind(hex::decode(robohash_hex)?)
+		.fetch_optional(&*self.db_pool)
+		.await?;
+
+		match row {
+			Some(row) => Ok(Some(row.try_get("taproot_pubkey_hex_maker")?)),
+			None => Ok(None),
+		}
+	}
+
+	/// returns the taproot pubkey the trader registered with its bond, used to authenticate its requests.
+	/// Looks in the taken offers first and falls back to the orderbook for makers waiting for a taker.
+	pub async fn fetch_trader_taproot_pubkey(
+		&self,
+		offer_id_hex: &str,
+		robohash_hex: &str,
+	) -> Result<Option<String>> {
+		let robohash = hex::decode(robohash_hex)?;
+		let row = sqlx::query(
+			"SELECT robohash_maker, taproot_xonly_pubkey_hex_maker, taproot_xonly_pubkey_hex_taker
+			FROM taken_offers WHERE offer_id = ? AND (robohash_maker = ? OR robohash_taker = ?)",
+		)
+		.bind(offer_id_hex)
+		.bind(&robohash)
+		.bind(&robohash)
+		.fetch_optional(&*self.db_pool)
+		.await?;
+
+		match row {
+			Some(row) => {
+				if row.try_get::<Vec<u8>, _>("robohash_maker")? == robohash {
+					Ok(Some(row.try_get("taproot_xonly_pubkey_hex_maker")?))
+				} else {
+					Ok(Some(row.try_get("taproot_xonly_pubkey_hex_taker")?))
+				}
+			}
+			None => {
+				self.fetch_maker_taproot_pubkey(offer_id_hex, robohash_hex)
+					.await
+			}
+		}
+	}
+
+	/// deletes a cancelled offer from the orderbook (active_maker_offers) table, returns false
+	/// if the offer is not in the orderbook anymore
+	pub async fn delete_offer_from_orderbook(
+		&self,
+		offer_id_hex: &str,
+		robohash_hex: &str,
+	) -> Result<bool> {
+		let result =
+			sqlx::query("DELETE FROM active_maker_offers WHERE offer_id = ? AND robohash = ?")
+				.bind(offer_id_hex)
+				.bind(hex::decode(robohash_hex)?)
+				.execute(&*self.db_pool)
+				.await?;
+		Ok(result.rows_affected() > 0)
+	}
+
 	/// used to fetch and delete the offer from the orderbook (active_maker_offers) table
 	async fn fetch_and_delete_offer_from_public_offers_table(
 		&self,
 		offer_id_hex: &str,
 	) -> Result<AwaitingTakerOffer> {
-		let fetched_values = sqlx::query_as::<_, (Vec<u8>, i32, i64, i32, i64, String, i64, String, String, String, String, String)> (
+		let fetched_values = sqlx::query_as::<_, (Vec<u8>, i32, i64, i32, i64, String, i64, String, String, String, String, String, String)> (
 			"SELECT robohash, is_buy_order, amount_sat, bond_ratio, offer_duration_ts, bond_address, bond_amount_sat, bond_tx_hex, payout_address, taproot_pubkey_hex_maker,
-			musig_pub_nonce_hex, musig_pubkey_hex FROM active_maker_offers WHERE offer_id = ?",
+			musig_pub_nonce_hex, musig_pubkey_hex, escrow_inputs_maker_json FROM active_maker_offers WHERE offer_id = ?",
 		)
 		.bind(offer_id_hex)
 		.fetch_one(&*self.db_pool)
@@ -380,6 +435,7 @@ impl CoordinatorDB {
 			taproot_pubkey_hex_maker: fetched_values.9,
 			musig_pub_nonce_hex_maker: fetched_values.10,
 			musig_pubkey_hex_maker: fetched_values.11,
+			escrow_inputs_maker_json: fetched_values.12,
 		})
 	}
 
@@ -401,9 +457,10 @@ impl CoordinatorDB {
 				"INSERT OR REPLACE INTO taken_offers (offer_id, robohash_maker, robohash_taker, is_buy_order, amount_sat,
 						bond_ratio, offer_duration_ts, bond_address_maker, bond_address_taker, bond_amount_sat, bond_tx_hex_maker,
 						bond_tx_hex_taker, payout_address_maker, payout_address_taker, taproot_xonly_pubkey_hex_maker, taproot_xonly_pubkey_hex_taker, musig_pub_nonce_hex_maker, musig_pubkey_compressed_hex_maker,
-						musig_pub_nonce_hex_taker, musig_pubkey_compressed_hex_taker, escrow_psbt_hex, escrow_psbt_txid, escrow_output_descriptor, escrow_psbt_is_confirmed, escrow_ongoing,
-						escrow_taproot_pk_coordinator, escrow_amount_maker_sat, escrow_amount_taker_sat, escrow_fee_per_participant, processing)
-						VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+						musig_pub_nonce_hex_taker, musig_pubkey_compressed_hex_taker, escrow_psbt_hex, escrow_psbt_txid, escrow_output_descriptor, escrow_psbt_is_confirmed,
+						escrow_taproot_pk_coordinator, escrow_amount_maker_sat, escrow_amount_taker_sat, escrow_fee_per_participant, escrow_inputs_maker_json,
+						escrow_inputs_taker_json, trade_state)
+						VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
 			)
 			.bind(public_offer.offer_id)
 			.bind(public_offer.robohash_maker)
@@ -429,12 +486,13 @@ impl CoordinatorDB {
 			.bind(&escrow_tx_data.escrow_tx_txid)
 			.bind(&escrow_tx_data.escrow_output_descriptor)
 			.bind(0)
-			.bind(0)
 			.bind(&escrow_tx_data.coordinator_xonly_escrow_pk)
 			.bind(escrow_tx_data.escrow_amount_maker_sat as i64)
 			.bind(escrow_tx_data.escrow_amount_taker_sat as i64)
 			.bind(escrow_tx_data.escrow_fee_sat_per_participant as i64)
-			.bind(0)
+			.bind(public_offer.escrow_inputs_maker_json)
+			.bind(serde_json::to_string(&trade_and_taker_info.trade_data.escrow_inputs)?)
+			.bind(TradeState::EscrowPending.as_str())
 			.execute(&*self.db_pool)
 			.await?;
 
@@ -448,7 +506,8 @@ impl CoordinatorDB {
 	) -> Result<Option<EscrowPsbt>> {
 		let offer = sqlx::query(
 			"SELECT escrow_output_descriptor, escrow_amount_maker_sat,
-			escrow_amount_taker_sat, escrow_fee_per_participant, escrow_taproot_pk_coordinator, escrow_psbt_hex, escrow_psbt_txid
+			escrow_amount_taker_sat, escrow_fee_per_participant, escrow_taproot_pk_coordinator, escrow_psbt_hex, escrow_psbt_txid,
+			musig_pubkey_compressed_hex_maker, musig_pubkey_compressed_hex_taker
 			FROM taken_offers WHERE offer_id = ?",
 		)
 		.bind(offer_id_hex)
@@ -467,6 +526,10 @@ impl CoordinatorDB {
 			offer.try_get::<String, _>("escrow_taproot_pk_coordinator")?;
 		let escrow_psbt_hex = offer.try_get::<String, _>("escrow_psbt_hex")?;
 		let escrow_tx_txid = offer.try_get::<String, _>("escrow_psbt_txid")?;
+		let musig_pubkey_compressed_hex_maker =
+			offer.try_get::<String, _>("musig_pubkey_compressed_hex_maker")?;
+		let musig_pubkey_compressed_hex_taker =
+			offer.try_get::<String, _>("musig_pubkey_compressed_hex_taker")?;
 
 		Ok(Some(EscrowPsbt {
 			escrow_tx_txid,
@@ -476,6 +539,8 @@ impl CoordinatorDB {
 			escrow_amount_maker_sat,
 			escrow_amount_taker_sat,
 			escrow_fee_sat_per_participant,
+			musig_pubkey_compressed_hex_maker,
+			musig_pubkey_compressed_hex_taker,
 		}))
 	}
 
@@ -503,12 +568,73 @@ impl CoordinatorDB {
 				trade_id_hex: row.get("offer_id"),
 				requirements,
 				table: Table::Orderbook,
+				escrow_txid: None,
 			};
 			bonds.push(bond);
 		}
+
+		// the bonds of taken offers stay monitored until the escrow transaction is confirmed
+		let mut rows_taken = sqlx::query(
+			"SELECT offer_id, robohash_maker, robohash_taker, bond_address_maker, bond_address_taker, bond_amount_sat,
+			amount_sat, bond_tx_hex_maker, bond_tx_hex_taker, escrow_psbt_txid FROM taken_offers
+			WHERE escrow_psbt_is_confirmed = 0 AND trade_aborted_by_robohash IS NULL",
+		)
+		.fetch(&*self.db_pool);
+		while let Some(row) = rows_taken.next().await {
+			let row = row?;
+			for trader in ["maker", "taker"] {
+				let requirements = BondRequirements {
+					bond_address: row.get(format!("bond_address_{}", trader).as_str()),
+					locking_amount_sat: row.get::<i64, _>("bond_amount_sat") as u64,
+					min_input_sum_sat: row.get::<i64, _>("amount_sat") as u64,
+				};
+
+				let bond = MonitoringBond {
+					bond_tx_hex: row.get(format!("bond_tx_hex_{}", trader).as_str()),
+					robot: row.get(format!("robohash_{}", trader).as_str()),
+					trade_id_hex: row.get("offer_id"),
+					requirements,
+					table: Table::ActiveTrades,
+					escrow_txid: Some(row.get("escrow_psbt_txid")),
+				};
+				bonds.push(bond);
+			}
+		}
 		Ok(bonds)
 	}
 
+	/// removes all offers from the orderbook (active_maker_offers) table whose offer duration passed
+	/// before `now_ts`, returns the ids of the removed offers. This also ends the monitoring of their bonds.
+	pub async fn delete_expired_offers(&self, now_ts: u64) -> Result<Vec<String>> {
+		let expired_offer_ids = sqlx::query(
+			"DELETE FROM active_maker_offers WHERE offer_duration_ts <= ? RETURNING offer_id",
+		)
+		.bind(now_ts as i64)
+		.fetch_all(&*self.db_pool)
+		.await?
+		.iter()
+		.map(|row| row.try_get::<String, _>("offer_id"))
+		.collect::<Result<Vec<String>, _>>()?;
+		Ok(expired_offer_ids)
+	}
+
+	/// removes maker requests whose bond did not arrive within `bond_timeout_secs` or whose
+	/// offer duration already passed, returns the number of removed requests
+	pub async fn delete_stale_maker_requests(
+		&self,
+		now_ts: u64,
+		bond_timeout_secs: u64,
+	) -> Result<u64> {
+		let result = sqlx::query(
+			"DELETE FROM maker_requests WHERE offer_duration_ts <= ? OR request_created_ts <= ?",
+		)
+		.bind(now_ts as i64)
+		.bind(now_ts.saturating_sub(bond_timeout_secs) as i64)
+		.execute(&*self.db_pool)
+		.await?;
+		Ok(result.rows_affected())
+	}
+
 	/// removes an offer from the orderbook (active_maker_offers) table, gets called when a bond violation is detected
 	pub async fn remove_violating_bond(&self, bond: &MonitoringBond) -> Result<()> {
 		if bond.table == Table::Orderbook {
@@ -517,6 +643,10 @@ impl CoordinatorDB {
 				.execute(&*self.db_pool)
 				.await?;
 			debug!("Removed violating bond offer from orderbook");
+		} else if bond.table == Table::ActiveTrades {
+			self.abort_taken_offer(&bond.trade_id_hex, &hex::encode(&bond.robot))
+				.await?;
+			debug!("Aborted taken offer of violating bond");
 		} else {
 			return Err(anyhow!(
 				"Invalid table type when trying to remove violating bond from db"
@@ -525,6 +655,168 @@ impl CoordinatorDB {
 		Ok(())
 	}
 
+	/// returns the payout address of the counterparty of the violating trader in a taken offer,
+	/// None if the trade already got aborted by a bond violation of the counterparty
+	pub async fn fetch_compensation_address(
+		&self,
+		offer_id: &str,
+		violating_robohash: &str,
+	) -> Result<Option<String>> {
+		let row = sqlx::query(
+			"SELECT robohash_maker, payout_address_maker, payout_address_taker, trade_aborted_by_robohash
+			FROM taken_offers WHERE offer_id = ?",
+		)
+		.bind(offer_id)
+		.fetch_one(&*self.db_pool)
+		.await?;
+		if row
+			.try_get::<Option<String>, _>("trade_aborted_by_robohash")?
+			.is_some()
+		{
+			return Ok(None);
+		}
+		if hex::encode(row.try_get::<Vec<u8>, _>("robohash_maker")?) == violating_robohash {
+			Ok(Some(row.try_get("payout_address_taker")?))
+		} else {
+			Ok(Some(row.try_get("payout_address_maker")?))
+		}
+	}
+
+	/// marks a taken offer as aborted because the trader with the given robohash violated its bond,
+	/// the trade won't continue and its bonds are not monitored anymore
+	pub async fn abort_taken_offer(&self, offer_id: &str, violating_robohash: &str) -> Result<()> {
+		sqlx::query(
+			"UPDATE taken_offers SET trade_aborted_by_robohash = ? WHERE offer_id = ? AND trade_aborted_by_robohash IS NULL",
+		)
+		.bind(violating_robohash)
+		.bind(offer_id)
+		.execute(&*self.db_pool)
+		.await?;
+		self.set_trade_state(offer_id, TradeState::Aborted).await
+	}
+
+	/// returns the robohash (hex) of the trader whose bond violation aborted the trade, None if the trade is not aborted
+	pub async fn fetch_trade_aborted_by(&self, offer_id: &str) -> Result<Option<String>> {
+		let row =
+			sqlx::query("SELECT trade_aborted_by_robohash FROM taken_offers WHERE offer_id = ?")
+				.bind(offer_id)
+				.fetch_one(&*self.db_pool)
+				.await?;
+		Ok(row.try_get::<Option<String>, _>("trade_aborted_by_robohash")?)
+	}
+
+	/// records a bond published as punishment so it can be monitored until it confirms. If a compensation
+	/// address is passed the bond output will be forwarded to it.
+	pub async fn insert_published_bond(
+		&self,
+		bond: &MonitoringBond,
+		compensation_address: Option<&str>,
+	) -> Result<()> {
+		let bond_tx: Transaction = deserialize(&hex::decode(&bond.bond_tx_hex)?)?;
+		sqlx::query(
+			"INSERT OR IGNORE INTO published_bonds (bond_txid, offer_id, robohash, bond_tx_hex, bond_address, status,
+			compensation_address) VALUES (?, ?, ?, ?, ?, ?, ?)",
+		)
+		.bind(bond_tx.txid().to_string())
+		.bind(&bond.trade_id_hex)
+		.bind(hex::encode(&bond.robot))
+		.bind(&bond.bond_tx_hex)
+		.bind(&bond.requirements.bond_address)
+		.bind(PunishmentStatus::Pending.as_str())
+		.bind(compensation_address)
+		.execute(&*self.db_pool)
+		.await?;
+		Ok(())
+	}
+
+	/// fetches all published bonds which are neither confirmed nor conflicted yet
+	pub async fn fetch_pending_published_bonds(&self) -> Result<Vec<PublishedBond>> {
+		let rows = sqlx::query(
+			"SELECT bond_txid, offer_id, robohash, bond_tx_hex, bond_address, cpfp_tx_hex, cpfp_feerate_sat_vb,
+			cpfp_block_height, status, compensation_address, compensation_txid FROM published_bonds WHERE status = ?",
+		)
+		.bind(PunishmentStatus::Pending.as_str())
+		.fetch_all(&*self.db_pool)
+		.await?;
+
+		let mut published_bonds = Vec::new();
+		for row in rows {
+			published_bonds.push(PublishedBond {
+				bond_txid: row.try_get("bond_txid")?,
+				offer_id_hex: row.try_get("offer_id")?,
+				robohash_hex: row.try_get("robohash")?,
+				bond_tx_hex: row.try_get("bond_tx_hex")?,
+				bond_address: row.try_get("bond_address")?,
+				cpfp_tx_hex: row.try_get("cpfp_tx_hex")?,
+				cpfp_feerate_sat_vb: row.try_get("cpfp_feerate_sat_vb")?,
+				cpfp_block_height: row
+					.try_get::<Option<i64>, _>("cpfp_block_height")?
+					.map(|height| height as u64),
+				status: PunishmentStatus::from_str(&row.try_get::<String, _>("status")?)?,
+				compensation_address: row.try_get("compensation_address")?,
+				compensation_txid: row.try_get("compensation_txid")?,
+			});
+		}
+		Ok(published_bonds)
+	}
+
+	/// stores the latest cpfp child of a published bond and the block height it got broadcasted at.
+	/// If the bond gets forwarded to a compensation address the child is the compensation transaction.
+	pub async fn update_published_bond_cpfp(
+		&self,
+		bond_txid: &str,
+		cpfp_child: &CpfpChild,
+		block_height: u64,
+	) -> Result<()> {
+		sqlx::query(
+			"UPDATE published_bonds SET cpfp_tx_hex = ?, cpfp_feerate_sat_vb = ?, cpfp_block_height = ?,
+			compensation_txid = CASE WHEN compensation_address IS NULL THEN NULL ELSE ? END,
+			compensation_amount_sat = CASE WHEN compensation_address IS NULL THEN NULL ELSE ? END
+			WHERE bond_txid = ?",
+		)
+		.bind(&cpfp_child.tx_hex)
+		.bind(cpfp_child.package_feerate_sat_vb)
+		.bind(block_height as i64)
+		.bind(&cpfp_child.txid)
+		.bind(cpfp_child.output_amount_sat as i64)
+		.bind(bond_txid)
+		.execute(&*self.db_pool)
+		.await?;
+		Ok(())
+	}
+
+	/// records the mined compensation tx of a published bond, e.g. an earlier child replaced by the stored one
+	pub async fn update_published_bond_compensation(
+		&self,
+		bond_txid: &str,
+		compensation_txid: &str,
+		compensation_amount_sat: u64,
+	) -> Result<()> {
+		sqlx::query(
+			"UPDATE published_bonds SET compensation_txid = ?, compensation_amount_sat = ? WHERE bond_txid = ?",
+		)
+		.bind(compensation_txid)
+		.bind(compensation_amount_sat as i64)
+		.bind(bond_txid)
+		.execute(&*self.db_pool)
+		.await?;
+		Ok(())
+	}
+
+	/// sets the outcome of the punishment once the bond confirmed or a conflicting transaction got mined
+	pub async fn set_published_bond_status(
+		&self,
+		bond_txid: &str,
+		status: &PunishmentStatus,
+	) -> Result<()> {
+		sqlx::query("UPDATE published_bonds SET status = ? WHERE bond_txid = ?")
+			.bind(status.as_str())
+			.bind(bond_txid)
+			.execute(&*self.db_pool)
+			.await?;
+		Ok(())
+	}
+
 	/// fetches all txids of escrow transactions that have the flag escrow_psbt_is_confirmed set to 0
 	/// used to check if theses txids are confirmed onchain
 	pub async fn fetch_unconfirmed_escrow_txids(&self) -> Result<Vec<String>> {
@@ -541,17 +833,40 @@ impl CoordinatorDB {
 		Ok(txids)
 	}
 
-	/// sets all passed escrow txids to confirmed
-	pub async fn confirm_bond_txids(&self, confirmed_txids: Vec<String>) -> Result<()> {
+	/// sets all passed escrow txids to confirmed and moves their pending trades into the escrow confirmed state,
+	/// returns the offer ids of the trades that got confirmed
+	pub async fn confirm_bond_txids(&self, confirmed_txids: Vec<String>) -> Result<Vec<String>> {
+		let mut confirmed_offers = Vec::new();
 		for txid in confirmed_txids {
 			sqlx::query(
 				"UPDATE taken_offers SET escrow_psbt_is_confirmed = 1 WHERE escrow_psbt_txid = ?",
 			)
-			.bind(txid)
+			.bind(&txid)
 			.execute(&*self.db_pool)
 			.await?;
+
+			let pending_offers = sqlx::query(
+				"SELECT offer_id FROM taken_offers WHERE escrow_psbt_txid = ? AND trade_state = ?",
+			)
+			.bind(&txid)
+			.bind(TradeState::EscrowPending.as_str())
+			.fetch_all(&*self.db_pool)
+			.await?;
+			for row in pending_offers {
+				let offer_id: String = row.try_get("offer_id")?;
+				if self
+					.transition_trade_state(
+						&offer_id,
+						TradeState::EscrowPending,
+						TradeState::EscrowConfirmed,
+					)
+					.await?
+				{
+					confirmed_offers.push(offer_id);
+				}
+			}
 		}
-		Ok(())
+		Ok(confirmed_offers)
 	}
 
 	/// used to check if txid is set to confirmed in the db
@@ -660,6 +975,33 @@ impl CoordinatorDB {
 		}
 	}
 
+	/// fetches the unsigned escrow locking psbt and the escrow inputs contributed by the trader with
+	/// the given robohash, used to validate the signed escrow psbt submitted by the trader
+	pub async fn fetch_escrow_psbt_and_trader_inputs(
+		&self,
+		offer_id_hex: &str,
+		robohash_hex: &str,
+	) -> Result<(String, Vec<EscrowInput>)> {
+		let is_maker = self
+			.is_maker_in_taken_offers(offer_id_hex, robohash_hex)
+			.await?;
+
+		let row = sqlx::query(
+			"SELECT escrow_psbt_hex, escrow_inputs_maker_json, escrow_inputs_taker_json FROM taken_offers WHERE offer_id = ?",
+		)
+		.bind(offer_id_hex)
+		.fetch_one(&*self.db_pool)
+		.await?;
+
+		let escrow_psbt: String = row.try_get("escrow_psbt_hex")?;
+		let trader_inputs_json: String = if is_maker {
+			row.try_get("escrow_inputs_maker_json")?
+		} else {
+			row.try_get("escrow_inputs_taker_json")?
+		};
+		Ok((escrow_psbt, serde_json::from_str(&trader_inputs_json)?))
+	}
+
 	/// used to fetch both signed escrow locking psbts from the db
 	pub async fn fetch_both_signed_escrow_psbts(
 		&self,
@@ -691,7 +1033,9 @@ impl CoordinatorDB {
 		Ok(status.get::<i64, _>("escrow_psbt_is_confirmed") == 1)
 	}
 
-	/// used to set that a trader is satisfied with the trade (true)
+	/// used to set that a trader is satisfied with the trade (true) or requests escrow (false).
+	/// Requesting escrow moves the trade into dispute, the flag and the trade state are written in one db
+	/// transaction and nothing is written if the trade state doesn't allow the change.
 	pub async fn set_trader_happy_field(
 		&self,
 		offer_id: &str,
@@ -706,19 +1050,53 @@ impl CoordinatorDB {
 			"UPDATE taken_offers SET taker_happy = ? WHERE offer_id = ?"
 		};
 
-		sqlx::query(query)
-			.bind(bool_to_sql_int(is_happy))
+		let mut db_tx = self.db_pool.begin().await?;
+		let row = sqlx::query("SELECT trade_state FROM taken_offers WHERE offer_id = ?")
 			.bind(offer_id)
-			.execute(&*self.db_pool)
+			.fetch_one(&mut *db_tx)
+			.await?;
+		let trade_state = TradeState::from_str(&row.try_get::<String, _>("trade_state")?)?;
+
+		if is_happy {
+			// once a trader requested escrow the trade can't be confirmed anymore
+			if !matches!(
+				trade_state,
+				TradeState::EscrowConfirmed
+					| TradeState::KeyspendPayoutProcessing
+					| TradeState::KeyspendPayoutReady
+			) {
+				return Err(anyhow!(
+					"Trade of offer {} can't be confirmed in state {}",
+					offer_id,
+					trade_state
+				));
+			}
+		} else if trade_state != TradeState::Disputed {
+			if !trade_state.can_transition_to(TradeState::Disputed) {
+				return Err(anyhow!(
+					"Invalid trade state transition from {} to {} for offer {}",
+					trade_state,
+					TradeState::Disputed,
+					offer_id
+				));
+			}
+			sqlx::query(
+				"UPDATE taken_offers SET trade_state = ? WHERE offer_id = ? AND trade_state = ?",
+			)
+			.bind(TradeState::Disputed.as_str())
+			.bind(offer_id)
+			.bind(trade_state.as_str())
+			.execute(&mut *db_tx)
 			.await?;
-
-		if !is_happy {
-			sqlx::query("UPDATE taken_offers SET escrow_ongoing = 1 WHERE offer_id = ?")
-				.bind(offer_id)
-				.execute(&*self.db_pool)
-				.await?;
 		}
 
+		// stored as 0 if the trader requested escrow, NULL means the trader didn't answer yet
+		sqlx::query(query)
+			.bind(is_happy as i64)
+			.bind(offer_id)
+			.execute(&mut *db_tx)
+			.await?;
+		db_tx.commit().await?;
 		Ok(())
 	}
 
@@ -726,7 +1104,7 @@ impl CoordinatorDB {
 	/// if escrow is required
 	pub async fn fetch_trader_happiness(&self, offer_id: &str) -> Result<TraderHappiness> {
 		let row = sqlx::query(
-			"SELECT maker_happy, taker_happy, escrow_ongoing FROM taken_offers WHERE offer_id = ?",
+			"SELECT maker_happy, taker_happy, trade_state FROM taken_offers WHERE offer_id = ?",
 		)
 		.bind(offer_id)
 		.fetch_one(&*self.db_pool)
@@ -734,12 +1112,12 @@ impl CoordinatorDB {
 
 		let maker_happy: Option<i64> = row.try_get::<Option<i64>, _>("maker_happy")?;
 		let taker_happy: Option<i64> = row.try_get::<Option<i64>, _>("taker_happy")?;
-		let escrow_ongoing: i64 = row.try_get::<i64, _>("escrow_ongoing")?;
+		let trade_state: String = row.try_get("trade_state")?;
 
 		Ok(TraderHappiness {
 			maker_happy: maker_happy.map(|v| v != 0),
 			taker_happy: taker_happy.map(|v| v != 0),
-			escrow_ongoing: escrow_ongoing != 0,
+			trade_state: TradeState::from_str(&trade_state)?,
 		})
 	}
 
@@ -793,29 +1171,34 @@ impl CoordinatorDB {
 	pub async fn fetch_maker_escrow_psbt_data(
 		&self,
 		trade_id: &str,
+		network: Network,
 	) -> Result<EscrowPsbtConstructionData> {
 		let row = sqlx::query(
-			"SELECT escrow_inputs_hex_maker_csv, change_address_maker, taproot_pubkey_hex_maker, musig_pubkey_hex FROM active_maker_offers WHERE offer_id = ?",
+			"SELECT escrow_inputs_maker_json, change_address_maker, taproot_pubkey_hex_maker, musig_pubkey_hex FROM active_maker_offers WHERE offer_id = ?",
 		)
 		.bind(trade_id)
 		.fetch_one(&*self.db_pool)
 		.await?;
 
-		let deserialized_inputs = csv_hex_to_bdk_input(row.get("escrow_inputs_hex_maker_csv"))?;
+		let escrow_inputs: Vec<EscrowInput> =
+			serde_json::from_str(row.get("escrow_inputs_maker_json"))?;
+		let deserialized_inputs = escrow_inputs_to_bdk_input(&escrow_inputs)?;
 		let change_address: String = row.get("change_address_maker");
 
 		Ok(EscrowPsbtConstructionData {
 			escrow_input_utxos: deserialized_inputs,
-			change_address: Address::from_str(&change_address)?.assume_checked(),
+			change_address: Address::from_str(&change_address)?
+				.require_network(network)
+				.context("Maker change address wrong network")?,
 			taproot_xonly_pubkey_hex: row.get("taproot_pubkey_hex_maker"),
 			musig_pubkey_compressed_hex: row.get("musig_pubkey_hex"),
 		})
 	}
 
 	/// fetch the data required to construct the musig keyspend payout transaction to be signed by the traders on payout initialization
-	pub async fn fetch_payout_data(&self, trade_id: &str) -> Result<PayoutData> {
+	pub async fn fetch_payout_data(&self, trade_id: &str, network: Network) -> Result<PayoutData> {
 		let row = sqlx::query(
-			"SELECT is_buy_order, escrow_output_descriptor, payout_address_maker,
+			"SELECT escrow_output_descriptor, payout_address_maker,
 			payout_address_taker, musig_pub_nonce_hex_maker, musig_pub_nonce_hex_taker,
 			escrow_amount_maker_sat, escrow_amount_taker_sat, musig_pubkey_compressed_hex_maker,
 			musig_pubkey_compressed_hex_taker
@@ -831,18 +1214,10 @@ impl CoordinatorDB {
 		let payout_address_taker = row.try_get("payout_address_taker")?;
 		let musig_pub_nonce_hex_maker: &str = row.try_get("musig_pub_nonce_hex_maker")?;
 		let musig_pub_nonce_hex_taker: &str = row.try_get("musig_pub_nonce_hex_taker")?;
-		// this hack needs a better solution
-		let (payout_amount_maker, payout_amount_taker) = if row.get::<i64, _>("is_buy_order") == 1 {
-			(
-				row.get::<i64, _>("escrow_amount_taker_sat") as u64,
-				row.get::<i64, _>("escrow_amount_maker_sat") as u64,
-			)
-		} else {
-			(
-				row.get::<i64, _>("escrow_amount_maker_sat") as u64,
-				row.get::<i64, _>("escrow_amount_taker_sat") as u64,
-			)
-		};
+		// each trader receives the escrow amount of the counterparty: the buyer gets the trade amount and
+		// its bond back, the seller its bond
+		let payout_amount_maker = row.try_get::<i64, _>("escrow_amount_taker_sat")? as u64;
+		let payout_amount_taker = row.try_get::<i64, _>("escrow_amount_maker_sat")? as u64;
 		let musig_pubkey_hex_maker: &str = row.try_get("musig_pubkey_compressed_hex_maker")?;
 		let musig_pubkey_hex_taker: &str = row.try_get("musig_pubkey_compressed_hex_taker")?;
 
@@ -856,21 +1231,191 @@ impl CoordinatorDB {
 			musig_pub_nonce_hex_taker,
 			musig_pubkey_hex_maker,
 			musig_pubkey_hex_taker,
+			network,
 		)
 	}
 
-	/// insert the keyspend payout transaction into the db
+	/// insert the keyspend payout transaction into the db and move the trade to keyspend payout ready.
+	/// Returns false and stores nothing if the trade left the keyspend payout processing state meanwhile.
 	pub async fn insert_keyspend_payout_psbt(
 		&self,
 		offer_id_hex: &str,
 		payout_psbt_hex: &str,
-	) -> Result<()> {
-		sqlx::query("UPDATE taken_offers SET payout_transaction_psbt_hex = ? WHERE offer_id = ?")
-			.bind(payout_psbt_hex)
+	) -> Result<bool> {
+		self.insert_payout_psbt(
+			"payout_transaction_psbt_hex",
+			offer_id_hex,
+			payout_psbt_hex,
+			TradeState::KeyspendPayoutProcessing,
+			TradeState::KeyspendPayoutReady,
+		)
+		.await
+	}
+
+	/// fetch the data required to construct the script path payout transaction for the escrow winner
+	pub async fn fetch_script_payout_data(
+		&self,
+		offer_id_hex: &str,
+		winner_robohash_hex: &str,
+		network: Network,
+	) -> Result<ScriptPayoutData> {
+		let winner_is_maker = self
+			.is_maker_in_taken_offers(offer_id_hex, winner_robohash_hex)
+			.await?;
+
+		let row = sqlx::query(
+			"SELECT escrow_output_descriptor, escrow_taproot_pk_coordinator, payout_address_maker,
+			payout_address_taker, taproot_xonly_pubkey_hex_maker, taproot_xonly_pubkey_hex_taker
+			FROM taken_offers WHERE offer_id = ?",
+		)
+		.bind(offer_id_hex)
+		.fetch_one(&*self.db_pool)
+		.await
+		.context("SQL query to fetch script payout data failed.")?;
+
+		let (payout_address_winner, taproot_pk_hex_winner): (&str, &str) = if winner_is_maker {
+			(
+				row.try_get("payout_address_maker")?,
+				row.try_get("taproot_xonly_pubkey_hex_maker")?,
+			)
+		} else {
+			(
+				row.try_get("payout_address_taker")?,
+				row.try_get("taproot_xonly_pubkey_hex_taker")?,
+			)
+		};
+
+		ScriptPayoutData::new_from_strings(
+			row.try_get("escrow_output_descriptor")?,
+			payout_address_winner,
+			taproot_pk_hex_winner,
+			row.try_get("escrow_taproot_pk_coordinator")?,
+			network,
+		)
+	}
+
+	/// insert the coordinator signed script path payout transaction into the db and move the trade to script
+	/// payout ready. Returns false and stores nothing if the trade left the script payout processing state meanwhile.
+	pub async fn insert_script_payout_psbt(
+		&self,
+		offer_id_hex: &str,
+		script_payout_psbt_hex: &str,
+	) -> Result<bool> {
+		self.insert_payout_psbt(
+			"script_payout_psbt_hex",
+			offer_id_hex,
+			script_payout_psbt_hex,
+			TradeState::ScriptPayoutProcessing,
+			TradeState::ScriptPayoutReady,
+		)
+		.await
+	}
+
+	/// writes the payout psbt and the trade state transition in one db transaction
+	async fn insert_payout_psbt(
+		&self,
+		psbt_column: &str,
+		offer_id_hex: &str,
+		psbt_hex: &str,
+		from: TradeState,
+		to: TradeState,
+	) -> Result<bool> {
+		let mut db_tx = self.db_pool.begin().await?;
+		let result = sqlx::query(&format!(
+			"UPDATE taken_offers SET {} = ?, trade_state = ? WHERE offer_id = ? AND trade_state = ?",
+			psbt_column
+		))
+		.bind(psbt_hex)
+		.bind(to.as_str())
+		.bind(offer_id_hex)
+		.bind(from.as_str())
+		.execute(&mut *db_tx)
+		.await?;
+		if result.rows_affected() != 1 {
+			db_tx.rollback().await?;
+			return Ok(false);
+		}
+		db_tx.commit().await?;
+		trace!(
+			"Offer {} changed trade state from {} to {}",
+			offer_id_hex,
+			from,
+			to
+		);
+		Ok(true)
+	}
+
+	/// fetches the script path payout psbt from the db
+	pub async fn fetch_script_payout_psbt(&self, offer_id_hex: &str) -> Result<Option<String>> {
+		let row = sqlx::query("SELECT script_payout_psbt_hex FROM taken_offers WHERE offer_id = ?")
 			.bind(offer_id_hex)
-			.execute(&*self.db_pool)
+			.fetch_one(&*self.db_pool)
 			.await?;
-		Ok(())
+
+		let script_payout_psbt: Option<String> = row.try_get("script_payout_psbt_hex")?;
+		Ok(script_payout_psbt)
+	}
+
+	/// fetches the musig data of both traders and the keyspend payout psbt to verify a partial signature
+	/// submitted by the trader with the given robohash. Returns None if the payout psbt has not been assembled yet.
+	pub async fn fetch_partial_sig_verification_data(
+		&self,
+		offer_id_hex: &str,
+		robohash_hex: &str,
+	) -> Result<Option<PartialSigVerificationData>> {
+		let is_maker = self
+			.is_maker_in_taken_offers(offer_id_hex, robohash_hex)
+			.await?;
+		let row = sqlx::query(
+			"SELECT musig_pubkey_compressed_hex_maker, musig_pubkey_compressed_hex_taker, musig_pub_nonce_hex_maker,
+			musig_pub_nonce_hex_taker, payout_transaction_psbt_hex, escrow_output_descriptor FROM taken_offers WHERE offer_id = ?",
+		).bind(offer_id_hex).fetch_one(&*self.db_pool).await?;
+
+		let maker_pubkey: String = row.try_get("musig_pubkey_compressed_hex_maker")?;
+		let taker_pubkey: String = row.try_get("musig_pubkey_compressed_hex_taker")?;
+
+		let maker_nonce: String = row.try_get("musig_pub_nonce_hex_maker")?;
+		let taker_nonce: String = row.try_get("musig_pub_nonce_hex_taker")?;
+
+		let keyspend_psbt: Option<String> = row.try_get("payout_transaction_psbt_hex")?;
+		let descriptor: String = row.try_get("escrow_output_descriptor")?;
+
+		match keyspend_psbt {
+			Some(keyspend_psbt) => Ok(Some(PartialSigVerificationData::from_hex_str(
+				is_maker,
+				&maker_nonce,
+				&taker_nonce,
+				&maker_pubkey,
+				&taker_pubkey,
+				&keyspend_psbt,
+				&descriptor,
+			)?)),
+			None => Ok(None),
+		}
+	}
+
+	/// increments the invalid partial signature counter of the trader and returns the new count
+	pub async fn record_invalid_partial_sig(
+		&self,
+		offer_id_hex: &str,
+		robohash_hex: &str,
+	) -> Result<i64> {
+		let is_maker = self
+			.is_maker_in_taken_offers(offer_id_hex, robohash_hex)
+			.await?;
+
+		let query = if is_maker {
+			"UPDATE taken_offers SET invalid_partial_sigs_maker = invalid_partial_sigs_maker + 1
+			WHERE offer_id = ? RETURNING invalid_partial_sigs_maker AS invalid_count"
+		} else {
+			"UPDATE taken_offers SET invalid_partial_sigs_taker = invalid_partial_sigs_taker + 1
+			WHERE offer_id = ? RETURNING invalid_partial_sigs_taker AS invalid_count"
+		};
+		let row = sqlx::query(query)
+			.bind(offer_id_hex)
+			.fetch_one(&*self.db_pool)
+			.await?;
+		Ok(row.try_get::<i64, _>("invalid_count")?)
 	}
 
 	/// insert a partial signature submitted by the trader into the db
@@ -910,7 +1455,6 @@ impl CoordinatorDB {
 			}
 		};
 
-		warn!("we can use musig2::verify_partial to detect users submitting invalid partial signatures");
 		if is_already_there {
 			return Err(anyhow!("Partial sig already submitted"));
 		} else {
@@ -979,25 +1523,117 @@ impl CoordinatorDB {
 		Ok(payout_psbt)
 	}
 
-	/// used to as db lock to prevent race conditions when the payout is being handled
-	pub async fn toggle_processing(&self, offer_id: &str) -> Result<bool> {
+	/// returns the current state of the trade
+	pub async fn fetch_trade_state(&self, offer_id: &str) -> Result<TradeState> {
+		let row = sqlx::query("SELECT trade_state FROM taken_offers WHERE offer_id = ?")
+			.bind(offer_id)
+			.fetch_one(&*self.db_pool)
+			.await?;
+		TradeState::from_str(&row.try_get::<String, _>("trade_state")?)
+	}
+
+	/// moves the trade from the expected state into the next state, errors if the transition is not allowed.
+	/// Returns false if the trade is not in the expected state (anymore), this is used as db lock to prevent
+	/// race conditions when the payout is being handled
+	pub async fn transition_trade_state(
+		&self,
+		offer_id: &str,
+		from: TradeState,
+		to: TradeState,
+	) -> Result<bool> {
+		if !from.can_transition_to(to) {
+			return Err(anyhow!(
+				"Invalid trade state transition from {} to {} for offer {}",
+				from,
+				to,
+				offer_id
+			));
+		}
 		let result = sqlx::query(
-			r#"
-        UPDATE taken_offers
-        SET processing = CASE
-            WHEN processing = 0 THEN 1
-            ELSE 0
-        END
-        WHERE offer_id = ?
-        RETURNING processing
-        "#,
+			"UPDATE taken_offers SET trade_state = ? WHERE offer_id = ? AND trade_state = ?",
 		)
+		.bind(to.as_str())
 		.bind(offer_id)
-		.fetch_one(&*self.db_pool)
+		.bind(from.as_str())
+		.execute(&*self.db_pool)
 		.await?;
 
-		trace!("Toggled processing status for offer {}", offer_id);
-		Ok(result.get::<i64, _>(0) == 1)
+		let transitioned = result.rows_affected() == 1;
+		if transitioned {
+			trace!(
+				"Offer {} changed trade state from {} to {}",
+				offer_id,
+				from,
+				to
+			);
+		}
+		Ok(transitioned)
+	}
+
+	/// moves the trade from whatever state it is in into the given state, does nothing if it is already
+	/// in this state and errors if the transition is not allowed
+	pub async fn set_trade_state(&self, offer_id: &str, to: TradeState) -> Result<()> {
+		let current = self.fetch_trade_state(offer_id).await?;
+		if current == to {
+			return Ok(());
+		}
+		if !self.transition_trade_state(offer_id, current, to).await? {
+			return Err(anyhow!(
+				"Trade state of offer {} changed concurrently while moving it to {}",
+				offer_id,
+				to
+			));
+		}
+		Ok(())
+	}
+
+	/// returns the offer ids of all trades in the given state
+	pub async fn fetch_trades_in_state(&self, state: TradeState) -> Result<Vec<String>> {
+		let rows = sqlx::query("SELECT offer_id FROM taken_offers WHERE trade_state = ?")
+			.bind(state.as_str())
+			.fetch_all(&*self.db_pool)
+			.await?;
+		rows.iter()
+			.map(|row| Ok(row.try_get::<String, _>("offer_id")?))
+			.collect()
+	}
+
+	/// called on startup to clean up payouts that were being assembled when the coordinator stopped.
+	/// If the payout psbt got stored the trade is moved forward, otherwise it is rolled back so the
+	/// payout gets assembled again on the next request of the traders. Returns the new state of each trade.
+	pub async fn recover_interrupted_payouts(&self) -> Result<Vec<(String, TradeState)>> {
+		let mut recovered = Vec::new();
+		for offer_id in self
+			.fetch_trades_in_state(TradeState::KeyspendPayoutProcessing)
+			.await?
+		{
+			let next = match self.fetch_keyspend_payout_psbt(&offer_id).await? {
+				Some(_) => TradeState::KeyspendPayoutReady,
+				None => TradeState::EscrowConfirmed,
+			};
+			if self
+				.transition_trade_state(&offer_id, TradeState::KeyspendPayoutProcessing, next)
+				.await?
+			{
+				recovered.push((offer_id, next));
+			}
+		}
+		for offer_id in self
+			.fetch_trades_in_state(TradeState::ScriptPayoutProcessing)
+			.await?
+		{
+			let next = match self.fetch_script_payout_psbt(&offer_id).await? {
+				Some(_) => TradeState::ScriptPayoutReady,
+				None => TradeState::EscrowResolved,
+			};
+			if self
+				.transition_trade_state(&offer_id, TradeState::ScriptPayoutProcessing, next)
+				.await?
+			{
+				recovered.push((offer_id, next));
+			}
+		}
+		Ok(recovered)
 	}
 
 	/// deletes a finished offer from the database 🎉
@@ -1013,8 +1649,9 @@ impl CoordinatorDB {
 	pub async fn get_open_escrows(&self) -> Result<Vec<EscrowCase>> {
 		let escrows = sqlx::query(
 			"SELECT offer_id, robohash_maker, robohash_taker
-			FROM taken_offers WHERE escrow_ongoing = 1",
+			FROM taken_offers WHERE trade_state = ?",
 		)
+		.bind(TradeState::Disputed.as_str())
 		.fetch_all(&*self.db_pool)
 		.await?;
 
@@ -1029,13 +1666,15 @@ impl CoordinatorDB {
 		Ok(escrow_cases)
 	}
 
-	// set the winning robohash in the db
+	// set the winning robohash in the db, the winner is stored first so a crash in between leaves
+	// the trade disputed and the escrow gets decided again
 	pub async fn resolve_escrow(&self, offer_id: &str, winner_robohash: &str) -> Result<()> {
-		sqlx::query("UPDATE taken_offers SET escrow_ongoing = 0, escrow_winner_robohash = ? WHERE offer_id = ?")
+		sqlx::query("UPDATE taken_offers SET escrow_winner_robohash = ? WHERE offer_id = ?")
 			.bind(winner_robohash)
 			.bind(offer_id)
 			.execute(&*self.db_pool)
 			.await?;
-		Ok(())
+		self.set_trade_state(offer_id, TradeState::EscrowResolved)
+			.await
 	}
 }
@@ -12,31 +12,38 @@ use axum::{
 };
 use bdk::{
 	bitcoin::{
-		address::Payload,
-		bip32::ExtendedPrivKey,
-		consensus::encode::deserialize,
+		bip32::{ExtendedPrivKey, KeySource},
+		consensus::encode::{deserialize, serialize},
+		hashes::Hash,
 		key::{secp256k1, XOnlyPublicKey},
 		psbt::{Input, PartiallySignedTransaction, Prevouts},
 		sighash::SighashCache,
-		Address, Network, OutPoint, Transaction, TxIn, Txid,
+		taproot::{LeafVersion, TapLeafHash},
+		Address, Network, OutPoint, Transaction, TxOut, Txid, Witness,
 	},
 	bitcoincore_rpc::{
 		jsonrpc::Error as JsonRpcError, Client, Error as CoreRpcError, RawTx, RpcApi,
 	},
-	blockchain::{rpc::Auth, Blockchain, ConfigurableBlockchain, GetTx, RpcBlockchain, RpcConfig},
+	blockchain::{
+		rpc::Auth, AnyBlockchain, Blockchain, ConfigurableBlockchain, GetTx, RpcBlockchain,
+		RpcConfig,
+	},
 	database::{Database, MemoryDatabase},
 	descriptor::Descriptor,
 	miniscript::{descriptor::TapTree, policy::Concrete, Tap, ToPublicKey},
 	sled::Tree,
 	template::Bip86,
-	wallet::verify::*,
+	wallet::{signer::TapLeavesOptions, verify::*, AddressInfo},
 	KeychainKind, SignOptions, SyncOptions, Wallet,
 };
 use chrono::Local;
-use communication::{api::*, api_server, communication_utils::*, handler_errors::*};
+use communication::{
+	api_server, communication_utils::*, handler_errors::*, signed_request::SeenRequestSignatures,
+};
 use coordinator::{
-	bond_monitoring::*, coordinator_utils::*, escrow_cli::escrow_cli_loop,
-	mempool_monitoring::MempoolHandler,
+	bond_monitoring::*, bond_punishment::*, chain_notifications::*, coordinator_utils::*,
+	escrow_cli::escrow_cli_loop, mempool_monitoring::MempoolHandler,
+	offer_expiry::remove_expired_offers, trade_events::*, trade_state::*,
 	tx_confirmation_monitoring::update_transaction_confirmations, *,
 };
 use database::CoordinatorDB;
@@ -49,25 +56,22 @@ use musig2::{
 };
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use sqlx::{sqlite::SqlitePoolOptions, Pool, Row, Sqlite};
+use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions, Pool, Row, Sqlite};
 use std::{
-	collections::{HashMap, HashSet},
+	collections::HashMap,
 	env, fmt,
 	io::Write,
 	net::SocketAddr,
-	ops::Deref,
 	str::FromStr,
 	sync::{
 		atomic::{AtomicBool, Ordering},
-		Arc, RwLock,
+		Arc,
 	},
 	time::{SystemTime, UNIX_EPOCH},
 };
-use tokio::{
-	net::TcpListener,
-	sync::{oneshot, Mutex},
-};
-use validator::{Validate, ValidationError};
+use taptrade_protocol::{endpoints, *};
+use tokio::{net::TcpListener, sync::Mutex};
+use validator::Validate;
 use wallet::{wallet_utils::*, *};
 
 // can be set false to disable logging in runtime (while awaiting cli input)
@@ -75,7 +79,10 @@ static LOGGING_ENABLED: AtomicBool = AtomicBool::new(true);
 
 pub struct Coordinator {
 	pub coordinator_db: Arc<CoordinatorDB>,
-	pub coordinator_wallet: Arc<CoordinatorWallet<MemoryDatabase>>,
+	pub coordinator_wallet: Arc<CoordinatorWallet<Tree>>,
+	pub chain_notifications: Arc<ChainNotifications>,
+	pub trade_events: Arc<TradeEvents>,
+	pub seen_request_signatures: Arc<SeenRequestSignatures>,
 }
 
 // populate .env with values before starting
@@ -109,8 +116,33 @@ async fn main() -> Result<()> {
 	let coordinator = Arc::new(Coordinator {
 		coordinator_db: Arc::new(CoordinatorDB::init().await?),
 		coordinator_wallet: Arc::new(init_coordinator_wallet().await?),
+		chain_notifications: Arc::new(ChainNotifications::new()),
+		trade_events: Arc::new(TradeEvents::new()),
+		seen_request_signatures: Arc::new(SeenRequestSignatures::new()),
 	});
 
+	// resume or roll back trades that were in flight when the coordinator stopped
+	recover_in_flight_trades(Arc::clone(&coordinator)).await?;
+
+	// optionally get notified about new transactions and blocks by bitcoin core over zmq,
+	// the monitoring tasks fall back to polling if it is not configured or disconnects
+	match env::var("ZMQ_ENDPOINT") {
+		Ok(zmq_endpoint) => {
+			let notifications_ref = Arc::clone(&coordinator.chain_notifications);
+			tokio::spawn(async move {
+				loop {
+					if let Err(e) =
+						run_zmq_subscriber(&zmq_endpoint, notifications_ref.clone()).await
+					{
+						error!("Error in run_zmq_subscriber: {:?}", e);
+						tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+					}
+				}
+			});
+		}
+		Err(_) => info!("ZMQ_ENDPOINT not set, monitoring the chain by polling only"),
+	}
+
 	// begin monitoring bonds as separate tokio task which runs concurrently
 	let coordinator_ref = Arc::clone(&coordinator);
 	tokio::spawn(async move {
@@ -123,13 +155,35 @@ async fn main() -> Result<()> {
 		}
 	});
 
+	// rebroadcast and fee bump published bonds until they are confirmed
+	let coordinator_ref = Arc::clone(&coordinator);
+	tokio::spawn(async move {
+		loop {
+			if let Err(e) = monitor_published_bonds(coordinator_ref.clone()).await {
+				error!("Error in monitor_published_bonds: {:?}", e);
+				tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+			}
+		}
+	});
+
+	// remove expired offers and maker requests without bond as separate tokio task
+	let coordinator_ref = Arc::clone(&coordinator);
+	tokio::spawn(async move {
+		loop {
+			if let Err(e) = remove_expired_offers(coordinator_ref.clone()).await {
+				error!("Error in remove_expired_offers: {:?}", e);
+				tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+			}
+		}
+	});
+
 	// begin monitoring escrow transactions confirmations
 	let coordinator_ref = Arc::clone(&coordinator);
 	tokio::spawn(async move { update_transaction_confirmations(coordinator_ref).await });
 
 	// begin monitoring escrow requests
-	let db_ref = Arc::clone(&coordinator.coordinator_db);
-	tokio::spawn(async move { escrow_cli_loop(db_ref).await });
+	let coordinator_ref = Arc::clone(&coordinator);
+	tokio::spawn(async move { escrow_cli_loop(coordinator_ref).await });
 
 	// Start the API server
 	api_server(coordinator).await?;
@@ -0,0 +1,540 @@
+/// chain backends used by the coordinator to broadcast and look up transactions. Bitcoin core (rpc) is the default,
+/// Esplora and Electrum can be used to run the coordinator without a co-located full node.
+/// The backend is selected with the CHAIN_BACKEND env variable (rpc, esplora or electrum).
+use super::*;
+use bdk::{
+	bitcoincore_rpc::jsonrpc,
+	blockchain::{AnyBlockchain, ElectrumBlockchain, ElectrumBlockchainConfig, EsploraBlockchain},
+	electrum_client::{self, ElectrumApi},
+	esplora_client,
+};
+
+/// stop gap used by bdk to sync the coordinator wallet with the esplora and electrum backends
+const WALLET_SYNC_STOP_GAP: usize = 20;
+
+/// result of a testmempoolaccept (or equivalent) check of a single transaction
+#[derive(Debug)]
+pub struct MempoolAcceptResult {
+	pub txid: Txid,
+	// None if the transaction would be accepted
+	pub reject_reason: Option<String>,
+}
+
+/// transaction spending an outpoint, txid is None if the backend only knows the outpoint
+/// got spent in a block (bitcoin core without index)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputSpend {
+	pub txid: Option<Txid>,
+	pub confirmed: bool,
+}
+
+/// the chain queries the coordinator needs besides the wallet sync (which is done by bdk).
+/// All calls are blocking and should be wrapped in spawn_blocking when called from async code.
+pub trait ChainBackend: Send + Sync {
+	fn broadcast(&self, tx: &Transaction) -> Result<()>;
+
+	/// returns None if the transaction is neither in the mempool nor mined
+	fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>>;
+
+	/// returns None if the transaction is unknown, Some(0) if it is in the mempool
+	fn get_confirmations(&self, txid: &Txid) -> Result<Option<u32>>;
+
+	fn get_block_height(&self) -> Result<u64>;
+
+	/// looks up the transactions spending the passed outpoints, unspent outpoints are not contained in the result
+	fn get_output_spends(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, OutputSpend>>;
+
+	/// equivalent to bitcoin core testmempoolaccept, checks if the inputs of the transactions exist
+	/// and are not spent yet. Scripts and signatures are not checked (this is done by verify_tx).
+	fn test_mempool_accept(&self, txs: &[Transaction]) -> Result<Vec<MempoolAcceptResult>> {
+		let mut results = Vec::new();
+		for tx in txs {
+			let outpoints: Vec<OutPoint> = tx.input.iter().map(|i| i.previous_output).collect();
+			let reject_reason = if self.get_confirmations(&tx.txid())?.is_some() {
+				Some("txn-already-known".to_string())
+			} else if !self.get_output_spends(&outpoints)?.is_empty() {
+				Some("bad-txns-inputs-missingorspent".to_string())
+			} else {
+				let mut reject_reason = None;
+				for outpoint in outpoints {
+					let prev_output_exists = match self.get_tx(&outpoint.txid)? {
+						Some(prev_tx) => prev_tx.output.len() > outpoint.vout as usize,
+						None => false,
+					};
+					if !prev_output_exists {
+						reject_reason = Some("missing-inputs".to_string());
+						break;
+					}
+				}
+				reject_reason
+			};
+			results.push(MempoolAcceptResult {
+				txid: tx.txid(),
+				reject_reason,
+			});
+		}
+		Ok(results)
+	}
+
+	/// returns the txids of mempool transactions spending the passed outpoints
+	fn get_mempool_spending_txids(
+		&self,
+		outpoints: &[OutPoint],
+	) -> Result<HashMap<OutPoint, Txid>> {
+		Ok(self
+			.get_output_spends(outpoints)?
+			.into_iter()
+			.filter_map(|(outpoint, spend)| match spend {
+				OutputSpend {
+					txid: Some(txid),
+					confirmed: false,
+				} => Some((outpoint, txid)),
+				_ => None,
+			})
+			.collect())
+	}
+
+	/// returns true if the outpoint got spent in a mined transaction
+	fn is_spent_in_block(&self, outpoint: &OutPoint) -> Result<bool> {
+		Ok(self
+			.get_output_spends(&[*outpoint])?
+			.get(outpoint)
+			.is_some_and(|spend| spend.confirmed))
+	}
+}
+
+/// outpoint as passed to gettxspendingprevout
+#[derive(Serialize, Debug)]
+struct Prevout {
+	txid: String,
+	vout: u32,
+}
+
+/// result of gettxspendingprevout for one outpoint, spendingtxid is only set if a mempool transaction spends it
+#[derive(Deserialize, Debug)]
+struct SpendingPrevout {
+	txid: String,
+	vout: u32,
+	spendingtxid: Option<String>,
+}
+
+/// bitcoin core json rpc backend
+pub struct CoreRpcBackend {
+	json_rpc_client: Client,
+}
+
+impl CoreRpcBackend {
+	pub fn new(json_rpc_client: Client) -> Self {
+		Self { json_rpc_client }
+	}
+
+	/// looks up all passed outpoints in a single gettxspendingprevout call (requires bitcoin core >= 24).
+	/// bitcoincore_rpc does not support gettxspendingprevout yet, so it is called as raw rpc method.
+	fn get_spending_prevouts(&self, outpoints: &[OutPoint]) -> Result<Vec<SpendingPrevout>> {
+		let prevouts: Vec<Prevout> = outpoints
+			.iter()
+			.map(|outpoint| Prevout {
+				txid: outpoint.txid.to_string(),
+				vout: outpoint.vout,
+			})
+			.collect();
+		self.json_rpc_client
+			.call("gettxspendingprevout", &[serde_json::to_value(prevouts)?])
+			.context("gettxspendingprevout rpc call failed")
+	}
+
+	/// returns the passed outpoints not contained in the utxo set of the last block (gettxout without mempool).
+	/// All outpoints are looked up in a single batched rpc call.
+	fn get_missing_confirmed_utxos(&self, outpoints: &[OutPoint]) -> Result<Vec<OutPoint>> {
+		if outpoints.is_empty() {
+			return Ok(Vec::new());
+		}
+		let params: Vec<_> = outpoints
+			.iter()
+			.map(|outpoint| {
+				vec![
+					jsonrpc::arg(outpoint.txid.to_string()),
+					jsonrpc::arg(outpoint.vout),
+					jsonrpc::arg(false),
+				]
+			})
+			.collect();
+		let jsonrpc_client = self.json_rpc_client.get_jsonrpc_client();
+		let requests: Vec<_> = params
+			.iter()
+			.map(|params| jsonrpc_client.build_request("gettxout", params))
+			.collect();
+		let responses = jsonrpc_client
+			.send_batch(&requests)
+			.context("gettxout batch rpc call failed")?;
+		let mut missing_outpoints = Vec::new();
+		for (outpoint, response) in outpoints.iter().zip(responses) {
+			let response =
+				response.ok_or_else(|| anyhow!("Missing gettxout response for {}", outpoint))?;
+			if response.result::<Option<serde_json::Value>>()?.is_none() {
+				missing_outpoints.push(*outpoint);
+			}
+		}
+		Ok(missing_outpoints)
+	}
+}
+
+impl ChainBackend for CoreRpcBackend {
+	fn broadcast(&self, tx: &Transaction) -> Result<()> {
+		self.json_rpc_client.send_raw_transaction(tx)?;
+		Ok(())
+	}
+
+	fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>> {
+		match self.json_rpc_client.get_raw_transaction(txid, None) {
+			Ok(tx) => Ok(Some(tx)),
+			Err(CoreRpcError::JsonRpc(JsonRpcError::Rpc(rpc_error))) if rpc_error.code == -5 => {
+				Ok(None)
+			}
+			Err(e) => Err(anyhow!("Error fetching transaction {}: {}", txid, e)),
+		}
+	}
+
+	fn get_confirmations(&self, txid: &Txid) -> Result<Option<u32>> {
+		match self.json_rpc_client.get_raw_transaction_info(txid, None) {
+			Ok(tx_info) => Ok(Some(tx_info.confirmations.unwrap_or(0))),
+			Err(CoreRpcError::JsonRpc(JsonRpcError::Rpc(rpc_error))) if rpc_error.code == -5 => {
+				Ok(None)
+			}
+			Err(e) => Err(anyhow!(
+				"Error fetching transaction info for {}: {}",
+				txid,
+				e
+			)),
+		}
+	}
+
+	fn get_block_height(&self) -> Result<u64> {
+		Ok(self.json_rpc_client.get_block_count()?)
+	}
+
+	fn get_output_spends(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, OutputSpend>> {
+		let mut output_spends = HashMap::new();
+		let mempool_spends = self.get_mempool_spending_txids(outpoints)?;
+		let not_spent_in_mempool: Vec<OutPoint> = outpoints
+			.iter()
+			.filter(|outpoint| !mempool_spends.contains_key(outpoint))
+			.copied()
+			.collect();
+		// gettxout without mempool also returns None if the funding tx is unconfirmed or unknown,
+		// so a missing output is only spent in a block if its funding tx is confirmed
+		let mut funding_tx_confirmed: HashMap<Txid, bool> = HashMap::new();
+		for outpoint in self.get_missing_confirmed_utxos(&not_spent_in_mempool)? {
+			let confirmed = match funding_tx_confirmed.get(&outpoint.txid) {
+				Some(confirmed) => *confirmed,
+				None => {
+					let confirmed = self
+						.get_confirmations(&outpoint.txid)?
+						.is_some_and(|confirmations| confirmations > 0);
+					funding_tx_confirmed.insert(outpoint.txid, confirmed);
+					confirmed
+				}
+			};
+			if confirmed {
+				output_spends.insert(
+					outpoint,
+					OutputSpend {
+						txid: None,
+						confirmed: true,
+					},
+				);
+			}
+		}
+		for (outpoint, txid) in mempool_spends {
+			output_spends.insert(
+				outpoint,
+				OutputSpend {
+					txid: Some(txid),
+					confirmed: false,
+				},
+			);
+		}
+		Ok(output_spends)
+	}
+
+	fn test_mempool_accept(&self, txs: &[Transaction]) -> Result<Vec<MempoolAcceptResult>> {
+		// split txs into chunks of 25 to avoid hitting the maxmimum allowed size of the rpc call
+		let mut results = Vec::new();
+		for chunk in txs.chunks(25) {
+			let raw_txs: Vec<String> = chunk.iter().map(|tx| tx.raw_hex()).collect();
+			for res in self.json_rpc_client.test_mempool_accept(&raw_txs)? {
+				results.push(MempoolAcceptResult {
+					txid: res.txid,
+					reject_reason: match res.allowed {
+						true => None,
+						false => Some(
+							res.reject_reason
+								.unwrap_or("rejected by testmempoolaccept".to_string()),
+						),
+					},
+				});
+			}
+		}
+		Ok(results)
+	}
+
+	fn get_mempool_spending_txids(
+		&self,
+		outpoints: &[OutPoint],
+	) -> Result<HashMap<OutPoint, Txid>> {
+		let mut spending_txids = HashMap::new();
+		if outpoints.is_empty() {
+			return Ok(spending_txids);
+		}
+		for spending_prevout in self.get_spending_prevouts(outpoints)? {
+			if let Some(spending_txid) = spending_prevout.spendingtxid {
+				let outpoint = OutPoint {
+					txid: Txid::from_str(&spending_prevout.txid)?,
+					vout: spending_prevout.vout,
+				};
+				spending_txids.insert(outpoint, Txid::from_str(&spending_txid)?);
+			}
+		}
+		Ok(spending_txids)
+	}
+}
+
+/// esplora http api backend (e.g. blockstream.info or a self hosted electrs/esplora instance)
+pub struct EsploraBackend {
+	client: esplora_client::BlockingClient,
+}
+
+impl EsploraBackend {
+	pub fn new(client: esplora_client::BlockingClient) -> Self {
+		Self { client }
+	}
+}
+
+impl ChainBackend for EsploraBackend {
+	fn broadcast(&self, tx: &Transaction) -> Result<()> {
+		self.client.broadcast(tx)?;
+		Ok(())
+	}
+
+	fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>> {
+		Ok(self.client.get_tx(txid)?)
+	}
+
+	fn get_confirmations(&self, txid: &Txid) -> Result<Option<u32>> {
+		if self.client.get_tx(txid)?.is_none() {
+			return Ok(None);
+		}
+		match self.client.get_tx_status(txid)?.block_height {
+			Some(block_height) => Ok(Some(
+				self.client.get_height()?.saturating_sub(block_height) + 1,
+			)),
+			None => Ok(Some(0)),
+		}
+	}
+
+	fn get_block_height(&self) -> Result<u64> {
+		Ok(self.client.get_height()? as u64)
+	}
+
+	fn get_output_spends(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, OutputSpend>> {
+		let mut output_spends = HashMap::new();
+		for outpoint in outpoints {
+			if let Some(output_status) = self
+				.client
+				.get_output_status(&outpoint.txid, outpoint.vout as u64)?
+			{
+				if output_status.spent {
+					output_spends.insert(
+						*outpoint,
+						OutputSpend {
+							txid: output_status.txid,
+							confirmed: output_status.status.is_some_and(|s| s.confirmed),
+						},
+					);
+				}
+			}
+		}
+		Ok(output_spends)
+	}
+}
+
+/// electrum server backend (electrs, fulcrum, ...)
+pub struct ElectrumBackend {
+	client: electrum_client::Client,
+}
+
+impl ElectrumBackend {
+	pub fn new(client: electrum_client::Client) -> Self {
+		Self { client }
+	}
+
+	// looks up the height of a transaction in the histories of the scripts of its spendable outputs
+	// (OP_RETURN outputs have no history), 0 or -1 means unconfirmed
+	fn get_tx_height(&self, tx: &Transaction) -> Result<Option<i32>> {
+		let mut scripts: Vec<&bdk::bitcoin::Script> = Vec::new();
+		for output in tx.output.iter() {
+			if !output.script_pubkey.is_provably_unspendable()
+				&& !scripts.contains(&output.script_pubkey.as_script())
+			{
+				scripts.push(&output.script_pubkey);
+			}
+		}
+		if scripts.is_empty() {
+			return Ok(None);
+		}
+		let txid = tx.txid();
+		Ok(self
+			.client
+			.batch_script_get_history(scripts)?
+			.into_iter()
+			.flatten()
+			.find(|entry| entry.tx_hash == txid)
+			.map(|entry| entry.height))
+	}
+}
+
+/// electrum servers forward the bitcoin core error for unknown transactions (RPC_INVALID_ADDRESS_OR_KEY),
+/// other protocol errors like rate limits or failures of the server are no proof the transaction is missing
+pub fn is_electrum_tx_not_found_error(error: &serde_json::Value) -> bool {
+	error.get("code").and_then(|code| code.as_i64()) == Some(-5)
+		|| error
+			.to_string()
+			.contains("No such mempool or blockchain transaction")
+}
+
+impl ChainBackend for ElectrumBackend {
+	fn broadcast(&self, tx: &Transaction) -> Result<()> {
+		self.client.transaction_broadcast(tx)?;
+		Ok(())
+	}
+
+	fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>> {
+		match self.client.transaction_get(txid) {
+			Ok(tx) => Ok(Some(tx)),
+			Err(electrum_client::Error::Protocol(error))
+				if is_electrum_tx_not_found_error(&error) =>
+			{
+				Ok(None)
+			}
+			Err(e) => Err(anyhow!("Error fetching transaction {}: {}", txid, e)),
+		}
+	}
+
+	fn get_confirmations(&self, txid: &Txid) -> Result<Option<u32>> {
+		let tx = match self.get_tx(txid)? {
+			Some(tx) => tx,
+			None => return Ok(None),
+		};
+		match self.get_tx_height(&tx)? {
+			Some(height) if height > 0 => Ok(Some(
+				(self.get_block_height()? as u32).saturating_sub(height as u32) + 1,
+			)),
+			_ => Ok(Some(0)),
+		}
+	}
+
+	fn get_block_height(&self) -> Result<u64> {
+		Ok(self.client.block_headers_subscribe()?.height as u64)
+	}
+
+	fn get_output_spends(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, OutputSpend>> {
+		let mut output_spends = HashMap::new();
+		for outpoint in outpoints {
+			let prev_tx = match self.get_tx(&outpoint.txid)? {
+				Some(tx) => tx,
+				None => continue,
+			};
+			let script = match prev_tx.output.get(outpoint.vout as usize) {
+				Some(output) => &output.script_pubkey,
+				None => continue,
+			};
+			// the spending transaction is part of the history of the script of the spent output
+			for entry in self.client.script_get_history(script)? {
+				if entry.tx_hash == outpoint.txid {
+					continue;
+				}
+				let history_tx = self.client.transaction_get(&entry.tx_hash)?;
+				if history_tx
+					.input
+					.iter()
+					.any(|input| input.previous_output == *outpoint)
+				{
+					output_spends.insert(
+						*outpoint,
+						OutputSpend {
+							txid: Some(entry.tx_hash),
+							confirmed: entry.height > 0,
+						},
+					);
+					break;
+				}
+			}
+		}
+		Ok(output_spends)
+	}
+}
+
+/// sets up the bdk blockchain used to sync the coordinator wallet and the chain backend
+/// selected by the CHAIN_BACKEND env variable (defaults to bitcoin core rpc)
+pub fn init_chain_backend(
+	wallet_name: String,
+	network: Network,
+) -> Result<(AnyBlockchain, Arc<dyn ChainBackend>)> {
+	match env::var("CHAIN_BACKEND")
+		.unwrap_or_else(|_| "rpc".to_string())
+		.as_str()
+	{
+		"rpc" => {
+			let rpc_config = RpcConfig {
+				url: env::var("BITCOIN_RPC_ADDRESS_PORT")?.to_string(),
+				auth: Auth::UserPass {
+					username: env::var("BITCOIN_RPC_USER")?,
+					password: env::var("BITCOIN_RPC_PASSWORD")?,
+				},
+				network,
+				wallet_name,
+				sync_params: None,
+			};
+			let json_rpc_client = Client::new(&rpc_config.url, rpc_config.auth.clone().into())?;
+			let backend = RpcBlockchain::from_config(&rpc_config)?;
+			info!("Using bitcoin core rpc chain backend at {}", rpc_config.url);
+			Ok((
+				AnyBlockchain::from(backend),
+				Arc::new(CoreRpcBackend::new(json_rpc_client)),
+			))
+		}
+		"esplora" => {
+			let esplora_url =
+				env::var("ESPLORA_URL").context("loading ESPLORA_URL from .env failed")?;
+			let client = esplora_client::Builder::new(&esplora_url).build_blocking()?;
+			let backend = EsploraBlockchain::from_client(client.clone(), WALLET_SYNC_STOP_GAP);
+			info!("Using esplora chain backend at {}", esplora_url);
+			Ok((
+				AnyBlockchain::from(backend),
+				Arc::new(EsploraBackend::new(client)),
+			))
+		}
+		"electrum" => {
+			let electrum_url =
+				env::var("ELECTRUM_URL").context("loading ELECTRUM_URL from .env failed")?;
+			let backend = ElectrumBlockchain::from_config(&ElectrumBlockchainConfig {
+				url: electrum_url.clone(),
+				socks5: None,
+				retry: 3,
+				timeout: None,
+				stop_gap: WALLET_SYNC_STOP_GAP,
+				validate_domain: true,
+			})?;
+			let client = electrum_client::Client::new(&electrum_url)?;
+			info!("Using electrum chain backend at {}", electrum_url);
+			Ok((
+				AnyBlockchain::from(backend),
+				Arc::new(ElectrumBackend::new(client)),
+			))
+		}
+		backend => Err(anyhow!(
+			"Invalid CHAIN_BACKEND {}, use rpc, esplora or electrum",
+			backend
+		)),
+	}
+}
@@ -112,10 +112,118 @@ pub fn build_escrow_transaction_output_descriptor(
 		.context("Error assembling escrow output descriptor")?;
 	descriptor.sanity_check()?;
 	debug!("Escrow descriptor: {:#?}", descriptor.to_string());
-	Ok(descriptor) // then spend to descriptor.address(Network::Regtest)
+	Ok(descriptor) // then spend to descriptor.address(network)
 }
 
-impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
+/// validates the signed escrow locking psbt (hex) submitted by a trader: the unsigned transaction has to be
+/// identical to the escrow psbt created by the coordinator, the trader may only sign the inputs it contributed
+/// (`trader_inputs`) and the (taproot keyspend) signatures of these inputs have to be valid
+pub fn validate_escrow_init_psbt(
+	signed_escrow_psbt_hex: &str,
+	unsigned_escrow_psbt: &str,
+	trader_inputs: &[OutPoint],
+) -> Result<()> {
+	let signed_psbt =
+		PartiallySignedTransaction::deserialize(&hex::decode(signed_escrow_psbt_hex)?)
+			.context("Error decoding submitted escrow psbt")?;
+	let unsigned_psbt = PartiallySignedTransaction::from_str(unsigned_escrow_psbt)
+		.context("Error decoding stored escrow psbt")?;
+
+	if serialize(&signed_psbt.unsigned_tx) != serialize(&unsigned_psbt.unsigned_tx) {
+		return Err(anyhow!(
+			"Submitted escrow transaction differs from the escrow transaction created by the coordinator"
+		));
+	}
+
+	// the sighash commits to all spent outputs, we take them from the coordinator created psbt
+	let prevouts = unsigned_psbt
+		.inputs
+		.iter()
+		.enumerate()
+		.map(|(index, input)| {
+			input.witness_utxo.clone().ok_or(anyhow!(
+				"Escrow psbt input {} is missing the witness utxo",
+				index
+			))
+		})
+		.collect::<Result<Vec<TxOut>>>()?;
+
+	let secp = secp256k1::Secp256k1::verification_only();
+	for (index, (txin, input)) in signed_psbt
+		.unsigned_tx
+		.input
+		.iter()
+		.zip(signed_psbt.inputs.iter())
+		.enumerate()
+	{
+		let is_signed = input.final_script_witness.is_some()
+			|| input.final_script_sig.is_some()
+			|| input.tap_key_sig.is_some()
+			|| !input.tap_script_sigs.is_empty()
+			|| !input.partial_sigs.is_empty();
+
+		if !trader_inputs.contains(&txin.previous_output) {
+			if is_signed {
+				return Err(anyhow!(
+					"Escrow psbt contains a signature for input {} which was not contributed by the trader",
+					txin.previous_output
+				));
+			}
+			continue;
+		}
+		let final_witness = input.final_script_witness.as_ref().ok_or(anyhow!(
+			"Escrow psbt input {} of the trader is not signed and finalized",
+			txin.previous_output
+		))?;
+		verify_keyspend_witness(
+			&signed_psbt.unsigned_tx,
+			index,
+			&prevouts,
+			final_witness,
+			&secp,
+		)
+		.with_context(|| {
+			format!(
+				"Invalid signature for escrow input {}",
+				txin.previous_output
+			)
+		})?;
+	}
+	Ok(())
+}
+
+/// verifies the schnorr signature of a finalized taproot keyspend input against the output key of the spent output
+fn verify_keyspend_witness<C: secp256k1::Verification>(
+	tx: &Transaction,
+	index: usize,
+	prevouts: &[TxOut],
+	witness: &Witness,
+	secp: &secp256k1::Secp256k1<C>,
+) -> Result<()> {
+	let spent_script = &prevouts[index].script_pubkey;
+	if !spent_script.is_v1_p2tr() {
+		return Err(anyhow!("Only taproot (p2tr) escrow inputs are supported"));
+	}
+	let output_key = XOnlyPublicKey::from_slice(&spent_script.as_bytes()[2..])?;
+
+	if witness.len() != 1 {
+		return Err(anyhow!(
+			"Expected a taproot keyspend witness with a single signature"
+		));
+	}
+	let signature = bdk::bitcoin::taproot::Signature::from_slice(&witness.to_vec()[0])
+		.context("Error parsing schnorr signature")?;
+	let sighash = SighashCache::new(tx).taproot_key_spend_signature_hash(
+		index,
+		&Prevouts::All(prevouts),
+		signature.hash_ty,
+	)?;
+	let message = secp256k1::Message::from_slice(&sighash.to_byte_array())?;
+	secp.verify_schnorr(&signature.sig, &message, &output_key)?;
+	Ok(())
+}
+
+impl<D: PersistentWalletDatabase> CoordinatorWallet<D> {
 	/// assemble the escrow locking transaction as psbt and return it with relevant associated data
 	pub async fn create_escrow_psbt(
 		&self,
@@ -123,16 +231,19 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 		taker_psbt_request: &OfferPsbtRequest,
 	) -> Result<EscrowPsbt> {
 		let trade_id = &taker_psbt_request.offer.offer_id_hex.clone();
-		let maker_psbt_input_data = db.fetch_maker_escrow_psbt_data(trade_id).await?;
+		let maker_psbt_input_data = db
+			.fetch_maker_escrow_psbt_data(trade_id, self.network)
+			.await?;
 		let taker_psbt_input_data = EscrowPsbtConstructionData {
 			taproot_xonly_pubkey_hex: taker_psbt_request.trade_data.taproot_pubkey_hex.clone(),
-			escrow_input_utxos: csv_hex_to_bdk_input(
-				&taker_psbt_request.trade_data.bdk_psbt_inputs_hex_csv,
+			escrow_input_utxos: escrow_inputs_to_bdk_input(
+				&taker_psbt_request.trade_data.escrow_inputs,
 			)?,
 			change_address: Address::from_str(
 				&taker_psbt_request.trade_data.client_change_address,
 			)?
-			.assume_checked(),
+			.require_network(self.network)
+			.context("Taker change address wrong network")?,
 			musig_pubkey_compressed_hex: taker_psbt_request.trade_data.musig_pubkey_hex.clone(),
 		};
 
@@ -144,7 +255,7 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 		)?;
 
 		let escrow_coordinator_fee_address =
-			Address::from_str(&self.get_new_address().await?)?.assume_checked();
+			Address::from_str(&self.get_new_address().await?)?.require_network(self.network)?;
 
 		let (escrow_amount_maker_sat, escrow_amount_taker_sat, escrow_fee_sat_per_participant) = db
 			.get_escrow_tx_amounts(trade_id, self.coordinator_feerate)
@@ -152,8 +263,7 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 
 		let (escrow_psbt, details) = {
 			// get address for escrow output from the descriptor
-			let escrow_address =
-				escrow_output_descriptor.address(bdk::bitcoin::Network::Regtest)?;
+			let escrow_address = escrow_output_descriptor.address(self.network)?;
 
 			// using absolute fee for now, in production we should come up with a way to determine the tx weight
 			// upfront and substract the fee from the change outputs (10k == ~30/sat vbyte)
@@ -211,15 +321,11 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 			escrow_amount_maker_sat,
 			escrow_amount_taker_sat,
 			escrow_fee_sat_per_participant,
+			musig_pubkey_compressed_hex_maker: maker_psbt_input_data.musig_pubkey_compressed_hex,
+			musig_pubkey_compressed_hex_taker: taker_psbt_input_data.musig_pubkey_compressed_hex,
 		})
 	}
 
-	/// placeholder to validate the returned, signed escrow psbt
-	pub async fn validate_escrow_init_psbt(&self, _escrow_init_psbt: &str) -> Result<()> {
-		warn!("Implement escrow psbt validation. For now, returning Ok");
-		Ok(())
-	}
-
 	/// combines the two signed, hex serialized escrow locking psbts returned by the traders, finalizes it
 	/// and broadcasts the escrow transaction
 	pub async fn combine_and_broadcast_escrow_psbt(
@@ -245,7 +351,7 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 		match wallet.finalize_psbt(&mut maker_psbt, SignOptions::default()) {
 			Ok(true) => {
 				let tx = maker_psbt.extract_tx();
-				self.backend.broadcast(&tx)?;
+				self.chain_backend.broadcast(&tx)?;
 				info!("Escrow transaction broadcasted: {}", tx.txid());
 				Ok(())
 			}
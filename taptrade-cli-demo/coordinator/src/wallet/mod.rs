@@ -1,21 +1,27 @@
+pub mod chain_backend;
 pub mod escrow_psbt;
 pub mod payout_tx;
+pub mod punishment_tx;
 pub mod wallet_utils;
 // pub mod verify_tx;
 #[cfg(test)]
 mod wallet_tests;
 
+pub use self::chain_backend::*;
 pub use self::escrow_psbt::*;
+pub use self::punishment_tx::*;
 use super::*;
 // use verify_tx::*;
 
 #[derive(Clone)]
 pub struct CoordinatorWallet<D: bdk::database::BatchDatabase> {
 	pub wallet: Arc<Mutex<Wallet<D>>>,
-	pub backend: Arc<RpcBlockchain>,
-	pub json_rpc_client: Arc<bdk::bitcoincore_rpc::Client>,
+	// bdk blockchain used to sync the wallet and estimate fees
+	pub backend: Arc<AnyBlockchain>,
+	pub chain_backend: Arc<dyn ChainBackend>,
 	pub mempool: Arc<MempoolHandler>,
 	pub coordinator_feerate: f64,
+	pub network: Network,
 }
 
 #[derive(Debug)]
@@ -27,6 +33,28 @@ pub struct EscrowPsbt {
 	pub escrow_amount_maker_sat: u64,
 	pub escrow_amount_taker_sat: u64,
 	pub escrow_fee_sat_per_participant: u64,
+	pub musig_pubkey_compressed_hex_maker: String,
+	pub musig_pubkey_compressed_hex_taker: String,
+}
+
+/// database backing the coordinator wallet. New derivation indexes have to be persisted before
+/// derived addresses or keys are handed out so they are never reused after a restart.
+pub trait PersistentWalletDatabase: bdk::database::BatchDatabase {
+	fn persist(&self) -> Result<()>;
+}
+
+impl PersistentWalletDatabase for Tree {
+	fn persist(&self) -> Result<()> {
+		self.flush()?;
+		Ok(())
+	}
+}
+
+// only used for tests and temporary wallets
+impl PersistentWalletDatabase for MemoryDatabase {
+	fn persist(&self) -> Result<()> {
+		Ok(())
+	}
 }
 
 /// struct to hold the necessary data to construct the bond transaction
@@ -37,67 +65,68 @@ pub struct BondRequirements {
 	pub min_input_sum_sat: u64,
 }
 
+/// loads the network the coordinator runs on (bitcoin, testnet, signet or regtest) from the env variables, defaults to regtest
+pub fn get_bitcoin_network() -> Result<Network> {
+	let network = env::var("BITCOIN_NETWORK").unwrap_or_else(|_| "regtest".to_string());
+	Network::from_str(&network).with_context(|| {
+		format!(
+			"Invalid BITCOIN_NETWORK {}, use bitcoin, testnet, signet or regtest",
+			network
+		)
+	})
+}
+
 /// sets up the coordinator bdk wallet from the env variables
-pub async fn init_coordinator_wallet() -> Result<CoordinatorWallet<MemoryDatabase>> {
+pub async fn init_coordinator_wallet() -> Result<CoordinatorWallet<Tree>> {
+	let network = get_bitcoin_network()?;
 	let wallet_xprv = ExtendedPrivKey::from_str(
 		&env::var("WALLET_XPRV").context("loading WALLET_XPRV from .env failed")?,
 	)?;
 	let secp_context = secp256k1::Secp256k1::new();
-	let rpc_config = RpcConfig {
-		url: env::var("BITCOIN_RPC_ADDRESS_PORT")?.to_string(),
-		auth: Auth::UserPass {
-			username: env::var("BITCOIN_RPC_USER")?,
-			password: env::var("BITCOIN_RPC_PASSWORD")?,
-		},
-		network: Network::Regtest,
-		// derives wallet name from xprv/wallet
-		wallet_name: bdk::wallet::wallet_name_from_descriptor(
-			Bip86(wallet_xprv, KeychainKind::External),
-			Some(Bip86(wallet_xprv, KeychainKind::Internal)),
-			Network::Regtest,
-			&secp_context,
-		)?,
-		sync_params: None,
-	};
-	let json_rpc_client = Arc::new(Client::new(
-		&rpc_config.url,
-		rpc_config.auth.clone().into(),
-	)?);
-	let json_rpc_client_clone = Arc::clone(&json_rpc_client);
-	// start new mempool instance
-	let mempool = MempoolHandler::new(json_rpc_client_clone).await;
-	let backend = RpcBlockchain::from_config(&rpc_config)?;
+	// derives wallet name from xprv/wallet
+	let wallet_name = bdk::wallet::wallet_name_from_descriptor(
+		Bip86(wallet_xprv, KeychainKind::External),
+		Some(Bip86(wallet_xprv, KeychainKind::Internal)),
+		network,
+		&secp_context,
+	)?;
+	// the wallet is stored in its own tree of the sled database so the derivation index survives restarts
+	let bdk_db_path = env::var("BDK_DB_PATH").context("loading BDK_DB_PATH from .env failed")?;
+	let wallet_db = bdk::sled::open(&bdk_db_path)
+		.with_context(|| format!("Opening wallet database {} failed", bdk_db_path))?
+		.open_tree(&wallet_name)?;
+	let (backend, chain_backend) = init_chain_backend(wallet_name, network)?;
+	let mempool = MempoolHandler::new(Arc::clone(&chain_backend));
 	let wallet = Wallet::new(
 		Bip86(wallet_xprv, KeychainKind::External),
 		Some(Bip86(wallet_xprv, KeychainKind::Internal)),
-		Network::Regtest,
-		MemoryDatabase::new(),
+		network,
+		wallet_db,
 	)?;
 
 	wallet
 		.sync(&backend, SyncOptions::default())
-		.context("Connection to blockchain server failed.")?; // we could also use Esplora to make this async
-	info!("{}", wallet.get_balance()?);
+		.context("Connection to blockchain server failed.")?;
+	info!(
+		"Coordinator wallet on {}: {}",
+		network,
+		wallet.get_balance()?
+	);
 	Ok(CoordinatorWallet {
 		wallet: Arc::new(Mutex::new(wallet)),
 		backend: Arc::new(backend),
-		json_rpc_client,
+		chain_backend,
 		mempool: Arc::new(mempool),
 		coordinator_feerate: env::var("COORDINATOR_FEERATE")?.parse::<f64>()?,
+		network,
 	})
 }
 
-impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
-	/// shutdown function to end the mempool task
-	pub async fn shutdown(&self) {
-		debug!("Shutting down wallet");
-		self.mempool.shutdown().await;
-	}
-
+impl<D: PersistentWalletDatabase> CoordinatorWallet<D> {
 	/// get a new address of the coordinator wallet
 	pub async fn get_new_address(&self) -> Result<String> {
 		let wallet = self.wallet.lock().await;
-		let address = wallet.get_address(bdk::wallet::AddressIndex::New)?;
+		let address = get_new_persisted_address(&wallet)?;
 		Ok(address.address.to_string())
 	}
 
@@ -115,6 +144,7 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 			robot: vec![0],
 			requirements: requirements.clone(),
 			table: Table::Memory,
+			escrow_txid: None,
 		};
 		let invalid_bond = self
 			.validate_bonds(Arc::new(vec![dummy_monitoring_bond]))
@@ -169,7 +199,7 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 					}
 				};
 				// check if bond output to us is big enough
-				match tx.bond_output_sum(&bond.requirements.bond_address) {
+				match tx.bond_output_sum(&bond.requirements.bond_address, self.network) {
 					Ok(amount) => {
 						if amount < bond.requirements.locking_amount_sat {
 							invalid_bonds.insert(
@@ -205,47 +235,128 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 			}
 		}
 
-		// now test all bonds with bitcoin core rpc testmempoolaccept, this would be triggered if the bond inputs are spent in another
-		// transaction on the chain (e.g. out of band mining)
-		let json_rpc_client = self.json_rpc_client.clone();
+		// now test all bonds with testmempoolaccept (or the equivalent of the chain backend), this would be triggered if the
+		// bond inputs are spent in another transaction on the chain (e.g. out of band mining)
+		let chain_backend = Arc::clone(&self.chain_backend);
 		let bonds_clone = Arc::clone(&bonds);
 		let mempool_accept_future = tokio::task::spawn_blocking(move || {
-			test_mempool_accept_bonds(json_rpc_client, bonds_clone)
+			test_mempool_accept_bonds(chain_backend, bonds_clone)
 		});
 		let invalid_bonds_testmempoolaccept = mempool_accept_future.await??;
-		invalid_bonds.extend(invalid_bonds_testmempoolaccept.into_iter());
+		invalid_bonds.extend(invalid_bonds_testmempoolaccept);
 
 		// looks up inputs in the mempool, would be triggered if a transaction appears in the mempool that spends the bond inputs
 		let mempool_bonds = self.mempool.lookup_mempool_inputs(&bonds).await?;
-		invalid_bonds.extend(mempool_bonds.into_iter());
+		invalid_bonds.extend(mempool_bonds);
 		debug!("validate_bond_tx_hex(): Bond validation done.");
 		Ok(invalid_bonds)
 	}
 
+	/// parses the escrow inputs submitted by a trader and checks them against the chain before they
+	/// are accepted for the escrow psbt
+	pub async fn validate_escrow_inputs(&self, escrow_inputs: &[EscrowInput]) -> Result<()> {
+		let inputs = escrow_inputs_to_bdk_input(escrow_inputs)?;
+		let chain_backend = Arc::clone(&self.chain_backend);
+		tokio::task::spawn_blocking(move || {
+			check_escrow_inputs_on_chain(chain_backend.as_ref(), &inputs)
+		})
+		.await??;
+		debug!(
+			"validate_escrow_inputs(): {} escrow inputs valid",
+			escrow_inputs.len()
+		);
+		Ok(())
+	}
+
 	/// Publishes the bond transaction to the mempool as punishment
 	pub fn publish_bond_tx_hex(&self, bond: &str) -> Result<()> {
 		warn!("publish_bond_tx_hex(): publishing cheating bond tx!");
-		let blockchain = &*self.backend;
 		let tx: Transaction = deserialize(&hex::decode(bond)?)?;
 
-		blockchain.broadcast(&tx)?;
+		self.chain_backend.broadcast(&tx)?;
 		Ok(())
 	}
 
-	/// derive a new address from the coordinator wallet and extract the xonly taproot pubkey for use in the
-	/// trade protocol
+	/// returns true if the spent inputs of the bond are all spent by the escrow transaction with the given txid
+	pub async fn is_bond_spent_by_escrow_tx(
+		&self,
+		bond_tx_hex: &str,
+		escrow_txid: &str,
+	) -> Result<bool> {
+		let bond_tx: Transaction = deserialize(&hex::decode(bond_tx_hex)?)?;
+		let escrow_txid = Txid::from_str(escrow_txid)?;
+		let chain_backend = Arc::clone(&self.chain_backend);
+		tokio::task::spawn_blocking(move || {
+			bond_inputs_spent_by_escrow_tx(chain_backend.as_ref(), &bond_tx, &escrow_txid)
+		})
+		.await?
+	}
+
+	/// derive a new address from the coordinator wallet and extract the (untweaked) xonly taproot internal key
+	/// for use in the trade protocol. The untweaked key is used so the wallet is able to sign the escrow script path.
 	pub async fn get_coordinator_taproot_pk(&self) -> Result<XOnlyPublicKey> {
 		let wallet = self.wallet.lock().await;
-		let address = wallet.get_address(bdk::wallet::AddressIndex::New)?;
-		let pubkey = if let Payload::WitnessProgram(witness_program) = &address.payload {
-			witness_program.program().as_bytes()
-		} else {
-			return Err(anyhow!("Getting taproot pubkey failed"));
+		let address_info = get_new_persisted_address(&wallet)?;
+		let (pubkey, _) = derive_coordinator_taproot_key(&wallet, address_info.index)?;
+		Ok(pubkey)
+	}
+
+	/// look up the key origin (fingerprint and derivation path) of a taproot pubkey previously handed out
+	/// by get_coordinator_taproot_pk(), required by the bdk signer to sign the escrow script path
+	fn get_coordinator_key_origin(
+		wallet: &Wallet<D>,
+		coordinator_pk: &XOnlyPublicKey,
+	) -> Result<KeySource> {
+		let last_index = match wallet.database().get_last_index(KeychainKind::External)? {
+			Some(index) => index,
+			None => return Err(anyhow!("Coordinator wallet has not derived any keys yet")),
 		};
-		Ok(XOnlyPublicKey::from_slice(pubkey)?)
+		for index in 0..=last_index {
+			let (pubkey, key_source) = derive_coordinator_taproot_key(wallet, index)?;
+			if pubkey == *coordinator_pk {
+				return Ok(key_source);
+			}
+		}
+		Err(anyhow!(
+			"Coordinator taproot pk {} not derived from coordinator wallet",
+			coordinator_pk
+		))
 	}
 }
 
+/// derive a new address and persist the increased derivation index before it gets handed out
+fn get_new_persisted_address<D: PersistentWalletDatabase>(
+	wallet: &Wallet<D>,
+) -> Result<AddressInfo> {
+	let address_info = wallet.get_address(bdk::wallet::AddressIndex::New)?;
+	wallet.database().persist()?;
+	Ok(address_info)
+}
+
+/// derive the untweaked taproot internal key and its key origin at the given index of the external keychain
+fn derive_coordinator_taproot_key<D: bdk::database::BatchDatabase>(
+	wallet: &Wallet<D>,
+	index: u32,
+) -> Result<(XOnlyPublicKey, KeySource)> {
+	let descriptor = wallet
+		.get_descriptor_for_keychain(KeychainKind::External)
+		.at_derivation_index(index)?;
+	let internal_key = if let Descriptor::Tr(tr) = descriptor {
+		tr.internal_key().clone()
+	} else {
+		return Err(anyhow!(
+			"Coordinator wallet descriptor is not a taproot descriptor"
+		));
+	};
+	let pubkey = internal_key
+		.derive_public_key(wallet.secp_ctx())?
+		.to_x_only_pubkey();
+	let derivation_path = internal_key
+		.full_derivation_path()
+		.ok_or(anyhow!("Coordinator key has no derivation path"))?;
+	Ok((pubkey, (internal_key.master_fingerprint(), derivation_path)))
+}
+
 /// lookup a MonitoringBond by its txid in a Vec of MonitoringBonds
 fn search_monitoring_bond_by_txid(
 	monitoring_bonds: &Vec<MonitoringBond>,
@@ -260,33 +371,21 @@ fn search_monitoring_bond_by_txid(
 	Err(anyhow!("Bond not found in monitoring bonds"))
 }
 
-/// tests all passed MonitoringBonds against bitcoin core rpc testmempoolaccept and returns a HashMap of invalid bonds
+/// tests all passed MonitoringBonds against testmempoolaccept of the chain backend and returns a HashMap of invalid bonds
 fn test_mempool_accept_bonds(
-	json_rpc_client: Arc<Client>,
+	chain_backend: Arc<dyn ChainBackend>,
 	bonds: Arc<Vec<MonitoringBond>>,
 ) -> Result<HashMap<Vec<u8>, (MonitoringBond, anyhow::Error)>> {
 	let mut invalid_bonds: HashMap<Vec<u8>, (MonitoringBond, anyhow::Error)> = HashMap::new();
 
-	// split bonds into chunks of 25 to avoid hitting the maxmimum allowed size of the rpc call
-	let raw_bonds: Vec<Vec<String>> = bonds
+	let bond_txs = bonds
 		.iter()
-		.map(|bond| bond.bond_tx_hex.clone().raw_hex())
-		.collect::<Vec<String>>()
-		.chunks(25) // Assuming `raw_hex()` returns a String or &str
-		.map(|chunk| chunk.to_vec())
-		.collect();
-
-	let mut test_mempool_accept_res = Vec::new();
-	for raw_bonds_subvec in raw_bonds {
-		test_mempool_accept_res.extend(
-			json_rpc_client
-				.deref()
-				.test_mempool_accept(&raw_bonds_subvec)?,
-		);
-	}
+		.map(|bond| Ok(deserialize(&hex::decode(&bond.bond_tx_hex)?)?))
+		.collect::<Result<Vec<Transaction>>>()?;
+	let test_mempool_accept_res = chain_backend.test_mempool_accept(&bond_txs)?;
 
 	for res in test_mempool_accept_res {
-		if !res.allowed {
+		if let Some(reject_reason) = res.reject_reason {
 			let invalid_bond: MonitoringBond =
 				search_monitoring_bond_by_txid(&bonds, &res.txid.to_string())?;
 			invalid_bonds.insert(
@@ -295,8 +394,7 @@ fn test_mempool_accept_bonds(
 					invalid_bond,
 					anyhow!(
 						"Bond not accepted by testmempoolaccept: {:?}",
-						res.reject_reason
-							.unwrap_or("rejected by testmempoolaccept".to_string())
+						reject_reason
 					),
 				),
 			);
@@ -305,13 +403,24 @@ fn test_mempool_accept_bonds(
 	Ok(invalid_bonds)
 }
 
+// name of the chain backend as configured with the CHAIN_BACKEND env variable
+fn chain_backend_kind(backend: &AnyBlockchain) -> &'static str {
+	#[allow(unreachable_patterns)]
+	match backend {
+		AnyBlockchain::Rpc(_) => "rpc",
+		AnyBlockchain::Esplora(_) => "esplora",
+		AnyBlockchain::Electrum(_) => "electrum",
+		_ => "unknown",
+	}
+}
+
 impl fmt::Debug for CoordinatorWallet<Tree> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.debug_struct("CoordinatorWallet")
 			.field("wallet", &self.wallet)
-			// Since ElectrumBlockchain doesn't implement Debug, we can't automatically derive it.
-			// Instead, we can print a placeholder or simply omit it from the debug output.
-			.field("backend", &"ElectrumBlockchain (Debug not implemented)")
+			// the bdk blockchains don't implement Debug, so only the backend selected at runtime is printed
+			.field("backend", &chain_backend_kind(&self.backend))
+			.field("network", &self.network)
 			.finish()
 	}
 }
@@ -1,12 +1,16 @@
 use bdk::FeeRate;
 
 /// construction of the transaction spending the escrow output after a successfull trade as keyspend transaction
+/// or after an escrow (dispute) case as script path transaction
 use super::*;
 use bitcoin;
 
-/// get current feerate from blockchain backend and calculate absolute fees for the keyspend tx
+/// get current feerate from blockchain backend and calculate absolute fees for a payout tx of the given size
 /// depending on the feerate. Fallback to 40sat/vb if the feerate cannot be estimated (e.g. regtest backend).
-fn get_tx_fees_abs_sat(blockchain_backend: &RpcBlockchain) -> Result<(u64, u64)> {
+fn get_tx_fees_abs_sat(
+	blockchain_backend: &AnyBlockchain,
+	tx_size_vb: usize,
+) -> Result<(u64, u64)> {
 	let feerate = match blockchain_backend.estimate_fee(6) {
 		Ok(feerate) => feerate,
 		Err(e) => {
@@ -14,14 +18,12 @@ fn get_tx_fees_abs_sat(blockchain_backend: &RpcBlockchain) -> Result<(u64, u64)>
 			FeeRate::from_sat_per_vb(40.0)
 		}
 	};
-	let keyspend_payout_tx_size_vb = 140; // ~, always 1 input, 2 outputs
-
-	let tx_fee_abs = feerate.fee_vb(keyspend_payout_tx_size_vb);
+	let tx_fee_abs = feerate.fee_vb(tx_size_vb);
 
 	Ok((tx_fee_abs, tx_fee_abs / 2))
 }
 
-impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
+impl<D: PersistentWalletDatabase> CoordinatorWallet<D> {
 	/// loads the escrow descriptor in a temp wallet and return the escrow utxo (as Input and its Outpoint)
 	fn get_escrow_utxo(
 		&self,
@@ -30,7 +32,7 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 		let temp_wallet = Wallet::new(
 			&descriptor.to_string(),
 			None,
-			bdk::bitcoin::Network::Regtest,
+			self.network,
 			MemoryDatabase::new(),
 		)?;
 		temp_wallet.sync(&self.backend, SyncOptions::default())?;
@@ -59,7 +61,9 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 		let (payout_psbt, _) = {
 			let wallet = self.wallet.lock().await;
 			let mut builder = wallet.build_tx();
-			let (tx_fee_abs, tx_fee_abs_sat_per_user) = get_tx_fees_abs_sat(&self.backend)?;
+			let keyspend_payout_tx_size_vb = 140; // ~, always 1 input, 2 outputs
+			let (tx_fee_abs, tx_fee_abs_sat_per_user) =
+				get_tx_fees_abs_sat(&self.backend, keyspend_payout_tx_size_vb)?;
 
 			// why 264 wu?: see escrow_psbt.tx
 			builder.add_foreign_utxo(escrow_utxo_outpoint, escrow_utxo_psbt_input, 264)?;
@@ -81,6 +85,79 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 		Ok(payout_psbt.serialize_hex())
 	}
 
+	/// assembles the script path payout transaction paying the whole escrow output to the escrow winner
+	/// and signs the winner + coordinator tapleaf with the coordinator key. The returned PSBT (hex) has to
+	/// be signed and finalized by the winning trader.
+	pub async fn assemble_script_payout_psbt(
+		&self,
+		payout_information: &ScriptPayoutData,
+	) -> anyhow::Result<String> {
+		let (escrow_utxo_psbt_input, escrow_utxo_outpoint) =
+			self.get_escrow_utxo(&payout_information.escrow_output_descriptor)?;
+		let escrow_utxo_value = escrow_utxo_psbt_input
+			.witness_utxo
+			.as_ref()
+			.ok_or(anyhow!(
+				"Escrow utxo psbt input is missing the witness utxo"
+			))?
+			.value;
+		let winner_leaf_hash = get_escrow_winner_leaf_hash(
+			&payout_information.escrow_output_descriptor,
+			&payout_information.taproot_pk_winner,
+			&payout_information.taproot_pk_coordinator,
+		)?;
+
+		let wallet = self.wallet.lock().await;
+		let coordinator_key_origin =
+			Self::get_coordinator_key_origin(&wallet, &payout_information.taproot_pk_coordinator)?;
+
+		let (mut payout_psbt, _) = {
+			let mut builder = wallet.build_tx();
+			let script_payout_tx_size_vb = 170; // ~, always 1 input (script path), 1 output
+			let (tx_fee_abs, _) = get_tx_fees_abs_sat(&self.backend, script_payout_tx_size_vb)?;
+
+			// 298 wu: 2 schnorr signatures, the 2-of-2 leaf script and the control block (tree depth 2)
+			builder.add_foreign_utxo(escrow_utxo_outpoint, escrow_utxo_psbt_input, 298)?;
+			builder.manually_selected_only();
+
+			builder.add_recipient(
+				payout_information.payout_address_winner.script_pubkey(),
+				escrow_utxo_value
+					.checked_sub(tx_fee_abs)
+					.ok_or(anyhow!("Escrow output too small to pay the payout fee"))?,
+			);
+			builder.fee_absolute(tx_fee_abs);
+
+			builder.finish()?
+		};
+
+		// the descriptor only knows the bare coordinator key, the signer needs the wallet key origin
+		payout_psbt.inputs[0].tap_key_origins.insert(
+			payout_information.taproot_pk_coordinator,
+			(vec![winner_leaf_hash], coordinator_key_origin),
+		);
+
+		let sign_options = SignOptions {
+			trust_witness_utxo: true,
+			try_finalize: false,
+			sign_with_tap_internal_key: false,
+			tap_leaves_options: TapLeavesOptions::Include(vec![winner_leaf_hash]),
+			..Default::default()
+		};
+		wallet.sign(&mut payout_psbt, sign_options)?;
+
+		if !payout_psbt.inputs[0]
+			.tap_script_sigs
+			.contains_key(&(payout_information.taproot_pk_coordinator, winner_leaf_hash))
+		{
+			return Err(anyhow!(
+				"Coordinator failed to sign the script path payout psbt"
+			));
+		}
+		debug!("Assembled script payout psbt: {}", payout_psbt);
+		Ok(payout_psbt.serialize_hex())
+	}
+
 	/// Inserts the aggregated signature into the keyspend transaction and broadcasts it
 	pub async fn broadcast_keyspend_tx(
 		&self,
@@ -88,7 +165,7 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 	) -> anyhow::Result<()> {
 		// we need a bitcoin 0.32 psbt to access the taproot_hash_ty() method
 		let bitcoin_032_psbt = bitcoin::Psbt::from_str(&keyspend_ctx.keyspend_psbt.to_string())?;
-		debug!("Payout psbt: {}", bitcoin_032_psbt.to_string());
+		debug!("Payout psbt: {}", bitcoin_032_psbt);
 
 		// extract the unsigned transaction from the bitcoin 0.32 psbt
 		let mut bitcoin_032_tx: bitcoin::Transaction = bitcoin_032_psbt.clone().extract_tx()?;
@@ -117,8 +194,46 @@ impl<D: bdk::database::BatchDatabase> CoordinatorWallet<D> {
 		let bdk_bitcoin_030_tx: bdk::bitcoin::Transaction =
 			deserialize(&hex::decode(signed_hex_tx.clone())?)?;
 
-		self.backend.broadcast(&bdk_bitcoin_030_tx)?;
+		self.chain_backend.broadcast(&bdk_bitcoin_030_tx)?;
 		debug!("Broadcasted keyspend tx: {}", signed_hex_tx);
 		Ok(())
 	}
 }
+
+/// returns the leaf hash of the escrow tapleaf which is spendable by the winner together with the coordinator
+pub(super) fn get_escrow_winner_leaf_hash(
+	escrow_output_descriptor: &Descriptor<XOnlyPublicKey>,
+	winner_pk: &XOnlyPublicKey,
+	coordinator_pk: &XOnlyPublicKey,
+) -> Result<TapLeafHash> {
+	let tr_descriptor = if let Descriptor::Tr(tr) = escrow_output_descriptor {
+		tr
+	} else {
+		return Err(anyhow!(
+			"Escrow output descriptor is not a taproot descriptor"
+		));
+	};
+	for (_, leaf) in tr_descriptor.iter_scripts() {
+		let leaf_keys: Vec<XOnlyPublicKey> = leaf.iter_pk().collect();
+		let timelock_info = leaf.ext.timelock_info;
+		let is_timelocked = timelock_info.cltv_with_height
+			|| timelock_info.cltv_with_time
+			|| timelock_info.csv_with_height
+			|| timelock_info.csv_with_time;
+		if !is_timelocked
+			&& leaf_keys.len() == 2
+			&& leaf_keys.contains(winner_pk)
+			&& leaf_keys.contains(coordinator_pk)
+		{
+			return Ok(TapLeafHash::from_script(
+				&leaf.encode(),
+				LeafVersion::TapScript,
+			));
+		}
+	}
+	Err(anyhow!(
+		"No escrow tapleaf found for winner {} and coordinator {}",
+		winner_pk,
+		coordinator_pk
+	))
+}
@@ -0,0 +1,174 @@
+/// construction of the CPFP child transactions spending the bond output of published (punished) bonds
+/// so the bond gets confirmed even if the trader tries to get a conflicting transaction mined
+use super::*;
+use bdk::FeeRate;
+
+/// ~ vsize of the cpfp child, always 1 taproot keyspend input, 1 taproot output
+const CPFP_CHILD_TX_SIZE_VB: usize = 111;
+
+/// smallest output value we create, the rest of the bond output can be spent as fee
+const TAPROOT_DUST_LIMIT_SAT: u64 = 330;
+
+/// onchain state of a published bond or its cpfp child
+#[derive(Debug, PartialEq)]
+pub enum PunishmentTxStatus {
+	Confirmed,
+	InMempool,
+	// one of the inputs got spent in a mined conflicting transaction (e.g. of the trader)
+	Conflicted,
+	// neither in the mempool nor mined, can be rebroadcasted
+	Missing,
+}
+
+/// signed cpfp child transaction spending a bond output to the coordinator wallet or
+/// the payout address of the wronged trader (compensation)
+#[derive(Debug)]
+pub struct CpfpChild {
+	pub txid: String,
+	pub tx_hex: String,
+	pub package_feerate_sat_vb: f32,
+	pub output_amount_sat: u64,
+}
+
+/// get the feerate the package of bond and cpfp child should pay to confirm in the next blocks.
+/// Fallback to 40sat/vb if the feerate cannot be estimated (e.g. regtest backend).
+pub fn get_punishment_feerate(blockchain_backend: &AnyBlockchain) -> FeeRate {
+	match blockchain_backend.estimate_fee(2) {
+		Ok(feerate) => feerate,
+		Err(e) => {
+			error!("Failed to estimate fee: {}. Using fallback 40 sat/vb", e);
+			FeeRate::from_sat_per_vb(40.0)
+		}
+	}
+}
+
+impl<D: PersistentWalletDatabase> CoordinatorWallet<D> {
+	/// looks up if the bond (or cpfp child) transaction is mined, in the mempool or got replaced by a mined conflicting transaction
+	pub fn get_punishment_tx_status(&self, tx_hex: &str) -> Result<PunishmentTxStatus> {
+		let tx: Transaction = deserialize(&hex::decode(tx_hex)?)?;
+		match self.chain_backend.get_confirmations(&tx.txid())? {
+			Some(confirmations) if confirmations > 0 => Ok(PunishmentTxStatus::Confirmed),
+			Some(_) => Ok(PunishmentTxStatus::InMempool),
+			None => {
+				for input in tx.input.iter() {
+					if self
+						.chain_backend
+						.is_spent_in_block(&input.previous_output)?
+					{
+						return Ok(PunishmentTxStatus::Conflicted);
+					}
+				}
+				Ok(PunishmentTxStatus::Missing)
+			}
+		}
+	}
+
+	/// looks up if an earlier (replaced) cpfp child forwarding the bond output to the compensation address got
+	/// mined instead of the latest child, returns its txid and output sum
+	pub async fn find_mined_compensation_tx(
+		&self,
+		cpfp_tx_hex: &str,
+		compensation_address: &str,
+	) -> Result<Option<(Txid, u64)>> {
+		let cpfp_tx: Transaction = deserialize(&hex::decode(cpfp_tx_hex)?)?;
+		let compensation_script = Address::from_str(compensation_address)?
+			.require_network(self.network)?
+			.script_pubkey();
+		let chain_backend = Arc::clone(&self.chain_backend);
+		tokio::task::spawn_blocking(move || {
+			compensation_tx_spending_bond_output(
+				chain_backend.as_ref(),
+				&cpfp_tx,
+				&compensation_script,
+			)
+		})
+		.await?
+	}
+
+	/// builds, signs and broadcasts a child transaction spending the bond output back to the coordinator wallet,
+	/// or to the compensation address if passed. The child pays enough fee to bring the package of bond and child
+	/// to the target feerate, limited by the bond output value. Broadcasting a new child replaces the previous
+	/// child of the bond (rbf).
+	pub async fn broadcast_cpfp_child(
+		&self,
+		bond_tx_hex: &str,
+		bond_address: &str,
+		target_feerate: FeeRate,
+		compensation_address: Option<&str>,
+	) -> Result<CpfpChild> {
+		let bond_tx: Transaction = deserialize(&hex::decode(bond_tx_hex)?)?;
+		let bond_script = Address::from_str(bond_address)?
+			.require_network(self.network)?
+			.script_pubkey();
+		let bond_vout = bond_tx
+			.output
+			.iter()
+			.position(|output| output.script_pubkey == bond_script)
+			.ok_or(anyhow!("No output to bond address in bond transaction"))?;
+		let bond_output_value = bond_tx.output[bond_vout].value;
+
+		let wallet = self.wallet.lock().await;
+		let drain_script = match compensation_address {
+			Some(address) => Address::from_str(address)?
+				.require_network(self.network)?
+				.script_pubkey(),
+			None => get_new_persisted_address(&wallet)?.script_pubkey(),
+		};
+		// the wallet has to know the unconfirmed bond output to sign the child
+		wallet.sync(&*self.backend, SyncOptions::default())?;
+		// once the bond is mined the child only has to pay for itself
+		let (bond_fee, bond_vsize) = if self.get_punishment_tx_status(bond_tx_hex)?
+			== PunishmentTxStatus::Confirmed
+		{
+			(0, 0)
+		} else {
+			(
+				bond_tx.input_sum(&*self.backend, &*wallet.database())? - bond_tx.all_output_sum(),
+				bond_tx.vsize(),
+			)
+		};
+
+		// the child pays at least 1 sat/vb itself and can't spend more than the bond output
+		let package_fee = target_feerate.fee_vb(bond_vsize + CPFP_CHILD_TX_SIZE_VB);
+		let child_fee = package_fee
+			.saturating_sub(bond_fee)
+			.max(CPFP_CHILD_TX_SIZE_VB as u64)
+			.min(bond_output_value.saturating_sub(TAPROOT_DUST_LIMIT_SAT));
+
+		let (mut child_psbt, _) = {
+			let mut builder = wallet.build_tx();
+			builder.add_utxo(OutPoint {
+				txid: bond_tx.txid(),
+				vout: bond_vout as u32,
+			})?;
+			builder.manually_selected_only();
+			builder.drain_to(drain_script);
+			builder.fee_absolute(child_fee);
+			builder.enable_rbf();
+			builder.finish()?
+		};
+		if !wallet.sign(&mut child_psbt, SignOptions::default())? {
+			return Err(anyhow!(
+				"Failed to sign cpfp child of bond {}",
+				bond_tx.txid()
+			));
+		}
+		let child_tx = child_psbt.extract_tx();
+		self.chain_backend.broadcast(&child_tx)?;
+
+		Ok(CpfpChild {
+			txid: child_tx.txid().to_string(),
+			tx_hex: hex::encode(serialize(&child_tx)),
+			output_amount_sat: child_tx.all_output_sum(),
+			package_feerate_sat_vb: (bond_fee + child_fee) as f32
+				/ (bond_vsize + child_tx.vsize()) as f32,
+		})
+	}
+
+	/// rebroadcasts a previously created cpfp child, e.g. after the bond got evicted from the mempool
+	pub fn rebroadcast_cpfp_child(&self, child_tx_hex: &str) -> Result<()> {
+		let child_tx: Transaction = deserialize(&hex::decode(child_tx_hex)?)?;
+		self.chain_backend.broadcast(&child_tx)?;
+		Ok(())
+	}
+}
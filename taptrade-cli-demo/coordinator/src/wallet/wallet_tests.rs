@@ -1,8 +1,6 @@
-use std::time::Duration;
-
 use super::escrow_psbt::*;
 use super::*;
-use bdk::bitcoin::secp256k1::XOnlyPublicKey;
+use bdk::bitcoin::{secp256k1::XOnlyPublicKey, TxIn};
 use bdk::miniscript::ToPublicKey;
 use bdk::{
 	bitcoin::{psbt::Input, Network},
@@ -52,31 +50,32 @@ async fn new_test_wallet(wallet_xprv: &str) -> CoordinatorWallet<MemoryDatabase>
 		wallet_name: bdk::wallet::wallet_name_from_descriptor(
 			Bip86(wallet_xprv, KeychainKind::External),
 			Some(Bip86(wallet_xprv, KeychainKind::Internal)),
-			Network::Testnet,
+			Network::Regtest,
 			&secp_context,
 		)
 		.unwrap(),
 		sync_params: None,
 	};
-	let json_rpc_client =
-		Arc::new(Client::new(&rpc_config.url, rpc_config.auth.clone().into()).unwrap());
+	let chain_backend: Arc<dyn ChainBackend> = Arc::new(CoreRpcBackend::new(
+		Client::new(&rpc_config.url, rpc_config.auth.clone().into()).unwrap(),
+	));
 	let backend = RpcBlockchain::from_config(&rpc_config).unwrap();
 
 	let wallet = Wallet::new(
 		Bip86(wallet_xprv, KeychainKind::External),
 		Some(Bip86(wallet_xprv, KeychainKind::Internal)),
-		Network::Testnet,
+		Network::Regtest,
 		MemoryDatabase::new(),
 	)
 	.unwrap();
 	wallet.sync(&backend, SyncOptions::default()).unwrap();
-	tokio::time::sleep(Duration::from_secs(10)).await; // fetch the mempool
 	CoordinatorWallet::<MemoryDatabase> {
 		wallet: Arc::new(Mutex::new(wallet)),
-		backend: Arc::new(backend),
-		json_rpc_client: Arc::clone(&json_rpc_client),
-		mempool: Arc::new(MempoolHandler::new(json_rpc_client).await),
+		backend: Arc::new(AnyBlockchain::from(backend)),
+		chain_backend: Arc::clone(&chain_backend),
+		mempool: Arc::new(MempoolHandler::new(chain_backend)),
 		coordinator_feerate: env::var("COORDINATOR_FEERATE").unwrap().parse().unwrap(),
+		network: Network::Regtest,
 	}
 }
 
@@ -154,7 +153,6 @@ async fn test_transaction_without_signature() {
 		.validate_bond_tx_hex(bond_without_signature, &requirements)
 		.await;
 	assert!(result.is_err());
-	test_wallet.shutdown().await;
 }
 
 #[tokio::test]
@@ -172,7 +170,6 @@ async fn test_transaction_with_invalid_signature() {
 		.validate_bond_tx_hex(bond_with_invalid_signature, &requirements)
 		.await;
 	assert!(result.is_err());
-	test_wallet.shutdown().await;
 }
 
 #[tokio::test]
@@ -189,7 +186,6 @@ async fn test_bond_with_spent_input() {
 		.validate_bond_tx_hex(bond_with_spent_input, &requirements)
 		.await;
 	assert!(result.is_err());
-	test_wallet.shutdown().await;
 }
 
 #[tokio::test]
@@ -204,7 +200,6 @@ async fn test_valid_bond_tx() {
 
 	let result = test_wallet.validate_bond_tx_hex(bond, &requirements).await;
 	assert!(result.is_ok());
-	test_wallet.shutdown().await;
 }
 
 #[tokio::test]
@@ -223,7 +218,6 @@ async fn test_invalid_bond_tx_low_input_sum() {
 		.unwrap_err()
 		.to_string()
 		.contains("Bond input sum too small"));
-	test_wallet.shutdown().await;
 }
 
 #[tokio::test]
@@ -237,7 +231,6 @@ async fn test_invalid_bond_tx_low_output_sum() {
 	};
 
 	let result = test_wallet.validate_bond_tx_hex(bond, &requirements).await;
-	test_wallet.shutdown().await;
 	assert!(result.is_err());
 	assert!(result
 		.unwrap_err()
@@ -256,7 +249,6 @@ async fn test_invalid_bond_tx_low_fee_rate() {
 	};
 
 	let result = test_wallet.validate_bond_tx_hex(bond, &requirements).await;
-	test_wallet.shutdown().await;
 	assert!(result.is_err());
 	assert!(result
 		.unwrap_err()
@@ -287,8 +279,6 @@ async fn test_build_escrow_transaction_output_descriptor() {
 
 		(maker_escrow_data, taker_escrow_data) =
 			get_dummy_escrow_psbt_data(&maker_wallet, &taker_wallet).await;
-		maker_wallet.shutdown().await;
-		taker_wallet.shutdown().await;
 	}
 	println!("created dummmy psbt data");
 	let coordinator_pk = XOnlyPublicKey::from_str(
@@ -439,3 +429,713 @@ fn test_signing_keyspend_payout() {
 	// backend.broadcast(&bdk_bitcoin_030_tx).unwrap();
 	// dbg!(bdk_bitcon_030_tx);
 }
+
+#[test]
+fn test_get_escrow_winner_leaf_hash() {
+	let test_descriptor = Descriptor::<XOnlyPublicKey>::from_str("tr(f00949d6dd1ce99a03f88a1a4f59117d553b0da51728bb7fd5b98fbf541337fb,{{and_v(v:pk(4987f3de20a9b1fa6f76c6758934953a8d615e415f1a656f0f6563694b53107d),pk(8f808f457423ff5e4e20a36d317ce9426f9da2fde875e74e15a04481b94bec06)),and_v(v:pk(f1f1db08126af105974cde6021096525ed390cf9b7cde5fedb17a0b16ed31151),pk(8f808f457423ff5e4e20a36d317ce9426f9da2fde875e74e15a04481b94bec06))},{and_v(v:and_v(v:pk(4987f3de20a9b1fa6f76c6758934953a8d615e415f1a656f0f6563694b53107d),pk(f1f1db08126af105974cde6021096525ed390cf9b7cde5fedb17a0b16ed31151)),after(2048)),and_v(v:pk(4987f3de20a9b1fa6f76c6758934953a8d615e415f1a656f0f6563694b53107d),after(12228))}})#0edq24m2").unwrap();
+	let maker_pk = XOnlyPublicKey::from_str(
+		"4987f3de20a9b1fa6f76c6758934953a8d615e415f1a656f0f6563694b53107d",
+	)
+	.unwrap();
+	let taker_pk = XOnlyPublicKey::from_str(
+		"f1f1db08126af105974cde6021096525ed390cf9b7cde5fedb17a0b16ed31151",
+	)
+	.unwrap();
+	let coordinator_pk = XOnlyPublicKey::from_str(
+		"8f808f457423ff5e4e20a36d317ce9426f9da2fde875e74e15a04481b94bec06",
+	)
+	.unwrap();
+
+	let maker_leaf =
+		payout_tx::get_escrow_winner_leaf_hash(&test_descriptor, &maker_pk, &coordinator_pk)
+			.unwrap();
+	let taker_leaf =
+		payout_tx::get_escrow_winner_leaf_hash(&test_descriptor, &taker_pk, &coordinator_pk)
+			.unwrap();
+	assert_ne!(maker_leaf, taker_leaf);
+	// the maker + taker timelocked leaf must not be used for the escrow payout
+	assert!(
+		payout_tx::get_escrow_winner_leaf_hash(&test_descriptor, &maker_pk, &taker_pk).is_err()
+	);
+}
+
+#[test]
+fn test_coordinator_key_origin_lookup() {
+	let wallet_xprv = ExtendedPrivKey::from_str("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32").unwrap();
+	let wallet = Wallet::new(
+		Bip86(wallet_xprv, KeychainKind::External),
+		Some(Bip86(wallet_xprv, KeychainKind::Internal)),
+		Network::Regtest,
+		MemoryDatabase::new(),
+	)
+	.unwrap();
+	let mut coordinator_pks = Vec::new();
+	for _ in 0..3 {
+		let address_info = wallet.get_address(bdk::wallet::AddressIndex::New).unwrap();
+		let (pubkey, _) = derive_coordinator_taproot_key(&wallet, address_info.index).unwrap();
+		coordinator_pks.push(pubkey);
+	}
+
+	let (fingerprint, derivation_path) =
+		CoordinatorWallet::get_coordinator_key_origin(&wallet, &coordinator_pks[1]).unwrap();
+	assert_eq!(
+		fingerprint,
+		wallet_xprv.fingerprint(&secp256k1::Secp256k1::new())
+	);
+	assert_eq!(
+		derivation_path,
+		bdk::bitcoin::bip32::DerivationPath::from_str("m/86'/1'/0'/0/1").unwrap()
+	);
+
+	let unknown_pk = XOnlyPublicKey::from_str(
+		"8f808f457423ff5e4e20a36d317ce9426f9da2fde875e74e15a04481b94bec06",
+	)
+	.unwrap();
+	assert!(CoordinatorWallet::get_coordinator_key_origin(&wallet, &unknown_pk).is_err());
+}
+
+/// returns a regtest wallet with a new address and a (fake) utxo paying to it as psbt input
+fn get_dummy_escrow_input(wallet_xprv: &str, txid: &str) -> (Wallet<MemoryDatabase>, PsbtInput) {
+	let wallet_xprv = ExtendedPrivKey::from_str(wallet_xprv).unwrap();
+	let wallet = Wallet::new(
+		Bip86(wallet_xprv, KeychainKind::External),
+		Some(Bip86(wallet_xprv, KeychainKind::Internal)),
+		Network::Regtest,
+		MemoryDatabase::new(),
+	)
+	.unwrap();
+	let address = wallet
+		.get_address(bdk::wallet::AddressIndex::New)
+		.unwrap()
+		.address;
+	let utxo = bdk::LocalUtxo {
+		outpoint: OutPoint::new(Txid::from_str(txid).unwrap(), 0),
+		txout: TxOut {
+			value: 100_000,
+			script_pubkey: address.script_pubkey(),
+		},
+		keychain: KeychainKind::External,
+		is_spent: false,
+	};
+	let mut psbt_input = wallet.get_psbt_input(utxo.clone(), None, true).unwrap();
+	// the funding tx is not known to the wallet, so the witness utxo has to be added manually
+	psbt_input.witness_utxo = Some(utxo.txout.clone());
+	(
+		wallet,
+		PsbtInput {
+			psbt_input,
+			utxo: utxo.outpoint,
+		},
+	)
+}
+
+/// assembles an unsigned escrow psbt spending the given inputs to a single output
+fn get_dummy_escrow_psbt(inputs: &[&PsbtInput], output_value: u64) -> PartiallySignedTransaction {
+	let unsigned_tx = Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: inputs
+			.iter()
+			.map(|input| TxIn {
+				previous_output: input.utxo,
+				..Default::default()
+			})
+			.collect(),
+		output: vec![TxOut {
+			value: output_value,
+			script_pubkey: inputs[0]
+				.psbt_input
+				.witness_utxo
+				.as_ref()
+				.unwrap()
+				.script_pubkey
+				.clone(),
+		}],
+	};
+	let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx).unwrap();
+	for (index, input) in inputs.iter().enumerate() {
+		psbt.inputs[index] = input.psbt_input.clone();
+	}
+	psbt
+}
+
+#[test]
+fn test_validate_escrow_init_psbt() {
+	let (maker_wallet, maker_input) = get_dummy_escrow_input("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32", "0000000000000000000000000000000000000000000000000000000000000001");
+	let (_, taker_input) = get_dummy_escrow_input("tprv8ZgxMBicQKsPdKxWZWv9zVc22ubUdFrgaUzA4BZQUpEyMxYX3dwFbNfAGsVJ94zEhUUS1z56YBARpvTEjrSz9NzHyySCL33oMXpbqoGunL4", "0000000000000000000000000000000000000000000000000000000000000002");
+	let escrow_psbt = get_dummy_escrow_psbt(&[&maker_input, &taker_input], 190_000);
+	let unsigned_escrow_psbt = escrow_psbt.to_string();
+
+	let mut maker_signed_psbt = escrow_psbt.clone();
+	let sign_options = SignOptions {
+		trust_witness_utxo: true,
+		try_finalize: true,
+		..SignOptions::default()
+	};
+	maker_wallet
+		.sign(&mut maker_signed_psbt, sign_options)
+		.unwrap();
+	let maker_signed_psbt_hex = maker_signed_psbt.serialize_hex();
+
+	// valid submission of the maker
+	assert!(validate_escrow_init_psbt(
+		&maker_signed_psbt_hex,
+		&unsigned_escrow_psbt,
+		&[maker_input.utxo]
+	)
+	.is_ok());
+
+	// signature on an input not contributed by the submitting trader
+	assert!(validate_escrow_init_psbt(
+		&maker_signed_psbt_hex,
+		&unsigned_escrow_psbt,
+		&[taker_input.utxo]
+	)
+	.is_err());
+
+	// own input not signed
+	assert!(validate_escrow_init_psbt(
+		&escrow_psbt.serialize_hex(),
+		&unsigned_escrow_psbt,
+		&[maker_input.utxo]
+	)
+	.is_err());
+
+	// unsigned transaction differs from the coordinator created one
+	let modified_escrow_psbt = get_dummy_escrow_psbt(&[&maker_input, &taker_input], 180_000);
+	assert!(validate_escrow_init_psbt(
+		&maker_signed_psbt_hex,
+		&modified_escrow_psbt.to_string(),
+		&[maker_input.utxo]
+	)
+	.is_err());
+
+	// invalid signature
+	let mut invalid_sig_psbt = maker_signed_psbt.clone();
+	let mut witness = invalid_sig_psbt.inputs[0]
+		.final_script_witness
+		.clone()
+		.unwrap()
+		.to_vec();
+	witness[0][10] ^= 0x01;
+	invalid_sig_psbt.inputs[0].final_script_witness = Some(Witness::from_slice(&witness));
+	assert!(validate_escrow_init_psbt(
+		&invalid_sig_psbt.serialize_hex(),
+		&unsigned_escrow_psbt,
+		&[maker_input.utxo]
+	)
+	.is_err());
+}
+
+#[test]
+fn test_verify_partial_keyspend_sig() {
+	let secp = musig2::secp256k1::Secp256k1::new();
+	let maker_seckey = musig2::secp256k1::SecretKey::from_slice(&[0x11; 32]).unwrap();
+	let taker_seckey = musig2::secp256k1::SecretKey::from_slice(&[0x22; 32]).unwrap();
+	let maker_pk = maker_seckey.public_key(&secp).to_string();
+	let taker_pk = taker_seckey.public_key(&secp).to_string();
+	let maker_secnonce = musig2::SecNonceBuilder::new([0x33; 32]).build();
+	let taker_secnonce = musig2::SecNonceBuilder::new([0x44; 32]).build();
+	let maker_nonce = maker_secnonce.public_nonce().to_string();
+	let taker_nonce = taker_secnonce.public_nonce().to_string();
+
+	// keyspend only escrow output with the untweaked aggregated key as internal key
+	let agg_pk: MuSig2PubKey = aggregate_musig_pubkeys(&maker_pk, &taker_pk)
+		.unwrap()
+		.aggregated_pubkey();
+	let descriptor = format!("tr({})", agg_pk.x_only_public_key().0);
+	let escrow_output = Descriptor::<XOnlyPublicKey>::from_str(&descriptor).unwrap();
+	let escrow_input = PsbtInput {
+		psbt_input: Input {
+			witness_utxo: Some(TxOut {
+				value: 100_000,
+				script_pubkey: escrow_output.script_pubkey(),
+			}),
+			..Default::default()
+		},
+		utxo: OutPoint::new(
+			Txid::from_str("0000000000000000000000000000000000000000000000000000000000000003")
+				.unwrap(),
+			0,
+		),
+	};
+	let keyspend_psbt_hex = get_dummy_escrow_psbt(&[&escrow_input], 99_000).serialize_hex();
+
+	let maker_verification_data = PartialSigVerificationData::from_hex_str(
+		true,
+		&maker_nonce,
+		&taker_nonce,
+		&maker_pk,
+		&taker_pk,
+		&keyspend_psbt_hex,
+		&descriptor,
+	)
+	.unwrap();
+	let taker_verification_data = PartialSigVerificationData::from_hex_str(
+		false,
+		&maker_nonce,
+		&taker_nonce,
+		&maker_pk,
+		&taker_pk,
+		&keyspend_psbt_hex,
+		&descriptor,
+	)
+	.unwrap();
+
+	let maker_sig: PartialSignature = musig2::sign_partial(
+		&maker_verification_data.agg_keyspend_pk,
+		maker_seckey,
+		maker_secnonce,
+		&maker_verification_data.agg_nonce,
+		maker_verification_data.keyspend_sighash,
+	)
+	.unwrap();
+	let maker_sig_hex = hex::encode(maker_sig.serialize());
+
+	// valid signature of the maker
+	assert!(maker_verification_data.verify(&maker_sig_hex).is_ok());
+
+	// valid maker signature submitted as taker signature
+	assert!(taker_verification_data.verify(&maker_sig_hex).is_err());
+
+	// garbage signature
+	let mut garbage_sig = maker_sig.serialize();
+	garbage_sig[31] ^= 0x01;
+	assert!(maker_verification_data
+		.verify(&hex::encode(garbage_sig))
+		.is_err());
+	assert!(maker_verification_data.verify("not a signature").is_err());
+}
+
+/// chain backend mock holding the known transactions and output spends in memory
+#[derive(Default)]
+struct MockChainBackend {
+	txs: HashMap<Txid, (Transaction, u32)>,
+	output_spends: HashMap<OutPoint, OutputSpend>,
+}
+
+impl ChainBackend for MockChainBackend {
+	fn broadcast(&self, _tx: &Transaction) -> Result<()> {
+		Ok(())
+	}
+
+	fn get_tx(&self, txid: &Txid) -> Result<Option<Transaction>> {
+		Ok(self.txs.get(txid).map(|(tx, _)| tx.clone()))
+	}
+
+	fn get_confirmations(&self, txid: &Txid) -> Result<Option<u32>> {
+		Ok(self.txs.get(txid).map(|(_, confirmations)| *confirmations))
+	}
+
+	fn get_block_height(&self) -> Result<u64> {
+		Ok(100)
+	}
+
+	fn get_output_spends(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, OutputSpend>> {
+		Ok(outpoints
+			.iter()
+			.filter_map(|outpoint| {
+				self.output_spends
+					.get(outpoint)
+					.map(|spend| (*outpoint, spend.clone()))
+			})
+			.collect())
+	}
+}
+
+#[test]
+fn test_electrum_tx_not_found_error() {
+	// electrs and fulcrum forward the bitcoin core error code
+	assert!(is_electrum_tx_not_found_error(&serde_json::json!({
+		"code": -5,
+		"message": "No such mempool or blockchain transaction. Use gettransaction for wallet transactions."
+	})));
+	// electrumx wraps the bitcoin core error in its own daemon error
+	assert!(is_electrum_tx_not_found_error(&serde_json::json!({
+		"code": 2,
+		"message": "daemon error: DaemonError({'code': -5, 'message': 'No such mempool or blockchain transaction. Use gettransaction for wallet transactions.'})"
+	})));
+	// rate limits and server failures don't mean the transaction is missing
+	assert!(!is_electrum_tx_not_found_error(&serde_json::json!({
+		"code": -101,
+		"message": "excessive resource usage"
+	})));
+	assert!(!is_electrum_tx_not_found_error(&serde_json::json!({
+		"code": 2,
+		"message": "daemon error: DaemonError({'code': -28, 'message': 'Loading block index...'})"
+	})));
+}
+
+#[test]
+fn test_chain_backend_mempool_accept_equivalent() {
+	let funding_tx = Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: vec![TxIn::default()],
+		output: vec![TxOut {
+			value: 100_000,
+			script_pubkey: bdk::bitcoin::ScriptBuf::new(),
+		}],
+	};
+	let spending_tx = Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: OutPoint {
+				txid: funding_tx.txid(),
+				vout: 0,
+			},
+			..Default::default()
+		}],
+		output: vec![funding_tx.output[0].clone()],
+	};
+
+	// input is not known to the backend
+	let mut backend = MockChainBackend::default();
+	let res = backend
+		.test_mempool_accept(std::slice::from_ref(&spending_tx))
+		.unwrap();
+	assert_eq!(res[0].txid, spending_tx.txid());
+	assert_eq!(res[0].reject_reason.as_deref(), Some("missing-inputs"));
+
+	// input exists and is unspent
+	backend
+		.txs
+		.insert(funding_tx.txid(), (funding_tx.clone(), 1));
+	let res = backend
+		.test_mempool_accept(std::slice::from_ref(&spending_tx))
+		.unwrap();
+	assert!(res[0].reject_reason.is_none());
+
+	// input spent by a mempool transaction
+	let outpoint = spending_tx.input[0].previous_output;
+	let conflicting_txid = Txid::all_zeros();
+	backend.output_spends.insert(
+		outpoint,
+		OutputSpend {
+			txid: Some(conflicting_txid),
+			confirmed: false,
+		},
+	);
+	let res = backend
+		.test_mempool_accept(std::slice::from_ref(&spending_tx))
+		.unwrap();
+	assert_eq!(
+		res[0].reject_reason.as_deref(),
+		Some("bad-txns-inputs-missingorspent")
+	);
+	assert_eq!(
+		backend.get_mempool_spending_txids(&[outpoint]).unwrap()[&outpoint],
+		conflicting_txid
+	);
+	assert!(!backend.is_spent_in_block(&outpoint).unwrap());
+
+	// conflicting transaction got mined
+	backend.output_spends.get_mut(&outpoint).unwrap().confirmed = true;
+	assert!(backend
+		.get_mempool_spending_txids(&[outpoint])
+		.unwrap()
+		.is_empty());
+	assert!(backend.is_spent_in_block(&outpoint).unwrap());
+}
+
+#[test]
+fn test_escrow_inputs_validation() {
+	let funding_tx = Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: vec![TxIn::default()],
+		output: vec![TxOut {
+			value: 100_000,
+			script_pubkey: bdk::bitcoin::ScriptBuf::from_hex(
+				"5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+			)
+			.unwrap(),
+		}],
+	};
+	let outpoint = OutPoint::new(funding_tx.txid(), 0);
+	let escrow_input = EscrowInput {
+		outpoint: outpoint.to_string(),
+		witness_utxo: WitnessUtxo {
+			value_sat: 100_000,
+			script_pubkey_hex: funding_tx.output[0].script_pubkey.to_hex_string(),
+		},
+		tap_internal_key_hex: Some(
+			"f00949d6dd1ce99a03f88a1a4f59117d553b0da51728bb7fd5b98fbf541337fb".to_string(),
+		),
+		tap_key_origins: vec![TapKeyOrigin {
+			xonly_pubkey_hex: "f00949d6dd1ce99a03f88a1a4f59117d553b0da51728bb7fd5b98fbf541337fb"
+				.to_string(),
+			leaf_hashes_hex: vec![],
+			master_fingerprint_hex: "0a0b0c0d".to_string(),
+			derivation_path: "m/86'/1'/0'/0/0".to_string(),
+		}],
+	};
+
+	// the json fields are parsed into the psbt input
+	let inputs = escrow_inputs_to_bdk_input(std::slice::from_ref(&escrow_input)).unwrap();
+	assert_eq!(inputs[0].utxo, outpoint);
+	assert_eq!(
+		inputs[0].psbt_input.witness_utxo.as_ref(),
+		Some(&funding_tx.output[0])
+	);
+	let internal_key = inputs[0].psbt_input.tap_internal_key.unwrap();
+	let (leaf_hashes, (fingerprint, path)) = &inputs[0].psbt_input.tap_key_origins[&internal_key];
+	assert!(leaf_hashes.is_empty());
+	assert_eq!(fingerprint.to_string(), "0a0b0c0d");
+	assert_eq!(path.to_string(), "m/86'/1'/0'/0/0");
+
+	// empty, duplicate and malformed inputs are rejected
+	assert!(escrow_inputs_to_bdk_input(&[]).is_err());
+	assert!(escrow_inputs_to_bdk_input(&[escrow_input.clone(), escrow_input.clone()]).is_err());
+	let mut malformed_input = escrow_input.clone();
+	malformed_input.outpoint = "l33t".to_string();
+	assert!(escrow_inputs_to_bdk_input(&[malformed_input]).is_err());
+	let mut segwit_v0_input = escrow_input.clone();
+	segwit_v0_input.witness_utxo.script_pubkey_hex =
+		"0014751e76e8199196d454941c45d1b3a323f1433bd6".to_string();
+	assert!(escrow_inputs_to_bdk_input(&[segwit_v0_input]).is_err());
+
+	// funding tx unknown
+	let mut backend = MockChainBackend::default();
+	assert!(check_escrow_inputs_on_chain(&backend, &inputs).is_err());
+
+	// funding tx still in the mempool, unconfirmed utxos are returned by the trader wallet as well
+	backend
+		.txs
+		.insert(funding_tx.txid(), (funding_tx.clone(), 0));
+	assert!(check_escrow_inputs_on_chain(&backend, &inputs).is_ok());
+
+	// exists and is unspent
+	backend
+		.txs
+		.insert(funding_tx.txid(), (funding_tx.clone(), 1));
+	assert!(check_escrow_inputs_on_chain(&backend, &inputs).is_ok());
+
+	// witness utxo claims a different value than the output on chain
+	let mut inflated_input = escrow_input.clone();
+	inflated_input.witness_utxo.value_sat = 200_000;
+	let inflated_inputs = escrow_inputs_to_bdk_input(&[inflated_input]).unwrap();
+	assert!(check_escrow_inputs_on_chain(&backend, &inflated_inputs).is_err());
+
+	// spent by a mempool transaction
+	backend.output_spends.insert(
+		outpoint,
+		OutputSpend {
+			txid: Some(Txid::all_zeros()),
+			confirmed: false,
+		},
+	);
+	assert!(check_escrow_inputs_on_chain(&backend, &inputs).is_err());
+}
+
+#[test]
+fn test_compensation_tx_spending_bond_output() {
+	let compensation_script = bdk::bitcoin::ScriptBuf::from_hex(
+		"5120a12e5d145a4a3ab43f6cc1188435e74f253eace72bd986f1aaf780fd0c653236",
+	)
+	.unwrap();
+	let bond_outpoint = OutPoint::new(Txid::all_zeros(), 0);
+	let child = |value: u64, script_pubkey: bdk::bitcoin::ScriptBuf| Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: bond_outpoint,
+			..Default::default()
+		}],
+		output: vec![TxOut {
+			value,
+			script_pubkey,
+		}],
+	};
+	// the stored child replaced an earlier child with a lower fee, which got mined anyways
+	let stored_child = child(9_000, compensation_script.clone());
+	let mined_child = child(9_500, compensation_script.clone());
+
+	// bond output not spent in a block yet
+	let mut backend = MockChainBackend::default();
+	assert!(
+		compensation_tx_spending_bond_output(&backend, &stored_child, &compensation_script)
+			.unwrap()
+			.is_none()
+	);
+
+	// the earlier child got mined, its txid and amount are the compensation
+	backend
+		.txs
+		.insert(mined_child.txid(), (mined_child.clone(), 1));
+	backend.output_spends.insert(
+		bond_outpoint,
+		OutputSpend {
+			txid: Some(mined_child.txid()),
+			confirmed: true,
+		},
+	);
+	assert_eq!(
+		compensation_tx_spending_bond_output(&backend, &stored_child, &compensation_script)
+			.unwrap(),
+		Some((mined_child.txid(), 9_500))
+	);
+
+	// a spend paying another script is not our compensation tx
+	let foreign_spend = child(9_500, bdk::bitcoin::ScriptBuf::new());
+	backend
+		.txs
+		.insert(foreign_spend.txid(), (foreign_spend.clone(), 1));
+	backend.output_spends.insert(
+		bond_outpoint,
+		OutputSpend {
+			txid: Some(foreign_spend.txid()),
+			confirmed: true,
+		},
+	);
+	assert!(
+		compensation_tx_spending_bond_output(&backend, &stored_child, &compensation_script)
+			.unwrap()
+			.is_none()
+	);
+
+	// the backend doesn't know the txid of the mined spend
+	backend.output_spends.insert(
+		bond_outpoint,
+		OutputSpend {
+			txid: None,
+			confirmed: true,
+		},
+	);
+	assert!(
+		compensation_tx_spending_bond_output(&backend, &stored_child, &compensation_script)
+			.unwrap()
+			.is_none()
+	);
+}
+
+#[test]
+fn test_bond_inputs_spent_by_escrow_tx() {
+	let funding_tx = Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: vec![TxIn::default()],
+		output: vec![
+			TxOut {
+				value: 100_000,
+				script_pubkey: bdk::bitcoin::ScriptBuf::new(),
+			},
+			TxOut {
+				value: 50_000,
+				script_pubkey: bdk::bitcoin::ScriptBuf::new(),
+			},
+		],
+	};
+	let bond_inputs = [
+		OutPoint::new(funding_tx.txid(), 0),
+		OutPoint::new(funding_tx.txid(), 1),
+	];
+	let bond_tx = Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: bond_inputs
+			.iter()
+			.map(|outpoint| TxIn {
+				previous_output: *outpoint,
+				..Default::default()
+			})
+			.collect(),
+		output: vec![funding_tx.output[0].clone()],
+	};
+	// the escrow tx only spends the first bond input
+	let escrow_tx = Transaction {
+		version: 2,
+		lock_time: bdk::bitcoin::absolute::LockTime::ZERO,
+		input: vec![TxIn {
+			previous_output: bond_inputs[0],
+			..Default::default()
+		}],
+		output: vec![funding_tx.output[0].clone()],
+	};
+	let escrow_txid = escrow_tx.txid();
+
+	// escrow tx not broadcasted
+	let mut backend = MockChainBackend::default();
+	backend
+		.txs
+		.insert(funding_tx.txid(), (funding_tx.clone(), 1));
+	assert!(!bond_inputs_spent_by_escrow_tx(&backend, &bond_tx, &escrow_txid).unwrap());
+
+	// escrow tx in the mempool spending one bond input, the other one is unspent
+	backend.txs.insert(escrow_txid, (escrow_tx.clone(), 0));
+	backend.output_spends.insert(
+		bond_inputs[0],
+		OutputSpend {
+			txid: Some(escrow_txid),
+			confirmed: false,
+		},
+	);
+	assert!(bond_inputs_spent_by_escrow_tx(&backend, &bond_tx, &escrow_txid).unwrap());
+
+	// the other bond input gets double spent by an unrelated transaction
+	backend.output_spends.insert(
+		bond_inputs[1],
+		OutputSpend {
+			txid: Some(Txid::all_zeros()),
+			confirmed: false,
+		},
+	);
+	assert!(!bond_inputs_spent_by_escrow_tx(&backend, &bond_tx, &escrow_txid).unwrap());
+	backend.output_spends.remove(&bond_inputs[1]);
+
+	// escrow tx mined, the backend doesn't know the spending txid
+	backend.txs.insert(escrow_txid, (escrow_tx.clone(), 1));
+	backend.output_spends.insert(
+		bond_inputs[0],
+		OutputSpend {
+			txid: None,
+			confirmed: true,
+		},
+	);
+	assert!(bond_inputs_spent_by_escrow_tx(&backend, &bond_tx, &escrow_txid).unwrap());
+
+	// the input not spent by the escrow tx got spent in a block
+	backend.output_spends.insert(
+		bond_inputs[1],
+		OutputSpend {
+			txid: None,
+			confirmed: true,
+		},
+	);
+	assert!(!bond_inputs_spent_by_escrow_tx(&backend, &bond_tx, &escrow_txid).unwrap());
+}
+
+#[test]
+fn test_wallet_db_persists_derivation_index() {
+	let wallet_xprv = ExtendedPrivKey::from_str("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32").unwrap();
+	let db_path = env::temp_dir().join(format!("coordinator-wallet-{}", rand::random::<u64>()));
+	let open_wallet = || {
+		let wallet_db = bdk::sled::open(&db_path)
+			.unwrap()
+			.open_tree("coordinator")
+			.unwrap();
+		Wallet::new(
+			Bip86(wallet_xprv, KeychainKind::External),
+			Some(Bip86(wallet_xprv, KeychainKind::Internal)),
+			Network::Regtest,
+			wallet_db,
+		)
+		.unwrap()
+	};
+
+	let wallet = open_wallet();
+	let first_address = get_new_persisted_address(&wallet).unwrap();
+	let second_address = get_new_persisted_address(&wallet).unwrap();
+	assert_eq!(second_address.index, first_address.index + 1);
+	drop(wallet);
+
+	// after a restart the next address continues at the persisted index
+	let wallet = open_wallet();
+	let address_after_restart = get_new_persisted_address(&wallet).unwrap();
+	assert_eq!(address_after_restart.index, second_address.index + 1);
+	assert_ne!(address_after_restart.address, first_address.address);
+	assert_ne!(address_after_restart.address, second_address.address);
+	drop(wallet);
+	std::fs::remove_dir_all(&db_path).unwrap();
+}
@@ -1,15 +1,77 @@
 /// This module provides utility functions for working with wallets.
 use super::*;
-#[derive(Serialize, Deserialize, Debug)]
+use bdk::bitcoin::{
+	bip32::{DerivationPath, Fingerprint},
+	ScriptBuf,
+};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone)]
 pub struct PsbtInput {
 	pub psbt_input: Input,
 	pub utxo: bdk::bitcoin::OutPoint,
 }
 
+impl TryFrom<&EscrowInput> for PsbtInput {
+	type Error = anyhow::Error;
+
+	/// parses the escrow input submitted by a trader into the psbt input added to the escrow psbt
+	fn try_from(input: &EscrowInput) -> Result<Self> {
+		let utxo = OutPoint::from_str(&input.outpoint)
+			.with_context(|| format!("Invalid escrow input outpoint {}", input.outpoint))?;
+		let witness_utxo = TxOut {
+			value: input.witness_utxo.value_sat,
+			script_pubkey: ScriptBuf::from_hex(&input.witness_utxo.script_pubkey_hex)
+				.with_context(|| format!("Invalid witness utxo script of {}", utxo))?,
+		};
+		// only taproot inputs can be added to the escrow psbt without the full previous transaction
+		if !witness_utxo.script_pubkey.is_v1_p2tr() {
+			return Err(anyhow!("Escrow input {} is not a taproot output", utxo));
+		}
+		let tap_internal_key = input
+			.tap_internal_key_hex
+			.as_deref()
+			.map(XOnlyPublicKey::from_str)
+			.transpose()
+			.with_context(|| format!("Invalid tap internal key of {}", utxo))?;
+		let mut tap_key_origins = BTreeMap::new();
+		for origin in input.tap_key_origins.iter() {
+			let leaf_hashes = origin
+				.leaf_hashes_hex
+				.iter()
+				.map(|hash| TapLeafHash::from_str(hash))
+				.collect::<Result<Vec<TapLeafHash>, _>>()
+				.with_context(|| format!("Invalid tap leaf hash in key origin of {}", utxo))?;
+			let key_source: KeySource = (
+				Fingerprint::from_str(&origin.master_fingerprint_hex).with_context(|| {
+					format!("Invalid key fingerprint in key origin of {}", utxo)
+				})?,
+				DerivationPath::from_str(&origin.derivation_path).with_context(|| {
+					format!("Invalid derivation path in key origin of {}", utxo)
+				})?,
+			);
+			tap_key_origins.insert(
+				XOnlyPublicKey::from_str(&origin.xonly_pubkey_hex)
+					.with_context(|| format!("Invalid pubkey in key origin of {}", utxo))?,
+				(leaf_hashes, key_source),
+			);
+		}
+		Ok(PsbtInput {
+			psbt_input: Input {
+				witness_utxo: Some(witness_utxo),
+				tap_internal_key,
+				tap_key_origins,
+				..Default::default()
+			},
+			utxo,
+		})
+	}
+}
+
 /// implements functions required for bond transactions on the bdk::bitcoin::Transaction struct
 pub trait BondTx {
 	fn input_sum<D: Database, B: GetTx>(&self, blockchain: &B, db: &D) -> Result<u64>;
-	fn bond_output_sum(&self, bond_address: &str) -> Result<u64>;
+	fn bond_output_sum(&self, bond_address: &str, network: Network) -> Result<u64>;
 	fn all_output_sum(&self) -> u64;
 }
 
@@ -55,13 +117,14 @@ impl BondTx for Transaction {
 	/// # Arguments
 	///
 	/// * `bond_address` - The bond address as a string.
+	/// * `network` - The network the bond address has to be valid for.
 	///
 	/// # Returns
 	///
 	/// The sum of output values as a `Result<u64>`.
-	fn bond_output_sum(&self, bond_address: &str) -> Result<u64> {
+	fn bond_output_sum(&self, bond_address: &str, network: Network) -> Result<u64> {
 		let bond_script = Address::from_str(bond_address)?
-			.require_network(Network::Regtest)?
+			.require_network(network)?
 			.script_pubkey();
 
 		for output in self.output.iter() {
@@ -81,23 +144,140 @@ impl BondTx for Transaction {
 	}
 }
 
-/// converts a csv string of bincode binary serialized, hex encoded bdk psbt inputs to a vector of PsbtInput
+/// parses the escrow inputs submitted by a trader to a vector of PsbtInput
 /// # Arguments
 ///
-/// * `inputs_csv_hex` - The CSV string of inputs as hex encoded strings.
+/// * `escrow_inputs` - The escrow inputs of the `BondSubmissionRequest`.
 ///
 /// # Returns
 ///
 /// A vector of `PsbtInput` as a `Result<Vec<PsbtInput>>`.
-pub fn csv_hex_to_bdk_input(inputs_csv_hex: &str) -> Result<Vec<PsbtInput>> {
-	let mut inputs: Vec<PsbtInput> = Vec::new();
-	for input_hex in inputs_csv_hex.split(',') {
-		let binary = hex::decode(input_hex)?;
-		let input: PsbtInput = bincode::deserialize(&binary)?;
-		inputs.push(input);
+pub fn escrow_inputs_to_bdk_input(escrow_inputs: &[EscrowInput]) -> Result<Vec<PsbtInput>> {
+	if escrow_inputs.is_empty() {
+		return Err(anyhow!("No escrow inputs submitted"));
 	}
-	if inputs.is_empty() {
-		return Err(anyhow!("No inputs found in csv input value"));
+	let inputs = escrow_inputs
+		.iter()
+		.map(PsbtInput::try_from)
+		.collect::<Result<Vec<PsbtInput>>>()?;
+	let mut outpoints = HashSet::new();
+	if let Some(duplicate) = inputs.iter().find(|input| !outpoints.insert(input.utxo)) {
+		return Err(anyhow!("Escrow input {} submitted twice", duplicate.utxo));
 	}
 	Ok(inputs)
 }
+
+/// checks the escrow inputs against the chain: the funding transaction has to be known, the spent
+/// output has to match the witness utxo of the input and it must not be spent (mined or in the mempool).
+/// Blocking, wrap in spawn_blocking when called from async code.
+pub fn check_escrow_inputs_on_chain(
+	chain_backend: &dyn ChainBackend,
+	inputs: &[PsbtInput],
+) -> Result<()> {
+	for input in inputs {
+		let funding_tx = chain_backend
+			.get_tx(&input.utxo.txid)?
+			.ok_or_else(|| anyhow!("Escrow input {} not found on chain", input.utxo))?;
+		let output = funding_tx
+			.output
+			.get(input.utxo.vout as usize)
+			.ok_or_else(|| anyhow!("Escrow input {} not found on chain", input.utxo))?;
+		if input.psbt_input.witness_utxo.as_ref() != Some(output) {
+			return Err(anyhow!(
+				"Witness utxo of escrow input {} doesn't match the output on chain",
+				input.utxo
+			));
+		}
+	}
+	let outpoints: Vec<OutPoint> = inputs.iter().map(|input| input.utxo).collect();
+	if let Some(outpoint) = chain_backend.get_output_spends(&outpoints)?.keys().next() {
+		return Err(anyhow!("Escrow input {} is already spent", outpoint));
+	}
+	Ok(())
+}
+
+/// checks if the spent inputs of a bond of a taken offer are spent by its escrow transaction only.
+/// Returns false if no input is spent by the escrow transaction or any input got spent by another transaction
+/// (e.g. a double spend of a single bond input), in this case the bond violation has to be punished.
+/// Blocking, wrap in spawn_blocking when called from async code.
+pub fn bond_inputs_spent_by_escrow_tx(
+	chain_backend: &dyn ChainBackend,
+	bond_tx: &Transaction,
+	escrow_txid: &Txid,
+) -> Result<bool> {
+	let escrow_tx = match chain_backend.get_tx(escrow_txid)? {
+		Some(tx) => tx,
+		None => return Ok(false),
+	};
+	let escrow_confirmed = chain_backend
+		.get_confirmations(escrow_txid)?
+		.is_some_and(|confirmations| confirmations > 0);
+	let outpoints: Vec<OutPoint> = bond_tx.input.iter().map(|i| i.previous_output).collect();
+	let output_spends = chain_backend.get_output_spends(&outpoints)?;
+	if output_spends.is_empty() {
+		return Ok(false);
+	}
+	for (outpoint, spend) in output_spends {
+		let spent_by_escrow_tx = match spend.txid {
+			Some(txid) => txid == *escrow_txid,
+			// the backend doesn't return the txid of mined spends, so the spend is attributed
+			// to the escrow tx if it is mined and spends the outpoint
+			None => {
+				spend.confirmed
+					&& escrow_confirmed
+					&& escrow_tx
+						.input
+						.iter()
+						.any(|input| input.previous_output == outpoint)
+			}
+		};
+		if !spent_by_escrow_tx {
+			return Ok(false);
+		}
+	}
+	Ok(true)
+}
+
+/// looks up the mined transaction spending the bond output the cpfp child spends. Returns its txid and output sum
+/// if it is one of our earlier (replaced) children forwarding the bond output to the compensation script, None if
+/// the spending transaction is unknown or pays somewhere else.
+/// Blocking, wrap in spawn_blocking when called from async code.
+pub fn compensation_tx_spending_bond_output(
+	chain_backend: &dyn ChainBackend,
+	cpfp_tx: &Transaction,
+	compensation_script: &bdk::bitcoin::Script,
+) -> Result<Option<(Txid, u64)>> {
+	let bond_outpoint = match cpfp_tx.input.first() {
+		Some(input) => input.previous_output,
+		None => return Ok(None),
+	};
+	let spending_txid = match chain_backend
+		.get_output_spends(&[bond_outpoint])?
+		.remove(&bond_outpoint)
+	{
+		Some(OutputSpend {
+			txid: Some(txid),
+			confirmed: true,
+		}) => txid,
+		_ => return Ok(None),
+	};
+	let spending_tx = match chain_backend.get_tx(&spending_txid)? {
+		Some(tx) => tx,
+		None => return Ok(None),
+	};
+	// our children spend only the bond output and forward it to the compensation address
+	if spending_tx.input.len() != 1
+		|| spending_tx.input[0].previous_output != bond_outpoint
+		|| spending_tx.output.is_empty()
+		|| spending_tx
+			.output
+			.iter()
+			.any(|output| output.script_pubkey.as_script() != compensation_script)
+	{
+		return Ok(None);
+	}
+	Ok(Some((
+		spending_txid,
+		spending_tx.output.iter().map(|output| output.value).sum(),
+	)))
+}
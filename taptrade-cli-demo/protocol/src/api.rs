@@ -0,0 +1,314 @@
+//! Request and response bodies of the coordinator api endpoints.
+//!
+//! The `OfferRequest` struct represents a request to create an offer. It contains the following fields:
+//! - `robohash_hex`: The identifier of the trader.
+//! - `amount_satoshi`: The amount in satoshi to buy or sell.
+//! - `is_buy_order`: A boolean indicating whether it is a buy order or a sell order.
+//! - `bond_ratio`: The percentage of the trading amount to be used as a bond.
+//! - `offer_duration_ts`: The unix timestamp indicating how long the offer should stay available.
+//! - `network`: The network of the trader, has to match the coordinator network.
+//!
+//! The `BondRequirementResponse` struct represents the response containing bond requirements. It has the following fields:
+//! - `bond_address`: The bond address.
+//! - `locking_amount_sat`: The minimum amount of the bond output in satoshi.
+//! - `escrow_locking_input_amount_without_trade_sum`: The minimum required input amount to the escrow transaction.
+//!
+//! The `BondSubmissionRequest` struct represents a request to submit a bond. It contains the following fields:
+//! - `robohash_hex`: The identifier of the trader.
+//! - `signed_bond_hex`: The signed bond transaction in hex format.
+//! - `payout_address`: The payout address.
+//! - `taproot_pubkey_hex`: The taproot public key in hex format.
+//! - `musig_pub_nonce_hex`: The musig public nonce in hex format.
+//! - `musig_pubkey_hex`: The musig public key in hex format.
+//! - `escrow_inputs`: The `EscrowInput` structs of the utxos the trader locks in the escrow transaction.
+//! - `client_change_address`: The client change address.
+//!
+//! The `EscrowInput` struct represents a utxo of the trader used as input of the escrow transaction. It has the following fields:
+//! - `outpoint`: The outpoint of the utxo in `txid:vout` format.
+//! - `witness_utxo`: The `WitnessUtxo` struct containing the output being spent.
+//! - `tap_internal_key_hex`: The x-only taproot internal key of the output in hex format, if known.
+//! - `tap_key_origins`: The `TapKeyOrigin` structs of the keys able to spend the output, used by the trader wallet to sign the input.
+//!
+//! The `WitnessUtxo` struct represents the output spent by an escrow input. It has the following fields:
+//! - `value_sat`: The value of the output in satoshi.
+//! - `script_pubkey_hex`: The script pubkey of the output in hex format.
+//!
+//! The `TapKeyOrigin` struct represents the origin of a key able to spend a taproot output. It has the following fields:
+//! - `xonly_pubkey_hex`: The x-only public key in hex format.
+//! - `leaf_hashes_hex`: The tap leaf hashes the key is used in, in hex format. Empty for the key path.
+//! - `master_fingerprint_hex`: The fingerprint of the master key in hex format.
+//! - `derivation_path`: The bip32 derivation path of the key, e.g. `m/86'/1'/0'/0/0`.
+//!
+//! The `OfferActivatedResponse` struct represents the response after successfully activating an offer. It has the following fields:
+//! - `offer_id_hex`: The offer ID in hex format.
+//! - `bond_locked_until_timestamp`: The unix timestamp until which the bond should not be touched unless the offer gets taken.
+//!
+//! The `OffersRequest` struct represents a request to get offers. It contains the following fields:
+//! - `buy_offers`: A boolean indicating whether to look for buy offers or sell offers.
+//! - `amount_min_sat`: The minimum amount in satoshi.
+//! - `amount_max_sat`: The maximum amount in satoshi.
+//! - `network`: The network of the trader, has to match the coordinator network.
+//!
+//! The `PublicOffer` struct represents information about a public offer. It has the following fields:
+//! - `amount_sat`: The amount in satoshi.
+//! - `offer_id_hex`: The offer ID in hex format.
+//! - `bond_requirements`: The bond requirements the taker has to fulfill.
+//!
+//! The `PublicOffers` struct represents a collection of public offers. It has the following field:
+//! - `offers`: An optional vector of `PublicOffer` structs. This field is not included in the return JSON if no offers are available.
+//!
+//! The `OfferTakenResponse` struct represents the response after taking an offer. It has the following fields:
+//! - `escrow_psbt_hex`: The escrow PSBT in hex format.
+//! - `escrow_output_descriptor`: The escrow output descriptor.
+//! - `escrow_amount_maker_sat`: The escrow amount for the maker in satoshi.
+//! - `escrow_amount_taker_sat`: The escrow amount for the taker in satoshi.
+//! - `escrow_fee_sat_per_participant`: The escrow fee in satoshi per participant.
+//! - `musig_pubkey_compressed_hex_maker`: The maker musig pubkey aggregated into the escrow output internal key.
+//! - `musig_pubkey_compressed_hex_taker`: The taker musig pubkey aggregated into the escrow output internal key.
+//!
+//! The `OfferPsbtRequest` struct represents a request to receive the escrow PSBT for a specified offer. It contains the following fields:
+//! - `offer`: The `PublicOffer` struct representing the offer.
+//! - `trade_data`: The `BondSubmissionRequest` struct representing the trade data.
+//!
+//! The `OfferTakenRequest` struct identifies a trader acting on a trade (status polling, obligation confirmation). It contains the following fields:
+//! - `robohash_hex`: The identifier of the trader.
+//! - `offer_id_hex`: The offer ID in hex format.
+//!
+//! The `PsbtSubmissionRequest` struct represents a request to submit a PSBT. It contains the following fields:
+//! - `signed_psbt_hex`: The signed PSBT in hex format.
+//! - `offer_id_hex`: The offer ID in hex format.
+//! - `robohash_hex`: The identifier of the trader.
+//!
+//! The `PayoutResponse` struct represents the response after a payout. It has the following fields:
+//! - `payout_psbt_hex`: The payout PSBT in hex format.
+//! - `agg_musig_nonce_hex`: The aggregated musig nonce in hex format.
+//! - `agg_musig_pubkey_ctx_hex`: The aggregated musig public key context in hex format.
+//!
+//! The `ScriptPayoutResponse` struct represents the response to the winner of an escrow case. It has the following field:
+//! - `script_payout_psbt_hex`: The script path payout PSBT in hex format, already signed by the coordinator.
+//!
+//! The `TradeObligationsUnsatisfied` struct represents unsatisfied trade obligations. It has the following fields:
+//! - `robohash_hex`: The identifier of the trader.
+//! - `offer_id_hex`: The offer ID in hex format.
+//!
+//! The `PayoutSignatureRequest` struct represents a request for a payout signature. It contains the following fields:
+//! - `partial_sig_hex`: The partial signature in hex format.
+//! - `offer_id_hex`: The offer ID in hex format.
+//! - `robohash_hex`: The identifier of the trader.
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use validator::{Validate, ValidationError};
+
+// maker step 1
+// requesting to create an offer on the orderbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate)]
+pub struct OfferRequest {
+	pub robohash_hex: String, // identifier of the trader
+	#[validate(range(min = 10000, max = 20000000))]
+	pub amount_satoshi: u64, // amount in satoshi to buy or sell
+	pub is_buy_order: bool,   // true if buy, false if sell
+	#[validate(range(min = 2, max = 50))]
+	pub bond_ratio: u8, // [2, 50]% of trading amount
+	#[validate(custom(function = "validate_timestamp"))]
+	pub offer_duration_ts: u64, // unix timestamp how long the offer should stay available
+	pub network: String,      // network of the trader, has to match the coordinator network
+}
+
+// coordinator answer to maker step 1
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BondRequirementResponse {
+	pub bond_address: String,
+	pub locking_amount_sat: u64, // min amount of the bond output in sat
+	pub escrow_locking_input_amount_without_trade_sum: u64, // minimum required amount of input to the escrow tx
+}
+
+// maker step 2
+// (submission of signed bond and other data neccessary to coordinate the trade)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BondSubmissionRequest {
+	pub robohash_hex: String,
+	pub signed_bond_hex: String,    // signed bond transaction, hex encoded
+	pub payout_address: String,     // does this make sense here?
+	pub taproot_pubkey_hex: String, // used for script path spending
+	pub musig_pub_nonce_hex: String,
+	pub musig_pubkey_hex: String,        // used for key path spending
+	pub escrow_inputs: Vec<EscrowInput>, // utxos the trader locks in the escrow tx
+	pub client_change_address: String,
+}
+
+// utxo of a trader used as input of the escrow tx, contains the psbt input fields
+// the coordinator needs to add it to the escrow psbt
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EscrowInput {
+	pub outpoint: String, // txid:vout
+	pub witness_utxo: WitnessUtxo,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tap_internal_key_hex: Option<String>,
+	#[serde(default)]
+	pub tap_key_origins: Vec<TapKeyOrigin>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WitnessUtxo {
+	pub value_sat: u64,
+	pub script_pubkey_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TapKeyOrigin {
+	pub xonly_pubkey_hex: String,
+	#[serde(default)]
+	pub leaf_hashes_hex: Vec<String>, // empty for the key path
+	pub master_fingerprint_hex: String,
+	pub derivation_path: String, // bip32 path, e.g. m/86'/1'/0'/0/0
+}
+
+// Response after step2 if offer creation was successful and the offer is now online in the orderbook
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfferActivatedResponse {
+	pub offer_id_hex: String,
+	pub bond_locked_until_timestamp: u64, // unix timestamp. Do not touch bond till then unless offer gets taken.
+}
+
+/// request of the maker to withdraw its offer from the orderbook, the signature is a schnorr signature
+/// of the makers taproot key over the offer cancellation message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfferCancellationRequest {
+	pub robohash_hex: String,
+	pub offer_id_hex: String,
+	pub cancellation_signature_hex: String,
+}
+
+// identifies the trader and trade of requests acting on a trade, e.g. polling the offer status,
+// the escrow confirmation or the final payout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfferTakenRequest {
+	pub robohash_hex: String,
+	pub offer_id_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfferTakenResponse {
+	pub escrow_psbt_hex: String,
+	pub escrow_output_descriptor: String,
+	pub escrow_amount_maker_sat: u64,
+	pub escrow_amount_taker_sat: u64,
+	pub escrow_fee_sat_per_participant: u64,
+	pub musig_pubkey_compressed_hex_maker: String,
+	pub musig_pubkey_compressed_hex_taker: String,
+}
+
+// Taker structures //
+
+// request all fitting offers from the coordinator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OffersRequest {
+	pub buy_offers: bool, // true if looking for buy offers, false if looking for sell offers
+	pub amount_min_sat: u64,
+	pub amount_max_sat: u64,
+	pub network: String, // network of the trader, has to match the coordinator network
+}
+
+// response of the coordinator, containing all fitting offers to the OffersRequest request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicOffers {
+	pub offers: Option<Vec<PublicOffer>>, // don't include offers var in return json if no offers are available
+}
+
+// Offer information of each offer returned by the previous response
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublicOffer {
+	pub amount_sat: u64,
+	pub offer_id_hex: String,
+	pub bond_requirements: BondRequirementResponse,
+}
+
+// request to receive the escrow psbt to sign for the specified offer to take it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OfferPsbtRequest {
+	pub offer: PublicOffer,
+	pub trade_data: BondSubmissionRequest,
+}
+
+// submit signed escrow psbt back to coordinator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PsbtSubmissionRequest {
+	pub signed_psbt_hex: String,
+	pub offer_id_hex: String,
+	pub robohash_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayoutResponse {
+	pub payout_psbt_hex: String,
+	pub agg_musig_nonce_hex: String,
+	pub agg_musig_pubkey_ctx_hex: String,
+}
+
+// returned to the winner of an escrow case, the psbt is already signed by the coordinator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScriptPayoutResponse {
+	pub script_payout_psbt_hex: String,
+}
+
+// the coordinator returns either the keyspend payout data or the script payout psbt on /poll-final-payout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PayoutPollResponse {
+	Keyspend(PayoutResponse),
+	ScriptPath(ScriptPayoutResponse),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TradeObligationsUnsatisfied {
+	pub robohash_hex: String,
+	pub offer_id_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PayoutSignatureRequest {
+	pub partial_sig_hex: String,
+	pub offer_id_hex: String,
+	pub robohash_hex: String,
+}
+
+/// Validates the offer duration timestamp of an OfferRequest.
+///
+/// The offer has to stay available between 3 hours and 7 days from now, otherwise
+/// a validation error is returned.
+///
+/// # Example
+///
+/// ```
+/// use taptrade_protocol::api::validate_timestamp;
+/// use std::time::{SystemTime, UNIX_EPOCH};
+///
+/// let unix_timestamp = SystemTime::now()
+///     .duration_since(UNIX_EPOCH)
+///     .expect("Time went backwards")
+///     .as_secs();
+///
+/// // a timestamp within the valid range (current time + 4 hours)
+/// assert!(validate_timestamp(unix_timestamp + 4 * 3600).is_ok());
+/// assert!(validate_timestamp(unix_timestamp + 3600).is_err());
+/// ```
+///
+/// # Panics
+///
+/// This function may panic if the system time goes backwards during the calculation of the current time.
+pub fn validate_timestamp(offer_duration_ts: u64) -> Result<(), ValidationError> {
+	// Get the current time
+	let now = SystemTime::now();
+	// Convert the current time to a UNIX timestamp
+	let unix_timestamp = now
+		.duration_since(UNIX_EPOCH)
+		.expect("Time went backwards")
+		.as_secs();
+	if offer_duration_ts < unix_timestamp + 10800 {
+		return Err(ValidationError::new("Offer duration too short"));
+	}
+	if offer_duration_ts > unix_timestamp + 604800 {
+		return Err(ValidationError::new("Offer duration too long"));
+	}
+	Ok(())
+}
@@ -0,0 +1,20 @@
+//! Paths of the coordinator api endpoints, all of them take POST requests with a json body.
+
+// maker
+pub const CREATE_OFFER: &str = "/create-offer";
+pub const SUBMIT_MAKER_BOND: &str = "/submit-maker-bond";
+pub const REQUEST_OFFER_STATUS: &str = "/request-offer-status";
+pub const CANCEL_OFFER: &str = "/cancel-offer";
+
+// taker
+pub const FETCH_AVAILABLE_OFFERS: &str = "/fetch-available-offers";
+pub const SUBMIT_TAKER_BOND: &str = "/submit-taker-bond";
+
+// both traders, signed with the taproot key of the trader
+pub const SUBMIT_ESCROW_PSBT: &str = "/submit-escrow-psbt";
+pub const POLL_ESCROW_CONFIRMATION: &str = "/poll-escrow-confirmation";
+pub const SUBMIT_OBLIGATION_CONFIRMATION: &str = "/submit-obligation-confirmation";
+pub const REQUEST_ESCROW: &str = "/request-escrow";
+pub const POLL_FINAL_PAYOUT: &str = "/poll-final-payout";
+pub const SUBMIT_PAYOUT_SIGNATURE: &str = "/submit-payout-signature";
+pub const TRADE_EVENTS: &str = "/trade-events";
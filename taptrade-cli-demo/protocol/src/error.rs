@@ -0,0 +1,81 @@
+//! Json body of the error responses of the coordinator api.
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// machine readable error codes returned in the ErrorResponse body. Codes added by newer
+/// coordinators decode as Unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+	InvalidRequest,
+	NetworkMismatch,
+	Unauthorized,
+	NotFound,
+	BondNotFound,
+	InvalidBond,
+	InvalidEscrowInputs,
+	EscrowNotConfirmed,
+	PsbtAlreadySubmitted,
+	TradeStateConflict,
+	PsbtInvalid,
+	PartialSigInvalid,
+	TradeAborted,
+	EscrowLost,
+	Database,
+	Internal,
+	#[serde(other)]
+	Unknown,
+}
+
+impl ErrorCode {
+	/// http status code the coordinator responds with for this error
+	pub fn http_status(&self) -> u16 {
+		match self {
+			ErrorCode::InvalidRequest | ErrorCode::NetworkMismatch => 400,
+			ErrorCode::Unauthorized => 401,
+			ErrorCode::NotFound | ErrorCode::BondNotFound => 404,
+			ErrorCode::InvalidBond
+			| ErrorCode::InvalidEscrowInputs
+			| ErrorCode::EscrowNotConfirmed
+			| ErrorCode::PsbtInvalid => 406,
+			ErrorCode::PsbtAlreadySubmitted | ErrorCode::TradeStateConflict => 409,
+			ErrorCode::PartialSigInvalid => 422,
+			ErrorCode::TradeAborted | ErrorCode::EscrowLost => 410,
+			ErrorCode::Database | ErrorCode::Internal | ErrorCode::Unknown => 500,
+		}
+	}
+}
+
+/// json body of all error responses of the coordinator api. Retryable errors are temporary,
+/// the trader can repeat the same request later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+	pub code: ErrorCode,
+	pub message: String,
+	pub retryable: bool,
+}
+
+impl ErrorResponse {
+	pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+		let retryable = matches!(
+			code,
+			ErrorCode::EscrowNotConfirmed
+				| ErrorCode::TradeStateConflict
+				| ErrorCode::Database
+				| ErrorCode::Internal
+		);
+		Self {
+			code,
+			message: message.into(),
+			retryable,
+		}
+	}
+}
+
+impl fmt::Display for ErrorResponse {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} ({:?})", self.message, self.code)
+	}
+}
+
+impl std::error::Error for ErrorResponse {}
@@ -0,0 +1,28 @@
+//! Wire types of the taptrade coordinator api, shared by the coordinator and the trader client.
+//! Changes to the json encoding of these types break the compatibility between coordinator and
+//! trader versions and require a new PROTOCOL_VERSION.
+pub mod api;
+pub mod endpoints;
+pub mod error;
+pub mod signing;
+
+#[cfg(test)]
+mod protocol_tests;
+
+pub use api::*;
+pub use error::*;
+pub use signing::*;
+
+/// version of the api spoken by coordinator and trader, has to be increased on breaking changes
+/// of the wire types or endpoints. Returned by the coordinator in the PROTOCOL_VERSION_HEADER.
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// response header containing the PROTOCOL_VERSION of the coordinator
+pub const PROTOCOL_VERSION_HEADER: &str = "x-taptrade-protocol-version";
+/// request header containing the bitcoin network of the trader (e.g. regtest), the coordinator
+/// rejects all requests of traders on another network
+pub const NETWORK_HEADER: &str = "x-taptrade-network";
+/// header containing the hex encoded schnorr signature of a request acting on a trade
+pub const SIGNATURE_HEADER: &str = "x-taptrade-signature";
+/// header containing the unix timestamp (seconds) the request got signed at
+pub const TIMESTAMP_HEADER: &str = "x-taptrade-timestamp";
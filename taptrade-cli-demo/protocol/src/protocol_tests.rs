@@ -0,0 +1,299 @@
+use super::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Debug;
+
+// serializes the value, compares it against the json the other side expects on the wire and
+// deserializes it again
+fn assert_round_trip<T>(value: T, expected_json: Value)
+where
+	T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+	let encoded = serde_json::to_value(&value).unwrap();
+	assert_eq!(encoded, expected_json);
+	let decoded: T = serde_json::from_value(encoded).unwrap();
+	assert_eq!(decoded, value);
+}
+
+fn bond_requirements() -> (BondRequirementResponse, Value) {
+	(
+		BondRequirementResponse {
+			bond_address: "bcrt1qbond".to_string(),
+			locking_amount_sat: 5000,
+			escrow_locking_input_amount_without_trade_sum: 12000,
+		},
+		json!({
+			"bond_address": "bcrt1qbond",
+			"locking_amount_sat": 5000,
+			"escrow_locking_input_amount_without_trade_sum": 12000
+		}),
+	)
+}
+
+fn bond_submission() -> (BondSubmissionRequest, Value) {
+	(
+		BondSubmissionRequest {
+			robohash_hex: "a1a1".to_string(),
+			signed_bond_hex: "0200".to_string(),
+			payout_address: "bcrt1qpayout".to_string(),
+			taproot_pubkey_hex: "b2b2".to_string(),
+			musig_pub_nonce_hex: "c3c3".to_string(),
+			musig_pubkey_hex: "d4d4".to_string(),
+			escrow_inputs: vec![EscrowInput {
+				outpoint: "e5e5:1".to_string(),
+				witness_utxo: WitnessUtxo {
+					value_sat: 150000,
+					script_pubkey_hex: "5120f6f6".to_string(),
+				},
+				tap_internal_key_hex: Some("f6f6".to_string()),
+				tap_key_origins: vec![TapKeyOrigin {
+					xonly_pubkey_hex: "f6f6".to_string(),
+					leaf_hashes_hex: vec![],
+					master_fingerprint_hex: "0a0b0c0d".to_string(),
+					derivation_path: "m/86'/1'/0'/0/3".to_string(),
+				}],
+			}],
+			client_change_address: "bcrt1qchange".to_string(),
+		},
+		json!({
+			"robohash_hex": "a1a1",
+			"signed_bond_hex": "0200",
+			"payout_address": "bcrt1qpayout",
+			"taproot_pubkey_hex": "b2b2",
+			"musig_pub_nonce_hex": "c3c3",
+			"musig_pubkey_hex": "d4d4",
+			"escrow_inputs": [{
+				"outpoint": "e5e5:1",
+				"witness_utxo": {"value_sat": 150000, "script_pubkey_hex": "5120f6f6"},
+				"tap_internal_key_hex": "f6f6",
+				"tap_key_origins": [{
+					"xonly_pubkey_hex": "f6f6",
+					"leaf_hashes_hex": [],
+					"master_fingerprint_hex": "0a0b0c0d",
+					"derivation_path": "m/86'/1'/0'/0/3"
+				}]
+			}],
+			"client_change_address": "bcrt1qchange"
+		}),
+	)
+}
+
+#[test]
+fn test_escrow_input_optional_fields() {
+	// inputs of clients that don't know the key origins are still accepted
+	let input: EscrowInput = serde_json::from_value(json!({
+		"outpoint": "e5e5:0",
+		"witness_utxo": {"value_sat": 1000, "script_pubkey_hex": "5120f6f6"}
+	}))
+	.unwrap();
+	assert_eq!(input.tap_internal_key_hex, None);
+	assert!(input.tap_key_origins.is_empty());
+}
+
+#[test]
+fn test_maker_messages_round_trip() {
+	assert_round_trip(
+		OfferRequest {
+			robohash_hex: "a1a1".to_string(),
+			amount_satoshi: 100000,
+			is_buy_order: true,
+			bond_ratio: 5,
+			offer_duration_ts: 1700000000,
+			network: "regtest".to_string(),
+		},
+		json!({
+			"robohash_hex": "a1a1",
+			"amount_satoshi": 100000,
+			"is_buy_order": true,
+			"bond_ratio": 5,
+			"offer_duration_ts": 1700000000,
+			"network": "regtest"
+		}),
+	);
+	let (requirements, requirements_json) = bond_requirements();
+	assert_round_trip(requirements, requirements_json);
+	let (submission, submission_json) = bond_submission();
+	assert_round_trip(submission, submission_json);
+	assert_round_trip(
+		OfferActivatedResponse {
+			offer_id_hex: "0f0f".to_string(),
+			bond_locked_until_timestamp: 1700000000,
+		},
+		json!({"offer_id_hex": "0f0f", "bond_locked_until_timestamp": 1700000000}),
+	);
+	assert_round_trip(
+		OfferCancellationRequest {
+			robohash_hex: "a1a1".to_string(),
+			offer_id_hex: "0f0f".to_string(),
+			cancellation_signature_hex: "5151".to_string(),
+		},
+		json!({
+			"robohash_hex": "a1a1",
+			"offer_id_hex": "0f0f",
+			"cancellation_signature_hex": "5151"
+		}),
+	);
+}
+
+#[test]
+fn test_taker_messages_round_trip() {
+	assert_round_trip(
+		OffersRequest {
+			buy_offers: false,
+			amount_min_sat: 90000,
+			amount_max_sat: 110000,
+			network: "regtest".to_string(),
+		},
+		json!({
+			"buy_offers": false,
+			"amount_min_sat": 90000,
+			"amount_max_sat": 110000,
+			"network": "regtest"
+		}),
+	);
+	let (requirements, requirements_json) = bond_requirements();
+	let offer = PublicOffer {
+		amount_sat: 100000,
+		offer_id_hex: "0f0f".to_string(),
+		bond_requirements: requirements,
+	};
+	let offer_json = json!({
+		"amount_sat": 100000,
+		"offer_id_hex": "0f0f",
+		"bond_requirements": requirements_json
+	});
+	assert_round_trip(
+		PublicOffers {
+			offers: Some(vec![offer.clone()]),
+		},
+		json!({ "offers": [offer_json.clone()] }),
+	);
+	let (submission, submission_json) = bond_submission();
+	assert_round_trip(
+		OfferPsbtRequest {
+			offer,
+			trade_data: submission,
+		},
+		json!({ "offer": offer_json, "trade_data": submission_json }),
+	);
+	assert_round_trip(
+		OfferTakenResponse {
+			escrow_psbt_hex: "7070".to_string(),
+			escrow_output_descriptor: "tr(xonly)".to_string(),
+			escrow_amount_maker_sat: 105000,
+			escrow_amount_taker_sat: 5000,
+			escrow_fee_sat_per_participant: 300,
+			musig_pubkey_compressed_hex_maker: "0202".to_string(),
+			musig_pubkey_compressed_hex_taker: "0303".to_string(),
+		},
+		json!({
+			"escrow_psbt_hex": "7070",
+			"escrow_output_descriptor": "tr(xonly)",
+			"escrow_amount_maker_sat": 105000,
+			"escrow_amount_taker_sat": 5000,
+			"escrow_fee_sat_per_participant": 300,
+			"musig_pubkey_compressed_hex_maker": "0202",
+			"musig_pubkey_compressed_hex_taker": "0303"
+		}),
+	);
+}
+
+#[test]
+fn test_trade_messages_round_trip() {
+	assert_round_trip(
+		OfferTakenRequest {
+			robohash_hex: "a1a1".to_string(),
+			offer_id_hex: "0f0f".to_string(),
+		},
+		json!({"robohash_hex": "a1a1", "offer_id_hex": "0f0f"}),
+	);
+	assert_round_trip(
+		PsbtSubmissionRequest {
+			signed_psbt_hex: "7070".to_string(),
+			offer_id_hex: "0f0f".to_string(),
+			robohash_hex: "a1a1".to_string(),
+		},
+		json!({"signed_psbt_hex": "7070", "offer_id_hex": "0f0f", "robohash_hex": "a1a1"}),
+	);
+	assert_round_trip(
+		TradeObligationsUnsatisfied {
+			robohash_hex: "a1a1".to_string(),
+			offer_id_hex: "0f0f".to_string(),
+		},
+		json!({"robohash_hex": "a1a1", "offer_id_hex": "0f0f"}),
+	);
+	assert_round_trip(
+		PayoutSignatureRequest {
+			partial_sig_hex: "8080".to_string(),
+			offer_id_hex: "0f0f".to_string(),
+			robohash_hex: "a1a1".to_string(),
+		},
+		json!({"partial_sig_hex": "8080", "offer_id_hex": "0f0f", "robohash_hex": "a1a1"}),
+	);
+}
+
+#[test]
+fn test_payout_poll_response_variants() {
+	// the untagged variants are told apart by their fields
+	assert_round_trip(
+		PayoutPollResponse::Keyspend(PayoutResponse {
+			payout_psbt_hex: "7070".to_string(),
+			agg_musig_nonce_hex: "9090".to_string(),
+			agg_musig_pubkey_ctx_hex: "a0a0".to_string(),
+		}),
+		json!({
+			"payout_psbt_hex": "7070",
+			"agg_musig_nonce_hex": "9090",
+			"agg_musig_pubkey_ctx_hex": "a0a0"
+		}),
+	);
+	assert_round_trip(
+		PayoutPollResponse::ScriptPath(ScriptPayoutResponse {
+			script_payout_psbt_hex: "7171".to_string(),
+		}),
+		json!({"script_payout_psbt_hex": "7171"}),
+	);
+}
+
+#[test]
+fn test_error_response_round_trip() {
+	let error = ErrorResponse::new(ErrorCode::EscrowNotConfirmed, "not confirmed yet");
+	assert!(error.retryable);
+	assert_eq!(error.code.http_status(), 406);
+	assert_round_trip(
+		error,
+		json!({"code": "escrow_not_confirmed", "message": "not confirmed yet", "retryable": true}),
+	);
+
+	// codes of newer coordinators are still readable
+	let error: ErrorResponse = serde_json::from_value(
+		json!({"code": "something_new", "message": "new error", "retryable": false}),
+	)
+	.unwrap();
+	assert_eq!(error.code, ErrorCode::Unknown);
+}
+
+#[test]
+fn test_signature_preimages() {
+	// both binaries sign and verify these exact bytes, changing them breaks every signed request
+	let body = serde_json::to_vec(&OfferTakenRequest {
+		robohash_hex: "a1a1".to_string(),
+		offer_id_hex: "b2b2".to_string(),
+	})
+	.unwrap();
+	let preimage = request_signature_preimage(endpoints::REQUEST_ESCROW, 1700000000, &body);
+	assert_eq!(
+		preimage,
+		br#"taptrade-request:/request-escrow:1700000000:{"robohash_hex":"a1a1","offer_id_hex":"b2b2"}"#
+	);
+	let signed_body = &preimage[preimage.len() - body.len()..];
+	assert_eq!(
+		serde_json::from_slice::<OfferTakenRequest>(signed_body).unwrap(),
+		OfferTakenRequest {
+			robohash_hex: "a1a1".to_string(),
+			offer_id_hex: "b2b2".to_string(),
+		}
+	);
+
+	assert_eq!(offer_cancellation_preimage("b2b2"), b"cancel-offer:b2b2");
+}
@@ -0,0 +1,15 @@
+//! Preimages of the messages the traders sign with their taproot key. The signed message is the
+//! sha256 hash of the preimage, a schnorr signature over it is verified by the coordinator.
+
+/// preimage of the signature of a request acting on a trade, commits to the endpoint path,
+/// the unix timestamp in the TIMESTAMP_HEADER and the json body
+pub fn request_signature_preimage(path: &str, timestamp: u64, body: &[u8]) -> Vec<u8> {
+	let mut preimage = format!("taptrade-request:{}:{}:", path, timestamp).into_bytes();
+	preimage.extend_from_slice(body);
+	preimage
+}
+
+/// preimage of the signature the maker passes in the OfferCancellationRequest
+pub fn offer_cancellation_preimage(offer_id_hex: &str) -> Vec<u8> {
+	format!("cancel-offer:{}", offer_id_hex).into_bytes()
+}
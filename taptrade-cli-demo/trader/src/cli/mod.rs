@@ -1,10 +1,9 @@
 use crate::wallet::get_wallet_xprv;
-use anyhow::{anyhow, Result};
-use bdk::bitcoin::bip32::ExtendedPrivKey;
-use sha2::{Digest, Sha256};
+use anyhow::{Context, Result};
+use bdk::bitcoin::{bip32::ExtendedPrivKey, Address, Network};
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
 use std::{
-	env,
-	io::{self, Write},
+	str::FromStr,
 	time::{SystemTime, UNIX_EPOCH},
 };
 
@@ -27,6 +26,31 @@ pub struct TraderSettings {
 	pub bond_ratio: u8,
 	pub wallet_xprv: ExtendedPrivKey,
 	pub duration_unix_ts: u64, // until when the order should stay available
+	pub network: Network,
+}
+
+/// settings to recover the escrow funds through the timelocked escrow leafs if the coordinator disappears
+#[derive(Debug)]
+pub struct RecoverySettings {
+	pub electrum_endpoint: String,
+	pub escrow_output_descriptor: String,
+	pub wallet_xprv: ExtendedPrivKey,
+	pub payout_address: String,
+	pub recovery_psbt_file: String, // psbt exchanged between the traders to sign the two party leaf
+	pub counterparty_payout_address: Option<String>,
+	pub counterparty_payout_amount_sat: Option<u64>,
+	pub expected_payout_amount_sat: Option<u64>, // agreed payout of an imported recovery psbt
+	pub network: Network,
+}
+
+/// settings to withdraw an offer of the maker from the orderbook
+#[derive(Debug)]
+pub struct CancellationSettings {
+	pub coordinator_endpoint: String,
+	pub robosats_robohash_hex: String,
+	pub wallet_xprv: ExtendedPrivKey,
+	pub offer_id_hex: String,
+	pub network: Network,
 }
 
 #[derive(Debug)]
@@ -34,12 +58,157 @@ pub enum CliSettings {
 	Coordinator(Coordinator),
 	Taker(TraderSettings),
 	Maker(TraderSettings),
+	Recover(RecoverySettings),
+	Cancel(CancellationSettings),
+}
+
+#[derive(Parser, Debug)]
+#[command(
+	version,
+	about = "RoboSats taproot onchain trade pipeline CLI demonstrator. Don't use with real funds."
+)]
+struct Cli {
+	/// .env file to load all arguments not passed as flag from (e.g. maker.env)
+	#[arg(long, global = true)]
+	env_file: Option<String>,
+
+	#[command(subcommand)]
+	mode: Mode,
+}
+
+#[derive(Subcommand, Debug)]
+enum Mode {
+	/// Create an offer and wait until it gets taken
+	Maker(TraderArgs),
+	/// Take a suitable offer from the coordinator orderbook
+	Taker(TraderArgs),
+	/// Recover the escrow funds through the timelocked escrow paths if the coordinator disappeared
+	Recover(RecoveryArgs),
+	/// Withdraw an offer from the orderbook before it gets taken
+	Cancel(CancellationArgs),
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+enum TradeType {
+	Buy,
+	Sell,
+}
+
+#[derive(Args, Debug)]
+struct TraderArgs {
+	/// Electrum server endpoint, e.g. tcp://localhost:50001
+	#[arg(long, env = "ELECTRUM_ENDPOINT")]
+	electrum_endpoint: String,
+
+	/// Coordinator endpoint, e.g. http://127.0.0.1:9999
+	#[arg(long, env = "COORDINATOR_ENDPOINT")]
+	coordinator_endpoint: String,
+
+	/// Robohash of the trader (hex)
+	#[arg(long, env = "ROBOHASH_HEX")]
+	robohash_hex: String,
+
+	/// Whether to buy or sell satoshis
+	#[arg(long, env = "TRADE_TYPE", value_enum, ignore_case = true)]
+	trade_type: TradeType,
+
+	/// Amount of satoshis to buy or sell
+	#[arg(long, env = "AMOUNT_SAT")]
+	amount_sat: u64,
+
+	/// Payout address for refunded bonds or the trade payout
+	#[arg(long, env = "PAYOUT_ADDRESS")]
+	payout_address: String,
+
+	/// Bond ratio in percent of the trade amount
+	#[arg(long, env = "BOND_RATIO", value_parser = clap::value_parser!(u8).range(2..=50))]
+	bond_ratio: u8,
+
+	/// How many hours the offer should stay online
+	#[arg(long, env = "OFFER_DURATION_HOURS")]
+	offer_duration_hours: u64,
+
+	/// Funded wallet xprv, a new one is generated if not passed
+	#[arg(long, env = "XPRV")]
+	xprv: Option<String>,
+
+	/// Bitcoin network (bitcoin, testnet, signet or regtest), has to match the coordinator network
+	#[arg(long, env = "BITCOIN_NETWORK", default_value = "regtest", value_parser = Network::from_str)]
+	network: Network,
+}
+
+#[derive(Args, Debug)]
+struct RecoveryArgs {
+	/// Electrum server endpoint, e.g. tcp://localhost:50001
+	#[arg(long, env = "ELECTRUM_ENDPOINT")]
+	electrum_endpoint: String,
+
+	/// Escrow output descriptor logged by the trader once the escrow got locked
+	#[arg(long, env = "ESCROW_OUTPUT_DESCRIPTOR")]
+	escrow_output_descriptor: String,
+
+	/// Wallet xprv used for the trade
+	#[arg(long, env = "XPRV")]
+	xprv: String,
+
+	/// Payout address for the recovered funds
+	#[arg(long, env = "PAYOUT_ADDRESS")]
+	payout_address: String,
+
+	/// Recovery psbt file, imported if it exists, otherwise exported
+	#[arg(
+		long,
+		env = "RECOVERY_PSBT_FILE",
+		default_value = "escrow_recovery.psbt"
+	)]
+	recovery_psbt_file: String,
+
+	/// Payout address of the other trader (only needed to start a two party recovery)
+	#[arg(
+		long,
+		env = "COUNTERPARTY_PAYOUT_ADDRESS",
+		requires = "counterparty_payout_amount_sat"
+	)]
+	counterparty_payout_address: Option<String>,
+
+	/// Amount paid to the other trader in sat (only needed to start a two party recovery)
+	#[arg(
+		long,
+		env = "COUNTERPARTY_PAYOUT_AMOUNT_SAT",
+		requires = "counterparty_payout_address"
+	)]
+	counterparty_payout_amount_sat: Option<u64>,
+
+	/// Amount in sat the other trader agreed to pay us (only needed to sign an imported two party recovery psbt)
+	#[arg(long, env = "EXPECTED_PAYOUT_AMOUNT_SAT")]
+	expected_payout_amount_sat: Option<u64>,
+
+	/// Bitcoin network (bitcoin, testnet, signet or regtest) the escrow output is on
+	#[arg(long, env = "BITCOIN_NETWORK", default_value = "regtest", value_parser = Network::from_str)]
+	network: Network,
 }
 
-fn hash256(input: &String) -> [u8; 32] {
-	let mut hasher = Sha256::new();
-	hasher.update(input.as_bytes());
-	hasher.finalize().into()
+#[derive(Args, Debug)]
+struct CancellationArgs {
+	/// Coordinator endpoint, e.g. http://127.0.0.1:9999
+	#[arg(long, env = "COORDINATOR_ENDPOINT")]
+	coordinator_endpoint: String,
+
+	/// Robohash of the maker (hex)
+	#[arg(long, env = "ROBOHASH_HEX")]
+	robohash_hex: String,
+
+	/// Wallet xprv used to create the offer, signs the cancellation
+	#[arg(long, env = "XPRV")]
+	xprv: String,
+
+	/// Id of the offer to cancel (hex)
+	#[arg(long)]
+	offer_id_hex: String,
+
+	/// Bitcoin network (bitcoin, testnet, signet or regtest) of the wallet xprv
+	#[arg(long, env = "BITCOIN_NETWORK", default_value = "regtest", value_parser = Network::from_str)]
+	network: Network,
 }
 
 impl OfferType {
@@ -57,188 +226,161 @@ impl OfferType {
 	}
 }
 
-impl CliSettings {
-	fn get_user_input(prompt: &str) -> String {
-		let mut buffer = String::new();
-		print!("{}", prompt);
-		io::stdout().flush().unwrap();
-		io::stdin()
-			.read_line(&mut buffer)
-			.expect("Failed to read line!");
-		buffer.trim().to_string()
-	}
+impl TryFrom<TraderArgs> for TraderSettings {
+	type Error = anyhow::Error;
 
-	fn get_trade_type(trade_type: Option<String>) -> OfferType {
-		let trade_type = match trade_type {
-			Some(value) => value,
-			None => Self::get_user_input("Do you want to buy or sell satoshis: "),
+	fn try_from(args: TraderArgs) -> Result<Self> {
+		let trade_type = match args.trade_type {
+			TradeType::Buy => OfferType::Buy(args.amount_sat),
+			TradeType::Sell => OfferType::Sell(args.amount_sat),
 		};
-		match trade_type.as_str() {
-			"buy" => OfferType::Buy(
-				Self::get_user_input("How many satoshi do you want to buy: ")
-					.parse()
-					.unwrap(),
-			),
-			"sell" => OfferType::Sell(
-				Self::get_user_input("How many satoshi do you want to sell: ")
-					.parse()
-					.unwrap(),
-			),
-			_ => panic!("Wrong offer type, you can only buy or sell"),
-		}
+		Ok(TraderSettings {
+			electrum_endpoint: args.electrum_endpoint,
+			coordinator_endpoint: args.coordinator_endpoint,
+			robosats_robohash_hex: args.robohash_hex,
+			trade_type,
+			payout_address: CliSettings::check_address_input(args.payout_address, args.network)?,
+			bond_ratio: args.bond_ratio,
+			wallet_xprv: CliSettings::check_xprv_input(args.xprv, args.network)?,
+			duration_unix_ts: CliSettings::hours_to_ts(args.offer_duration_hours)?,
+			network: args.network,
+		})
 	}
+}
+
+impl TryFrom<RecoveryArgs> for RecoverySettings {
+	type Error = anyhow::Error;
 
-	// parses the hours input string and returns the unix timestamp + the trade duration in seconds
-	fn hours_to_ts(hours: &str) -> Result<u64> {
-		let duration: u64 = hours.parse()?;
-		Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + duration * 3600)
+	fn try_from(args: RecoveryArgs) -> Result<Self> {
+		Ok(RecoverySettings {
+			electrum_endpoint: args.electrum_endpoint,
+			escrow_output_descriptor: args.escrow_output_descriptor,
+			wallet_xprv: get_wallet_xprv(Some(args.xprv), args.network).context("Invalid xprv")?,
+			payout_address: CliSettings::check_address_input(args.payout_address, args.network)?,
+			recovery_psbt_file: args.recovery_psbt_file,
+			counterparty_payout_address: args
+				.counterparty_payout_address
+				.map(|address| CliSettings::check_address_input(address, args.network))
+				.transpose()?,
+			counterparty_payout_amount_sat: args.counterparty_payout_amount_sat,
+			expected_payout_amount_sat: args.expected_payout_amount_sat,
+			network: args.network,
+		})
 	}
+}
 
-	fn get_trader_settings() -> Result<TraderSettings> {
-		let electrum_endpoint = Self::get_user_input("Enter electrum endpoint: ");
-		let coordinator_endpoint = Self::get_user_input("Enter coordinator endpoint: ");
-		let robosats_robohash_hex = hex::encode(hash256(&Self::get_user_input(
-			"Enter your robosats robot key: ", // just for testing purposes, to be improved to the real robohash spec
-		)));
-		let trade_type: OfferType = Self::get_trade_type(None);
-		let payout_address = Self::get_user_input(
-			"Enter a payout address for refunded bonds or your trade payout: ",
-		); // bdk can be used for validation
-		let bond_ratio: u8 = Self::get_user_input("Enter bond ration in [2, 50]%: ").parse()?;
-		let wallet_xprv = Self::check_xprv_input(Some(Self::get_user_input(
-			"Enter funded testnet wallet xprv or leave empty to generate: ",
-		)))?;
-		let duration_unix_ts: u64 = Self::hours_to_ts(&Self::get_user_input(
-			"How many hours should the offer stay online: ",
-		))?;
-		Ok(TraderSettings {
-			electrum_endpoint,
-			coordinator_endpoint,
-			robosats_robohash_hex,
-			trade_type,
-			payout_address,
-			bond_ratio,
-			wallet_xprv,
-			duration_unix_ts,
+impl TryFrom<CancellationArgs> for CancellationSettings {
+	type Error = anyhow::Error;
+
+	fn try_from(args: CancellationArgs) -> Result<Self> {
+		Ok(CancellationSettings {
+			coordinator_endpoint: args.coordinator_endpoint,
+			robosats_robohash_hex: args.robohash_hex,
+			wallet_xprv: get_wallet_xprv(Some(args.xprv), args.network).context("Invalid xprv")?,
+			offer_id_hex: args.offer_id_hex,
+			network: args.network,
 		})
 	}
+}
 
-	fn check_xprv_input(cli_input: Option<String>) -> Result<ExtendedPrivKey> {
+impl CliSettings {
+	// returns the unix timestamp + the trade duration in seconds
+	fn hours_to_ts(hours: u64) -> Result<u64> {
+		Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + hours * 3600)
+	}
+
+	fn check_xprv_input(cli_input: Option<String>, network: Network) -> Result<ExtendedPrivKey> {
 		if let Some(user_input) = cli_input {
 			if !(user_input.is_empty()) {
-				return get_wallet_xprv(Some(user_input));
+				return get_wallet_xprv(Some(user_input), network).context("Invalid xprv");
 			}
 		};
-		get_wallet_xprv(None)
+		get_wallet_xprv(None, network)
 	}
 
-	fn load_from_env(filename: &str) -> Result<TraderSettings> {
-		dotenvy::from_filename(filename)?;
-		Ok(TraderSettings {
-			electrum_endpoint: env::var("ELECTRUM_ENDPOINT")?,
-			coordinator_endpoint: env::var("COORDINATOR_ENDPOINT")?,
-			robosats_robohash_hex: env::var("ROBOHASH_HEX")?,
-			trade_type: Self::get_trade_type(Some(env::var("TRADE_TYPE")?)),
-			payout_address: env::var("PAYOUT_ADDRESS")?,
-			bond_ratio: env::var("BOND_RATIO")?.parse()?,
-			wallet_xprv: Self::check_xprv_input(Some(env::var("XPRV")?))?,
-			duration_unix_ts: Self::hours_to_ts(&env::var("OFFER_DURATION_HOURS")?)?,
-		})
+	// addresses passed by the user have to be valid for the configured network
+	fn check_address_input(address: String, network: Network) -> Result<String> {
+		Address::from_str(&address)?
+			.require_network(network)
+			.with_context(|| format!("Address {} is not valid for {}", address, network))?;
+		Ok(address)
 	}
 
-	fn parse_trader_settings(maybe_filename: &str) -> Result<TraderSettings> {
-		match Self::get_user_input("Load from .env (y/N): ").trim() {
-			"y" => Self::load_from_env(maybe_filename),
-			"N" => Self::get_trader_settings(),
-			_ => Err(anyhow!("Not a valid input!")),
+	// the env file has to be loaded before parsing so clap can fall back to its variables
+	// for arguments not passed as flag
+	fn load_env_file() -> Result<()> {
+		let matches = Cli::command().ignore_errors(true).get_matches();
+		if let Some(env_file) = matches.get_one::<String>("env_file") {
+			dotenvy::from_filename(env_file)
+				.with_context(|| format!("Failed to load env file {}", env_file))?;
 		}
+		Ok(())
 	}
 
 	pub fn parse_cli_args() -> Result<Self> {
-		let mode = Self::get_user_input("Enter mode, 'taker' or 'maker': ");
+		Self::load_env_file()?;
 
-		match mode.to_lowercase().as_str() {
-			"maker" => Ok(Self::Maker(Self::parse_trader_settings("maker.env")?)),
-			"taker" => Ok(Self::Taker(Self::parse_trader_settings("taker.env")?)),
-			_ => Err(anyhow!("Either select maker or taker!")),
+		match Cli::parse().mode {
+			Mode::Maker(args) => Ok(Self::Maker(args.try_into()?)),
+			Mode::Taker(args) => Ok(Self::Taker(args.try_into()?)),
+			Mode::Recover(args) => Ok(Self::Recover(args.try_into()?)),
+			Mode::Cancel(args) => Ok(Self::Cancel(args.try_into()?)),
 		}
 	}
 }
 
-// old cli parser using clap
-
-// use clap::{command, Arg, Command, ArgMatches};
-
-// trait ArgMatchesParser {
-// 	fn parse_into_enum(&self) -> CliSettings;
-// }
-
-// impl ArgMatchesParser for ArgMatches {
-// 	fn parse_into_enum(&self) -> CliSettings {
-// 		if let Some(_mode) = self.subcommand_matches("coordinator") {
-// 			CliSettings::Coordinator(Coordinator { })
-// 		} else if let Some(mode) = self.subcommand_matches("trader") {
-// 			let trader_settings = TraderSettings {
-// 				coordinator_endpoint: mode.get_one::<String>("coordinator-ep")
-// 										.expect("Coordinator endpoint not provided!").clone(),
-// 				electrum_endpoint: mode.get_one::<String>("electrum-ep")
-// 										.expect("Electrum endpoint not provided").clone()
-// 			};
-// 			if mode.contains_id("maker") {
-// 				CliSettings::Maker( trader_settings )
-// 			} else if mode.contains_id("taker") {
-// 				CliSettings::Taker( trader_settings )
-// 			} else {
-// 				panic!("Wrong arguments for Trader mode!")
-// 			}
-// 		} else {
-// 			panic!("Select either coordinator or trader mode!")
-// 		}
-// 	}
-// }
-
-// pub fn parse_cli_args() -> CliSettings {
-// 	command!()
-// 	.about("RoboSats taproot onchain trade pipeline CLI demonstrator. Don't use with real funds.")
-// 	.subcommand(
-// 		Command::new("coordinator")
-// 				.about("Run in coordinator mode.")
-// 	)
-// 	.subcommand(
-// 	Command::new("trader")
-// 		.about("Two available trader modes: Maker and Taker. Select one and provide Coordinator and Electum endpoint")
-// 		.arg(
-// 			Arg::new("taker")
-// 				.short('t')
-// 				.long("taker")
-// 				.help("Run program as taker")
-// 				.num_args(0)
-// 				.conflicts_with("maker")
-// 		)
-// 		.arg (
-// 			Arg::new("maker")
-// 				.short('m')
-// 				.long("maker")
-// 				.num_args(0)
-// 				.help("Run program as maker")
-// 				.conflicts_with("taker")
-// 		)
-// 		.arg(
-// 			Arg::new("coordinator-ep")
-// 					.short('p')
-// 					.long("endpoint")
-// 					.required(true)
-// 					.help("Communication endpoint of the coordinator to connect to")
-// 		)
-// 		.arg(
-// 			Arg::new("electrum-ep")
-// 					.short('e')
-// 					.long("electrum")
-// 					.required(true)
-// 					.help("URL of the electrum endpoint")
-// 		)
-// 	)
-// 	.arg_required_else_help(true)
-// 	.get_matches()
-// 	.parse_into_enum()
-// }
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashMap;
+
+	// turns the variables of a shipped env file, including the commented out optional ones,
+	// into the command line flags of the subcommand
+	fn env_file_args(subcommand: &str, env_file: &str) -> Vec<String> {
+		let uncommented: Vec<&str> = env_file
+			.lines()
+			.map(|line| match line.strip_prefix("# ") {
+				Some(assignment)
+					if assignment.split_once('=').is_some_and(|(key, _)| {
+						key.chars().all(|c| c.is_ascii_uppercase() || c == '_')
+					}) =>
+				{
+					assignment
+				}
+				_ => line,
+			})
+			.collect();
+		let vars: HashMap<String, String> =
+			dotenvy::from_read_iter(uncommented.join("\n").as_bytes())
+				.collect::<Result<_, _>>()
+				.unwrap();
+
+		let mut args = vec!["trader".to_string(), subcommand.to_string()];
+		let command = Cli::command();
+		for arg in command.find_subcommand(subcommand).unwrap().get_arguments() {
+			if let (Some(env), Some(long)) = (arg.get_env(), arg.get_long()) {
+				if let Some(value) = vars.get(env.to_str().unwrap()) {
+					args.push(format!("--{}={}", long, value));
+				}
+			}
+		}
+		args
+	}
+
+	#[test]
+	fn test_shipped_env_files() {
+		for (subcommand, env_file) in [
+			("maker", include_str!("../../maker.env")),
+			("taker", include_str!("../../taker.env")),
+			("recover", include_str!("../../recover.env")),
+		] {
+			let cli = Cli::try_parse_from(env_file_args(subcommand, env_file)).unwrap();
+			let settings = match cli.mode {
+				Mode::Maker(args) | Mode::Taker(args) => TraderSettings::try_from(args).map(|_| ()),
+				Mode::Recover(args) => RecoverySettings::try_from(args).map(|_| ()),
+				Mode::Cancel(_) => unreachable!(),
+			};
+			assert!(settings.is_ok(), "{}.env: {:?}", subcommand, settings);
+		}
+	}
+}
@@ -1,329 +1,478 @@
-pub mod api;
 pub mod taker_requests;
+pub mod trade_events;
 
 use super::*;
 use crate::{
-	cli::{OfferType, TraderSettings},
+	cli::{CancellationSettings, OfferType, TraderSettings},
 	trading::utils::ActiveOffer,
-	wallet::{bond::Bond, musig2_utils::MuSigData},
 };
 use anyhow::{anyhow, Result};
-use api::*;
-use bdk::bitcoin::{consensus::encode::serialize_hex, key::XOnlyPublicKey};
-use bdk::{
-	bitcoin::{consensus::Encodable, psbt::PartiallySignedTransaction},
-	wallet::AddressInfo,
-};
+use bdk::bitcoin::{key::Secp256k1, psbt::PartiallySignedTransaction, secp256k1::Message, Network};
 use musig2::{AggNonce, KeyAggContext};
-use serde::{Deserialize, Serialize};
-use std::{f32::consts::E, str::FromStr, thread::sleep, time::Duration};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+	str::FromStr,
+	thread::sleep,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use taptrade_protocol::{endpoints, *};
+use trade_events::TradeEventStream;
 
-impl BondRequirementResponse {
-	fn _format_order_request(trader_setup: &TraderSettings) -> OrderRequest {
-		let amount: u64;
-		let is_buy_order = match &trader_setup.trade_type {
-			OfferType::Buy(val) => {
-				amount = *val;
-				true
-			}
-			OfferType::Sell(val) => {
-				amount = *val;
-				false
-			}
-		};
+/// the message signed with our taproot key to authenticate a request, has to match the coordinator
+fn request_signature_message(path: &str, timestamp: u64, body: &[u8]) -> Result<Message> {
+	let mut hasher = Sha256::new();
+	hasher.update(request_signature_preimage(path, timestamp, body));
+	Ok(Message::from_slice(&hasher.finalize())?)
+}
+
+/// http client builder for coordinator requests, every request carries our network in the NETWORK_HEADER
+/// so the coordinator refuses to talk to us if it runs on another network
+pub fn coordinator_client_builder(network: Network) -> Result<reqwest::blocking::ClientBuilder> {
+	let mut headers = reqwest::header::HeaderMap::new();
+	headers.insert(
+		NETWORK_HEADER,
+		reqwest::header::HeaderValue::from_str(&network.to_string())?,
+	);
+	Ok(reqwest::blocking::Client::builder().default_headers(headers))
+}
 
-		OrderRequest {
-			robohash_hex: trader_setup.robosats_robohash_hex.clone(),
-			amount_satoshi: amount,
-			is_buy_order,
-			bond_ratio: trader_setup.bond_ratio,
-			offer_duration_ts: trader_setup.duration_unix_ts,
+fn coordinator_client(network: Network) -> Result<reqwest::blocking::Client> {
+	Ok(coordinator_client_builder(network)?.build()?)
+}
+
+/// posts the json request to the coordinator endpoint path, signed with the taproot key of our wallet
+fn post_signed<T: Serialize>(
+	client: &reqwest::blocking::Client,
+	trader_config: &TraderSettings,
+	path: &str,
+	request: &T,
+) -> Result<reqwest::blocking::Response> {
+	let body = serde_json::to_vec(request)?;
+	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+	let secp = Secp256k1::new();
+	let signature = secp.sign_schnorr(
+		&request_signature_message(path, timestamp, &body)?,
+		&trader_config.wallet_xprv.to_keypair(&secp),
+	);
+	Ok(client
+		.post(format!("{}{}", trader_config.coordinator_endpoint, path))
+		.header(reqwest::header::CONTENT_TYPE, "application/json")
+		.header(SIGNATURE_HEADER, signature.to_string())
+		.header(TIMESTAMP_HEADER, timestamp.to_string())
+		.body(body)
+		.send()?)
+}
+
+/// the coordinator returns its protocol version with every response, we refuse to start
+/// a trade with a coordinator speaking another version
+fn check_protocol_version(res: &reqwest::blocking::Response) -> Result<()> {
+	match res
+		.headers()
+		.get(PROTOCOL_VERSION_HEADER)
+		.and_then(|version| version.to_str().ok())
+	{
+		Some(version) if version == PROTOCOL_VERSION.to_string() => Ok(()),
+		Some(version) => Err(anyhow!(
+			"Coordinator speaks protocol version {}, we speak version {}",
+			version,
+			PROTOCOL_VERSION
+		)),
+		None => {
+			warn!("Coordinator did not return its protocol version");
+			Ok(())
 		}
 	}
+}
+
+/// decodes the error body of a failed coordinator request. Responses without json body
+/// (e.g. rejections of axum itself) are returned with the Unknown code and the plain text.
+pub fn decode_error_response(res: reqwest::blocking::Response) -> ErrorResponse {
+	let status = res.status();
+	let body = res.text().unwrap_or_default();
+	serde_json::from_str(&body).unwrap_or_else(|_| ErrorResponse {
+		code: ErrorCode::Unknown,
+		message: format!("{} {}", status, body),
+		retryable: status.is_server_error(),
+	})
+}
 
-	pub fn fetch(trader_setup: &TraderSettings) -> Result<BondRequirementResponse> {
-		trace!("Fetching bond requirements from coordinator. (create-offer)");
-		let client = reqwest::blocking::Client::new();
-		let endpoint = format!("{}{}", trader_setup.coordinator_endpoint, "/create-offer");
-		// requests an offer from the coordinator according to the .env/cli input stored in trader_setup
-		let res = match client
-			.post(endpoint)
-			.json(&Self::_format_order_request(trader_setup))
-			.send()
-		{
-			Ok(res) => res,
-			Err(e) => return Err(anyhow!("Error calling /create-offer: {}", e)),
-		};
-		let status_code = res.status();
-		debug!("/create-offer Response status code: {}", status_code);
-		match res.json::<BondRequirementResponse>() {
-			Ok(response) => Ok(response),
-			Err(e) => Err(anyhow!(
-				"Error fetching bond requirements: {}. Status code: {}",
-				e,
-				status_code
-			)),
+/// turns a failed coordinator response into an error, the ErrorResponse can be recovered
+/// with downcast_ref to check the code
+fn coordinator_error(res: reqwest::blocking::Response, context: &str) -> anyhow::Error {
+	anyhow::Error::new(decode_error_response(res)).context(context.to_string())
+}
+
+/// result of polling the coordinator for the final payout of a trade
+pub enum PayoutOutcome {
+	/// both traders are satisfied, the keyspend payout has to be signed with musig
+	Keyspend(PartiallySignedTransaction, AggNonce, Box<KeyAggContext>),
+	/// the escrow case was decided in our favour, the coordinator signed script payout psbt is returned
+	ScriptPath(PartiallySignedTransaction),
+	/// the escrow case was decided in favour of the other trader
+	LostEscrow,
+}
+
+fn offer_request(trader_setup: &TraderSettings) -> OfferRequest {
+	let amount: u64;
+	let is_buy_order = match &trader_setup.trade_type {
+		OfferType::Buy(val) => {
+			amount = *val;
+			true
 		}
+		OfferType::Sell(val) => {
+			amount = *val;
+			false
+		}
+	};
+
+	OfferRequest {
+		robohash_hex: trader_setup.robosats_robohash_hex.clone(),
+		amount_satoshi: amount,
+		is_buy_order,
+		bond_ratio: trader_setup.bond_ratio,
+		offer_duration_ts: trader_setup.duration_unix_ts,
+		network: trader_setup.network.to_string(),
 	}
 }
 
-impl BondSubmissionRequest {
-	/// sending the signed maker bond and additional trade data to the coordinator
-	pub fn send_maker(&self, trader_setup: &TraderSettings) -> Result<OrderActivatedResponse> {
-		let client = reqwest::blocking::Client::new();
-		let res = client
-			.post(format!(
-				"{}{}",
-				trader_setup.coordinator_endpoint, "/submit-maker-bond"
-			))
-			.json(self)
-			.send();
-		match res {
-			Ok(res) => {
-				let status_code = res.status();
-				match res.json::<OrderActivatedResponse>() {
-					Ok(response) => Ok(response),
-					Err(e) => Err(anyhow!(
-						"Error submitting maker bond: {}. Status code: {}",
-						e,
-						status_code
-					)),
-				}
+/// requests an offer from the coordinator according to the .env/cli input stored in trader_setup,
+/// returns the bond requirements of the offer
+pub fn fetch_bond_requirements(trader_setup: &TraderSettings) -> Result<BondRequirementResponse> {
+	trace!("Fetching bond requirements from coordinator. (create-offer)");
+	let client = coordinator_client(trader_setup.network)?;
+	let endpoint = format!(
+		"{}{}",
+		trader_setup.coordinator_endpoint,
+		endpoints::CREATE_OFFER
+	);
+	let res = match client
+		.post(endpoint)
+		.json(&offer_request(trader_setup))
+		.send()
+	{
+		Ok(res) => res,
+		Err(e) => return Err(anyhow!("Error calling /create-offer: {}", e)),
+	};
+	let status_code = res.status();
+	debug!("/create-offer Response status code: {}", status_code);
+	check_protocol_version(&res)?;
+	// e.g. the coordinator refuses to talk to us if we are on another network
+	if !status_code.is_success() {
+		return Err(coordinator_error(res, "Coordinator rejected the offer"));
+	}
+	match res.json::<BondRequirementResponse>() {
+		Ok(response) => Ok(response),
+		Err(e) => Err(anyhow!(
+			"Error fetching bond requirements: {}. Status code: {}",
+			e,
+			status_code
+		)),
+	}
+}
+
+/// sending the signed maker bond and additional trade data to the coordinator
+pub fn submit_maker_bond(
+	request: &BondSubmissionRequest,
+	trader_setup: &TraderSettings,
+) -> Result<OfferActivatedResponse> {
+	let client = coordinator_client(trader_setup.network)?;
+	let res = client
+		.post(format!(
+			"{}{}",
+			trader_setup.coordinator_endpoint,
+			endpoints::SUBMIT_MAKER_BOND
+		))
+		.json(request)
+		.send();
+	match res {
+		Ok(res) if !res.status().is_success() => Err(coordinator_error(
+			res,
+			"Coordinator rejected the maker bond",
+		)),
+		Ok(res) => {
+			let status_code = res.status();
+			match res.json::<OfferActivatedResponse>() {
+				Ok(response) => Ok(response),
+				Err(e) => Err(anyhow!(
+					"Error submitting maker bond: {}. Status code: {}",
+					e,
+					status_code
+				)),
 			}
-			Err(e) => Err(anyhow!("Error submitting maker bond: {}", e)),
 		}
+		Err(e) => Err(anyhow!("Error submitting maker bond: {}", e)),
 	}
 }
 
-impl OfferTakenResponse {
-	// posts offer to coordinator to check if it has been taken, if not taken
-	// returns status code 204 No Content
-	pub fn check(
-		offer: &ActiveOffer,
-		trader_setup: &TraderSettings,
-	) -> Result<Option<OfferTakenResponse>> {
-		trace!("Polling offer status from coordinator.");
-		let request = OfferTakenRequest {
-			robohash_hex: trader_setup.robosats_robohash_hex.clone(),
-			offer_id_hex: offer.offer_id_hex.clone(),
-		};
-		let client = reqwest::blocking::Client::new();
-		let res = client
-			.post(format!(
-				"{}{}",
-				trader_setup.coordinator_endpoint, "/request-offer-status"
-			))
-			.json(&request)
-			.send()?;
-		if res.status() == 200 {
-			Ok(Some(res.json::<OfferTakenResponse>()?))
-		} else if res.status() == 204 {
-			Ok(None)
-		} else {
-			Err(anyhow!("Offer status polling returned: {}", res.status()))
-		}
+// posts offer to coordinator to check if it has been taken, if not taken
+// returns status code 204 No Content
+pub fn check_offer_status(
+	offer: &ActiveOffer,
+	trader_setup: &TraderSettings,
+) -> Result<Option<OfferTakenResponse>> {
+	trace!("Polling offer status from coordinator.");
+	let request = OfferTakenRequest {
+		robohash_hex: trader_setup.robosats_robohash_hex.clone(),
+		offer_id_hex: offer.offer_id_hex.clone(),
+	};
+	let client = coordinator_client(trader_setup.network)?;
+	let res = post_signed(
+		&client,
+		trader_setup,
+		endpoints::REQUEST_OFFER_STATUS,
+		&request,
+	)?;
+	if res.status() == 200 {
+		Ok(Some(res.json::<OfferTakenResponse>()?))
+	} else if res.status() == 204 {
+		Ok(None)
+	} else {
+		Err(coordinator_error(res, "Offer status polling failed"))
 	}
 }
 
-impl PsbtSubmissionRequest {
-	// submits the signed escrow psbt to the coordinator
-	pub fn submit_escrow_psbt(
-		psbt: &PartiallySignedTransaction,
-		offer_id_hex: String,
-		taker_config: &TraderSettings,
-	) -> Result<()> {
-		let request = PsbtSubmissionRequest {
-			signed_psbt_hex: psbt.serialize_hex(),
-			offer_id_hex,
-			robohash_hex: taker_config.robosats_robohash_hex.clone(),
-		};
-		let client = reqwest::blocking::Client::new();
-		let res = client
-			.post(format!(
-				"{}{}",
-				taker_config.coordinator_endpoint, "/submit-escrow-psbt"
-			))
-			.json(&request)
-			.send()?;
-		if res.status() != 200 {
-			return Err(anyhow!(
-				"Submitting escrow psbt failed. Status: {}",
-				res.status()
-			));
-		}
-		Ok(())
+// submits the signed escrow psbt to the coordinator
+pub fn submit_escrow_psbt(
+	psbt: &PartiallySignedTransaction,
+	offer_id_hex: String,
+	taker_config: &TraderSettings,
+) -> Result<()> {
+	let request = PsbtSubmissionRequest {
+		signed_psbt_hex: psbt.serialize_hex(),
+		offer_id_hex,
+		robohash_hex: taker_config.robosats_robohash_hex.clone(),
+	};
+	let client = coordinator_client(taker_config.network)?;
+	let res = post_signed(
+		&client,
+		taker_config,
+		endpoints::SUBMIT_ESCROW_PSBT,
+		&request,
+	)?;
+	if res.status() != 200 {
+		return Err(coordinator_error(res, "Submitting escrow psbt failed"));
 	}
+	Ok(())
 }
 
-impl TradeObligationsSatisfied {
-	// if the trader is satisfied he can submit this to signal the coordinator readiness to close the trade
-	// if the other party also submits this the coordinator can initiate the closing transaction, otherwise
-	// escrow has to be initiated
-	pub fn submit(offer_id_hex: &str, trader_config: &TraderSettings) -> Result<()> {
-		let request = TradeObligationsSatisfied {
-			robohash_hex: trader_config.robosats_robohash_hex.clone(),
-			offer_id_hex: offer_id_hex.to_string(),
-		};
+// if the trader is satisfied he can submit this to signal the coordinator readiness to close the trade
+// if the other party also submits this the coordinator can initiate the closing transaction, otherwise
+// escrow has to be initiated
+pub fn submit_obligation_confirmation(
+	offer_id_hex: &str,
+	trader_config: &TraderSettings,
+) -> Result<()> {
+	let request = OfferTakenRequest {
+		robohash_hex: trader_config.robosats_robohash_hex.clone(),
+		offer_id_hex: offer_id_hex.to_string(),
+	};
 
-		let client = reqwest::blocking::Client::new();
-		let res = client
-			.post(format!(
-				"{}{}",
-				trader_config.coordinator_endpoint, "/submit-obligation-confirmation"
-			))
-			.json(&request)
-			.send()?;
-		if res.status() != 200 {
-			return Err(anyhow!(
-				"Submitting trade obligations confirmation failed. Status: {}",
-				res.status()
-			));
-		}
-		Ok(())
+	let client = coordinator_client(trader_config.network)?;
+	let res = post_signed(
+		&client,
+		trader_config,
+		endpoints::SUBMIT_OBLIGATION_CONFIRMATION,
+		&request,
+	)?;
+	if res.status() != 200 {
+		return Err(coordinator_error(
+			res,
+			"Submitting trade obligations confirmation failed",
+		));
 	}
+	Ok(())
 }
 
-impl IsOfferReadyRequest {
-	/// polls until the escrow locking transaction is signaled as confirmed by the coordinator. This could also be implemented client side in theory
-	pub fn poll(taker_config: &TraderSettings, offer: &ActiveOffer) -> Result<()> {
-		let request = IsOfferReadyRequest {
-			robohash_hex: taker_config.robosats_robohash_hex.clone(),
-			offer_id_hex: offer.offer_id_hex.clone(),
-		};
-		let client = reqwest::blocking::Client::new();
-		loop {
-			let res = client
-				.post(format!(
-					"{}{}",
-					taker_config.coordinator_endpoint, "/poll-escrow-confirmation"
-				))
-				.json(&request)
-				.send()?;
-			if res.status() == 200 {
-				return Ok(());
-			} else if res.status() != 202 {
-				return Err(anyhow!(
-					"Requesting offer status when waiting on other party failed: {}",
-					res.status()
-				));
+/// polls until the escrow locking transaction is signaled as confirmed by the coordinator. This could also be implemented client side in theory
+pub fn poll_escrow_confirmation(taker_config: &TraderSettings, offer: &ActiveOffer) -> Result<()> {
+	let request = OfferTakenRequest {
+		robohash_hex: taker_config.robosats_robohash_hex.clone(),
+		offer_id_hex: offer.offer_id_hex.clone(),
+	};
+	let client = coordinator_client(taker_config.network)?;
+	let mut trade_events = TradeEventStream::subscribe(taker_config, &offer.offer_id_hex);
+	loop {
+		let res = post_signed(
+			&client,
+			taker_config,
+			endpoints::POLL_ESCROW_CONFIRMATION,
+			&request,
+		)?;
+		if res.status() == 200 {
+			return Ok(());
+		} else if res.status() != 202 {
+			// e.g. the trade got aborted because a bond violation was detected
+			let error = decode_error_response(res);
+			if !error.retryable {
+				return Err(anyhow::Error::new(error)
+					.context("Requesting offer status when waiting on other party failed"));
 			}
-			// Sleep for 2 sec and poll again
-			sleep(Duration::from_secs(2));
+			warn!("Temporary coordinator error, retrying: {}", error);
 		}
+		// wait for the next trade event (or the poll interval) and poll again
+		trade_events.wait();
 	}
+}
 
-	/// polls until the other trader also confirmed happiness, then the payout data required to
-	/// create the partial signature for the keyspend payout is returned
-	pub fn poll_payout(
-		trader_config: &TraderSettings,
-		offer: &ActiveOffer,
-	) -> Result<(PartiallySignedTransaction, AggNonce, KeyAggContext)> {
-		let request = IsOfferReadyRequest {
-			robohash_hex: trader_config.robosats_robohash_hex.clone(),
-			offer_id_hex: offer.offer_id_hex.clone(),
-		};
-		let client = reqwest::blocking::Client::new();
-		let mut res: reqwest::blocking::Response;
-
-		loop {
-			// Sleep for 2 sec and poll
-			sleep(Duration::from_secs(2));
+/// polls until the other trader also confirmed happiness or the coordinator decided an escrow case,
+/// then the payout data required to finish the trade is returned
+pub fn poll_final_payout(
+	trader_config: &TraderSettings,
+	offer: &ActiveOffer,
+) -> Result<PayoutOutcome> {
+	let request = OfferTakenRequest {
+		robohash_hex: trader_config.robosats_robohash_hex.clone(),
+		offer_id_hex: offer.offer_id_hex.clone(),
+	};
+	let client = coordinator_client(trader_config.network)?;
+	let mut trade_events = TradeEventStream::subscribe(trader_config, &offer.offer_id_hex);
+	let mut res: reqwest::blocking::Response;
 
-			res = client
-				.post(format!(
-					"{}{}",
-					trader_config.coordinator_endpoint, "/poll-final-payout"
-				))
-				.json(&request)
-				.send()?;
-			debug!(
-				"Polling for final payout... Response status: {}",
-				res.status()
-			);
-			if res.status() == 200 {
-				// good case, psbt is returned
-				debug!("Payout psbt received. Signing...");
-				break;
-			} else if res.status() == 202 {
-				// still waiting, retry
-				continue;
-			} else if res.status() == 201 {
-				// Escrow ongoing
-				debug!("Escrow ongoing, awaiting coordinator decision. Waiting for coordinator to finalize.");
-				continue;
-			} else if res.status() != 410 {
-				return Err(anyhow!(
-					"We lost the escrow, your bond is gone: {}",
-					res.status()
-				));
-			} else {
-				// unintended response
-				return Err(anyhow!(
-					"Requesting final payout when waiting on other party failed: {}",
-					res.status()
-				));
+	loop {
+		res = post_signed(
+			&client,
+			trader_config,
+			endpoints::POLL_FINAL_PAYOUT,
+			&request,
+		)?;
+		debug!(
+			"Polling for final payout... Response status: {}",
+			res.status()
+		);
+		if res.status() == 200 {
+			// good case, psbt is returned
+			debug!("Payout psbt received. Signing...");
+			break;
+		} else if res.status() == 202 {
+			// still waiting, retry on the next trade event
+			trade_events.wait();
+			continue;
+		} else if res.status() == 201 {
+			// Escrow ongoing
+			debug!("Escrow ongoing, awaiting coordinator decision. Waiting for coordinator to finalize.");
+			trade_events.wait();
+			continue;
+		} else {
+			let error = decode_error_response(res);
+			match error.code {
+				// the coordinator decided the escrow case in favour of the other trader
+				ErrorCode::EscrowLost => return Ok(PayoutOutcome::LostEscrow),
+				_ if error.retryable => {
+					warn!("Temporary coordinator error, retrying: {}", error);
+					trade_events.wait();
+					continue;
+				}
+				_ => {
+					return Err(anyhow::Error::new(error)
+						.context("Requesting final payout when waiting on other party failed"))
+				}
 			}
 		}
-		let payout_response: PayoutResponse = res.json()?;
-		let final_psbt = PartiallySignedTransaction::deserialize(&hex::decode(
-			&payout_response.payout_psbt_hex,
-		)?)?;
-		let agg_nonce = AggNonce::from_str(&payout_response.agg_musig_nonce_hex)
-			.map_err(|e| anyhow!("Error parsing agg nonce: {}", e))?;
-		let agg_pubk_ctx = KeyAggContext::from_hex(&payout_response.agg_musig_pubkey_ctx_hex)
-			.map_err(|e| anyhow!("Error parsing agg pubkey ctx: {}", e))?;
-		Ok((final_psbt, agg_nonce, agg_pubk_ctx))
+	}
+	match res.json::<PayoutPollResponse>()? {
+		PayoutPollResponse::Keyspend(payout_response) => {
+			let final_psbt = PartiallySignedTransaction::deserialize(&hex::decode(
+				&payout_response.payout_psbt_hex,
+			)?)?;
+			let agg_nonce = AggNonce::from_str(&payout_response.agg_musig_nonce_hex)
+				.map_err(|e| anyhow!("Error parsing agg nonce: {}", e))?;
+			let agg_pubk_ctx = KeyAggContext::from_hex(&payout_response.agg_musig_pubkey_ctx_hex)
+				.map_err(|e| anyhow!("Error parsing agg pubkey ctx: {}", e))?;
+			Ok(PayoutOutcome::Keyspend(
+				final_psbt,
+				agg_nonce,
+				Box::new(agg_pubk_ctx),
+			))
+		}
+		PayoutPollResponse::ScriptPath(script_payout_response) => {
+			let script_payout_psbt = PartiallySignedTransaction::deserialize(&hex::decode(
+				&script_payout_response.script_payout_psbt_hex,
+			)?)?;
+			Ok(PayoutOutcome::ScriptPath(script_payout_psbt))
+		}
 	}
 }
 
-impl TradeObligationsUnsatisfied {
-	/// called to request escrow
-	pub fn request_escrow(offer_id_hex: &str, trader_config: &TraderSettings) -> Result<()> {
-		let request = TradeObligationsUnsatisfied {
-			robohash_hex: trader_config.robosats_robohash_hex.clone(),
-			offer_id_hex: offer_id_hex.to_string(),
-		};
+/// called to request escrow
+pub fn request_escrow(offer_id_hex: &str, trader_config: &TraderSettings) -> Result<()> {
+	let request = TradeObligationsUnsatisfied {
+		robohash_hex: trader_config.robosats_robohash_hex.clone(),
+		offer_id_hex: offer_id_hex.to_string(),
+	};
 
-		let client = reqwest::blocking::Client::new();
-		let res = client
-			.post(format!(
-				"{}{}",
-				trader_config.coordinator_endpoint, "/request-escrow"
-			))
-			.json(&request)
-			.send()?;
-		if res.status() != 200 {
-			return Err(anyhow!(
-				"Submitting trade obligations unsatisfied failed. Status: {}",
-				res.status()
-			));
-		}
-		Ok(())
+	let client = coordinator_client(trader_config.network)?;
+	let res = post_signed(&client, trader_config, endpoints::REQUEST_ESCROW, &request)?;
+	if res.status() != 200 {
+		return Err(coordinator_error(
+			res,
+			"Submitting trade obligations unsatisfied failed",
+		));
 	}
+	Ok(())
 }
 
-impl PayoutSignatureRequest {
-	pub fn send(trader_config: &TraderSettings, signature: &str, offer_id_hex: &str) -> Result<()> {
-		let request = Self {
-			robohash_hex: trader_config.robosats_robohash_hex.clone(),
-			offer_id_hex: offer_id_hex.to_string(),
-			partial_sig_hex: signature.to_string(),
-		};
+pub fn submit_payout_signature(
+	trader_config: &TraderSettings,
+	signature: &str,
+	offer_id_hex: &str,
+) -> Result<()> {
+	let request = PayoutSignatureRequest {
+		robohash_hex: trader_config.robosats_robohash_hex.clone(),
+		offer_id_hex: offer_id_hex.to_string(),
+		partial_sig_hex: signature.to_string(),
+	};
 
-		let client = reqwest::blocking::Client::new();
-		let res = client
-			.post(format!(
-				"{}{}",
-				trader_config.coordinator_endpoint, "/submit-payout-signature"
-			))
-			.json(&request)
-			.send()?;
-		if res.status() != 200 && res.status() != 202 {
-			return Err(anyhow!(
-				"Submitting trade obligations unsatisfied failed. Status: {}",
-				res.status()
-			));
-		}
-		Ok(())
+	let client = coordinator_client(trader_config.network)?;
+	let res = post_signed(
+		&client,
+		trader_config,
+		endpoints::SUBMIT_PAYOUT_SIGNATURE,
+		&request,
+	)?;
+	if res.status() != 200 && res.status() != 202 {
+		return Err(coordinator_error(res, "Submitting payout signature failed"));
+	}
+	Ok(())
+}
+
+/// the message signed with our taproot key to cancel an offer, has to match the coordinator
+fn offer_cancellation_message(offer_id_hex: &str) -> Result<Message> {
+	let mut hasher = Sha256::new();
+	hasher.update(offer_cancellation_preimage(offer_id_hex));
+	Ok(Message::from_slice(&hasher.finalize())?)
+}
+
+// withdraws our offer from the orderbook of the coordinator, the bond will not be monitored anymore
+pub fn send_offer_cancellation(cancellation_config: &CancellationSettings) -> Result<()> {
+	let keypair = cancellation_config
+		.wallet_xprv
+		.to_keypair(&Secp256k1::new());
+	let signature = Secp256k1::new().sign_schnorr(
+		&offer_cancellation_message(&cancellation_config.offer_id_hex)?,
+		&keypair,
+	);
+	let request = OfferCancellationRequest {
+		robohash_hex: cancellation_config.robosats_robohash_hex.clone(),
+		offer_id_hex: cancellation_config.offer_id_hex.clone(),
+		cancellation_signature_hex: signature.to_string(),
+	};
+
+	let client = coordinator_client(cancellation_config.network)?;
+	let res = client
+		.post(format!(
+			"{}{}",
+			cancellation_config.coordinator_endpoint,
+			endpoints::CANCEL_OFFER
+		))
+		.json(&request)
+		.send()?;
+	match res.status().as_u16() {
+		200 => Ok(()),
+		404 => Err(anyhow!(
+			"Offer {} not found in the orderbook, it may already be taken",
+			request.offer_id_hex
+		)),
+		_ => Err(coordinator_error(res, "Cancelling offer failed")),
 	}
 }
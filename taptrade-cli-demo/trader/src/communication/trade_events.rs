@@ -0,0 +1,106 @@
+// Subscription to the server sent events stream of a trade (endpoints::TRADE_EVENTS). The events only wake up the
+// polling loops so they fetch the new state right away, if the stream can't be opened or breaks the loops
+// fall back to polling every few seconds.
+use super::*;
+use std::{
+	io::{BufRead, BufReader},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		mpsc::{channel, Receiver, RecvTimeoutError},
+		Arc,
+	},
+};
+
+/// poll interval without event stream
+const FALLBACK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// with an open event stream we still poll occasionally in case an event got lost
+const EVENT_STREAM_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+pub struct TradeEventStream {
+	events: Option<Receiver<String>>,
+	// set once the stream is dropped so the reader thread exits and closes the connection
+	closed: Arc<AtomicBool>,
+}
+
+impl TradeEventStream {
+	/// subscribes to the events of the trade, never fails. If the coordinator can't be reached
+	/// the returned stream just waits for the fallback poll interval.
+	pub fn subscribe(trader_config: &TraderSettings, offer_id_hex: &str) -> Self {
+		let request = OfferTakenRequest {
+			robohash_hex: trader_config.robosats_robohash_hex.clone(),
+			offer_id_hex: offer_id_hex.to_string(),
+		};
+		// the stream stays open for the whole trade, so no request timeout
+		let response = coordinator_client_builder(trader_config.network)
+			.and_then(|builder| Ok(builder.timeout(None).build()?))
+			.and_then(|client| {
+				post_signed(&client, trader_config, endpoints::TRADE_EVENTS, &request)
+			});
+		let response = match response {
+			Ok(response) if response.status() == 200 => response,
+			Ok(response) => {
+				debug!(
+					"Trade event stream not available (status {}), polling instead",
+					response.status()
+				);
+				return Self::polling();
+			}
+			Err(e) => {
+				debug!("Trade event stream not available ({}), polling instead", e);
+				return Self::polling();
+			}
+		};
+
+		let (sender, receiver) = channel();
+		let closed = Arc::new(AtomicBool::new(false));
+		let reader_closed = Arc::clone(&closed);
+		std::thread::spawn(move || {
+			// the coordinator sends a keep-alive comment every 15 seconds, so the flag is checked
+			// regularly even if no events arrive
+			for line in BufReader::new(response).lines() {
+				let Ok(line) = line else { break };
+				if reader_closed.load(Ordering::Relaxed) {
+					return;
+				}
+				if let Some(event) = line.strip_prefix("event:") {
+					if sender.send(event.trim().to_string()).is_err() {
+						break;
+					}
+				}
+			}
+			debug!("Trade event stream closed, falling back to polling");
+		});
+		Self {
+			events: Some(receiver),
+			closed,
+		}
+	}
+
+	fn polling() -> Self {
+		Self {
+			events: None,
+			closed: Arc::new(AtomicBool::new(true)),
+		}
+	}
+
+	/// blocks until the next trade event arrives or the poll interval passed
+	pub fn wait(&mut self) {
+		match &self.events {
+			Some(events) => match events.recv_timeout(EVENT_STREAM_POLL_INTERVAL) {
+				Ok(event) => debug!("Received trade event: {}", event),
+				Err(RecvTimeoutError::Timeout) => (),
+				Err(RecvTimeoutError::Disconnected) => {
+					self.events = None;
+					sleep(FALLBACK_POLL_INTERVAL);
+				}
+			},
+			None => sleep(FALLBACK_POLL_INTERVAL),
+		}
+	}
+}
+
+impl Drop for TradeEventStream {
+	fn drop(&mut self) {
+		self.closed.store(true, Ordering::Relaxed);
+	}
+}
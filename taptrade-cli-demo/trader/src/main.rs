@@ -8,11 +8,13 @@ use anyhow::{anyhow, Result};
 use cli::CliSettings;
 use log::{debug, error, info, trace, warn};
 
-/// start the according trading mode depending on the CLI input or env variables/.env file [maker or taker]
+/// start the according trading mode depending on the CLI input or env variables/.env file [maker, taker, recover or cancel]
 fn start_trade_pipeline(cli_input: &CliSettings) -> Result<()> {
 	match cli_input {
 		CliSettings::Maker(maker_config) => trading::run_maker(maker_config),
 		CliSettings::Taker(taker_config) => trading::run_taker(taker_config),
+		CliSettings::Recover(recovery_config) => trading::run_recovery(recovery_config),
+		CliSettings::Cancel(cancellation_config) => trading::cancel_offer(cancellation_config),
 		_ => Err(anyhow!(
 			"Wrong trading mode selected, not implemented: {:?}",
 			cli_input
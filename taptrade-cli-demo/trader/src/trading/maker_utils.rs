@@ -5,6 +5,9 @@ use serde::Serialize;
 
 use super::utils::*;
 use super::*;
+use crate::communication::{
+	check_offer_status, fetch_bond_requirements, submit_maker_bond, trade_events::TradeEventStream,
+};
 
 impl ActiveOffer {
 	pub fn create(
@@ -12,7 +15,7 @@ impl ActiveOffer {
 		maker_config: &TraderSettings,
 	) -> Result<ActiveOffer> {
 		// fetches the bond requirements necessary to assemble the bond for the requested offer
-		let offer_conditions = BondRequirementResponse::fetch(maker_config)?;
+		let offer_conditions = fetch_bond_requirements(maker_config)?;
 		debug!("Offer conditions fetched: {:#?}", &offer_conditions);
 		// assembles the bond required by the coordinator, also generates the musig data (keys, nonces) and a payout address
 		// which are being submitted to the coordinator for the further trade
@@ -26,7 +29,7 @@ impl ActiveOffer {
 			offer_conditions.escrow_locking_input_amount_without_trade_sum
 				+ maker_config.trade_type.value()
 		};
-		let (psbt_inputs_hex_csv, escrow_change_address) =
+		let (escrow_inputs, escrow_change_address) =
 			trading_wallet.get_escrow_psbt_inputs(input_amount)?;
 
 		debug!(
@@ -40,19 +43,20 @@ impl ActiveOffer {
 			musig_pub_nonce_hex: hex::encode(musig_data.nonce.get_pub_for_sharing()?.serialize()),
 			musig_pubkey_hex: hex::encode(musig_data.public_key.serialize()),
 			taproot_pubkey_hex: hex::encode(trading_wallet.taproot_pubkey.serialize()),
-			bdk_psbt_inputs_hex_csv: psbt_inputs_hex_csv.clone(),
+			escrow_inputs: escrow_inputs.clone(),
 			client_change_address: escrow_change_address.clone(),
 		};
 
 		// send the bond submission request to the coordinator, returns submission result with offer id and unix timestamp of bond lock
-		let submission_result = bond_submission_request.send_maker(maker_config)?;
+		let submission_result = submit_maker_bond(&bond_submission_request, maker_config)?;
 		Ok(ActiveOffer {
 			offer_id_hex: submission_result.offer_id_hex,
 			used_musig_config: musig_data,
 			used_bond: bond,
 			expected_payout_address: payout_address,
 			escrow_psbt: None,
-			psbt_inputs_hex_csv,
+			escrow_requirements: None,
+			escrow_inputs,
 			escrow_change_address,
 		})
 	}
@@ -60,11 +64,13 @@ impl ActiveOffer {
 	// polling until offer is taken, in production a more efficient way would make sense
 	// returns the PSBT of the escrow trade transaction we have to validate, sign and return
 	pub fn wait_until_taken(&self, trader_config: &TraderSettings) -> Result<OfferTakenResponse> {
+		// the taken event wakes us up right away, otherwise we keep polling
+		let mut trade_events = TradeEventStream::subscribe(trader_config, &self.offer_id_hex);
 		loop {
-			thread::sleep(Duration::from_secs(2));
-			if let Some(offer_taken_response) = OfferTakenResponse::check(self, trader_config)? {
+			if let Some(offer_taken_response) = check_offer_status(self, trader_config)? {
 				return Ok(offer_taken_response);
 			}
+			trade_events.wait();
 		}
 	}
 }
@@ -5,26 +5,34 @@ pub mod utils;
 use self::utils::ActiveOffer;
 use super::*;
 use crate::{
-	cli::{OfferType, TraderSettings},
-	communication::api::{
-		BondRequirementResponse, BondSubmissionRequest, IsOfferReadyRequest, OfferTakenRequest,
-		OfferTakenResponse, PsbtSubmissionRequest, PublicOffer, PublicOffers,
-		TradeObligationsSatisfied, TradeObligationsUnsatisfied,
+	cli::{CancellationSettings, OfferType, RecoverySettings, TraderSettings},
+	communication::{
+		poll_final_payout, request_escrow, send_offer_cancellation, submit_escrow_psbt,
+		submit_obligation_confirmation, submit_payout_signature,
+		taker_requests::{ask_user_to_select_offer, fetch_available_offers},
+		PayoutOutcome,
 	},
 	wallet::{
 		bond::Bond,
+		escrow_recovery::EscrowRecovery,
 		musig2_utils::{MuSigData, MusigNonce},
 		TradingWallet,
 	},
 };
+use anyhow::Context;
 use bdk::{
-	bitcoin::{amount::serde::as_btc::deserialize, psbt::PartiallySignedTransaction},
+	bitcoin::{
+		amount::serde::as_btc::deserialize, psbt::PartiallySignedTransaction, Address, Network,
+	},
 	database::MemoryDatabase,
 	wallet::AddressInfo,
 };
-use communication::api::PayoutSignatureRequest;
 use reqwest::header::ACCEPT_LANGUAGE;
-use std::{str::FromStr, thread, time::Duration};
+use std::{fs, path::Path, str::FromStr, thread, time::Duration};
+use taptrade_protocol::{
+	BondRequirementResponse, BondSubmissionRequest, EscrowInput, OfferTakenRequest,
+	OfferTakenResponse, PublicOffer,
+};
 
 /// the main maker flow function
 pub fn run_maker(maker_config: &TraderSettings) -> Result<()> {
@@ -32,7 +40,7 @@ pub fn run_maker(maker_config: &TraderSettings) -> Result<()> {
 	let wallet = TradingWallet::load_wallet(maker_config)?; // initialize the wallet with xprv
 
 	// create an offer with the coordinator, offer is an offer that is in the coordinator orderbook (bond submitted, awaiting taker)
-	let offer = ActiveOffer::create(&wallet, maker_config)?;
+	let mut offer = ActiveOffer::create(&wallet, maker_config)?;
 	info!("Maker offer created: {:#?}", &offer);
 
 	// waits until taker accepts offer (polling), then gets the escrow psbt in return to sign the inputs
@@ -40,41 +48,31 @@ pub fn run_maker(maker_config: &TraderSettings) -> Result<()> {
 	let mut escrow_psbt =
 		PartiallySignedTransaction::from_str(escrow_psbt_requirements.escrow_psbt_hex.as_str())?;
 	let signed_escrow_psbt = wallet
-		.validate_escrow_psbt(&escrow_psbt)?
+		.validate_escrow_psbt(
+			&escrow_psbt,
+			&escrow_psbt_requirements,
+			&offer.used_musig_config,
+			&offer.escrow_change_address,
+		)?
 		.sign_escrow_psbt(&mut escrow_psbt)?;
 
 	// submit signed escrow psbt back to coordinator
-	PsbtSubmissionRequest::submit_escrow_psbt(
-		&escrow_psbt,
-		offer.offer_id_hex.clone(),
-		maker_config,
-	)?;
+	submit_escrow_psbt(&escrow_psbt, offer.offer_id_hex.clone(), maker_config)?;
+	offer.escrow_psbt = Some(escrow_psbt);
+	offer.escrow_requirements = Some(escrow_psbt_requirements);
 
 	// wait for confirmation of the escrow locking transaction (polling)
 	offer.wait_on_trade_ready_confirmation(maker_config)?;
 	if offer.fiat_confirmation_cli_input(maker_config)? {
 		// this represents the "confirm payment" / "confirm fiat recieved" button
-		TradeObligationsSatisfied::submit(&offer.offer_id_hex, maker_config)?;
+		submit_obligation_confirmation(&offer.offer_id_hex, maker_config)?;
 		info!("Waiting for other party to confirm the trade.");
-		let (payout_keyspend_psbt, agg_pub_nonce, agg_pubk_ctx) =
-			IsOfferReadyRequest::poll_payout(maker_config, &offer)?;
-		debug!("Payout PSBT received: {}", &payout_keyspend_psbt);
-		let signature = wallet
-			.validate_payout_psbt(&payout_keyspend_psbt)?
-			.create_keyspend_payout_signature(
-				payout_keyspend_psbt,
-				agg_pubk_ctx,
-				agg_pub_nonce,
-				offer.used_musig_config,
-			)?;
-		// submit signed payout psbt back to coordinator
-		PayoutSignatureRequest::send(maker_config, &signature, &offer.offer_id_hex)?;
-		debug!("now the coordinator will broadcast the payout transaction and the trade should be finished");
 	} else {
 		warn!("Trader unsatisfied. Initiating escrow mode.");
-		TradeObligationsUnsatisfied::request_escrow(&offer.offer_id_hex, maker_config)?;
-		let escrow_payout_script_psbt = IsOfferReadyRequest::poll_payout(maker_config, &offer)?;
+		request_escrow(&offer.offer_id_hex, maker_config)?;
 	}
+	// pull for other parties confirmation or the escrow decision of the coordinator, then finish the payout
+	handle_payout(&wallet, maker_config, offer)?;
 	Ok(())
 }
 
@@ -83,16 +81,16 @@ pub fn run_taker(taker_config: &TraderSettings) -> Result<()> {
 	let wallet = TradingWallet::load_wallet(taker_config)?;
 
 	// fetches public offers of the coordinator (Orderbook)
-	let mut available_offers = PublicOffers::fetch(taker_config)?;
+	let mut available_offers = fetch_available_offers(taker_config)?;
 
 	// polls until offers are available
 	while available_offers.offers.is_none() {
 		debug!("No offers available, fetching again in 2 sec.");
 		thread::sleep(Duration::from_secs(2));
-		available_offers = PublicOffers::fetch(taker_config)?;
+		available_offers = fetch_available_offers(taker_config)?;
 	}
 	// ask for taker cli input to select a suitable offer
-	let selected_offer: &PublicOffer = available_offers.ask_user_to_select()?;
+	let selected_offer: &PublicOffer = ask_user_to_select_offer(&available_offers)?;
 
 	// take selected offer and wait for maker to sign his input to the ecrow transaction
 	let accepted_offer = ActiveOffer::take(&wallet, taker_config, selected_offer)?;
@@ -104,29 +102,114 @@ pub fn run_taker(taker_config: &TraderSettings) -> Result<()> {
 	// ask the taker if he is satisfied or wants to go into escrow with cli input
 	if accepted_offer.fiat_confirmation_cli_input(taker_config)? {
 		// this represents the "confirm payment" / "confirm fiat recieved" button
-		TradeObligationsSatisfied::submit(&accepted_offer.offer_id_hex, taker_config)?;
+		submit_obligation_confirmation(&accepted_offer.offer_id_hex, taker_config)?;
 		debug!("Waiting for other party to confirm the trade.");
-		// pull for other parties confirmation, then receive the transaction to create MuSig partial signature for (keyspend) to payout address
-		let (payout_keyspend_psbt, agg_pub_nonce, agg_pubk_ctx) =
-			IsOfferReadyRequest::poll_payout(taker_config, &accepted_offer)?;
-
-		debug!("Received payout psbt: {}", &payout_keyspend_psbt);
-		let signature = wallet
-			.validate_payout_psbt(&payout_keyspend_psbt)?
-			.create_keyspend_payout_signature(
-				payout_keyspend_psbt,
-				agg_pubk_ctx,
-				agg_pub_nonce,
-				accepted_offer.used_musig_config,
-			)?;
-
-		// submit partial signature back to coordinator
-		PayoutSignatureRequest::send(taker_config, &signature, &accepted_offer.offer_id_hex)?;
-		debug!("now the coordinator will broadcast the payout transaction and the trade should be finished");
 	// here we need to handle if the other party is not cooperating
 	} else {
-		error!("Trader unsatisfied. Initiating escrow mode.");
-		panic!("Escrow to be implemented!");
+		warn!("Trader unsatisfied. Initiating escrow mode.");
+		request_escrow(&accepted_offer.offer_id_hex, taker_config)?;
+	}
+	// pull for other parties confirmation or the escrow decision of the coordinator, then finish the payout
+	handle_payout(&wallet, taker_config, accepted_offer)?;
+	Ok(())
+}
+
+/// withdraws an offer of the maker from the orderbook before it gets taken
+pub fn cancel_offer(cancellation_config: &CancellationSettings) -> Result<()> {
+	send_offer_cancellation(cancellation_config)?;
+	info!(
+		"Offer {} cancelled, the bond will not be published",
+		cancellation_config.offer_id_hex
+	);
+	Ok(())
+}
+
+/// recovers the escrow funds without the coordinator once a timelocked escrow leaf matured. The maker alone
+/// leaf is signed and broadcast directly, the two party leaf is passed between the traders as psbt file
+/// until both signatures are added
+pub fn run_recovery(recovery_config: &RecoverySettings) -> Result<()> {
+	let recovery = EscrowRecovery::load(recovery_config)?;
+	let leaf = recovery.get_spendable_leaf()?;
+	info!(
+		"Recovering escrow through the leaf with timelock {} ({} signer(s))",
+		leaf.locktime,
+		leaf.signers.len()
+	);
+
+	let psbt_file = Path::new(&recovery_config.recovery_psbt_file);
+	let mut recovery_psbt = if !leaf.is_single_signer() && psbt_file.exists() {
+		let imported_psbt =
+			PartiallySignedTransaction::from_str(fs::read_to_string(psbt_file)?.trim())
+				.context("Error parsing imported recovery psbt")?;
+		recovery.validate_recovery_psbt(
+			&imported_psbt,
+			&leaf,
+			recovery_config.expected_payout_amount_sat,
+		)?;
+		imported_psbt
+	} else {
+		let counterparty_output = match (
+			&recovery_config.counterparty_payout_address,
+			recovery_config.counterparty_payout_amount_sat,
+		) {
+			(Some(address), Some(amount)) => Some((
+				Address::from_str(address)?.require_network(recovery_config.network)?,
+				amount,
+			)),
+			_ => None,
+		};
+		recovery.assemble_recovery_psbt(&leaf, counterparty_output)?
+	};
+	recovery.sign_recovery_psbt(&mut recovery_psbt, &leaf)?;
+
+	if recovery.is_fully_signed(&recovery_psbt, &leaf) {
+		let recovery_txid = recovery.finalize_and_broadcast(recovery_psbt)?;
+		info!("Broadcasted escrow recovery transaction: {}", recovery_txid);
+	} else {
+		fs::write(psbt_file, recovery_psbt.to_string())?;
+		info!(
+			"Exported signed recovery psbt to {}. Pass it to the other trader to add their signature and broadcast.",
+			psbt_file.display()
+		);
+	}
+	Ok(())
+}
+
+/// polls the coordinator for the final payout and acts on the outcome: signing the keyspend payout
+/// if both traders are satisfied, signing and broadcasting the script payout if we won the escrow case,
+/// or reporting the lost bond if the coordinator decided in favour of the other trader
+fn handle_payout(
+	wallet: &TradingWallet,
+	trader_config: &TraderSettings,
+	offer: ActiveOffer,
+) -> Result<()> {
+	match poll_final_payout(trader_config, &offer)? {
+		PayoutOutcome::Keyspend(payout_keyspend_psbt, agg_pub_nonce, agg_pubk_ctx) => {
+			debug!("Received payout psbt: {}", &payout_keyspend_psbt);
+			let signature = wallet
+				.validate_payout_psbt(&payout_keyspend_psbt, &agg_pubk_ctx, &offer)?
+				.create_keyspend_payout_signature(
+					payout_keyspend_psbt,
+					*agg_pubk_ctx,
+					agg_pub_nonce,
+					offer.used_musig_config,
+				)?;
+
+			// submit partial signature back to coordinator
+			submit_payout_signature(trader_config, &signature, &offer.offer_id_hex)?;
+			debug!("now the coordinator will broadcast the payout transaction and the trade should be finished");
+		}
+		PayoutOutcome::ScriptPath(script_payout_psbt) => {
+			info!("Escrow decided in our favour, received script payout psbt.");
+			debug!("Script payout psbt: {}", &script_payout_psbt);
+			let payout_txid = wallet
+				.validate_script_payout_psbt(&script_payout_psbt, &offer)?
+				.sign_and_broadcast_script_payout(script_payout_psbt, trader_config)?;
+			info!("Broadcasted escrow payout transaction: {}", payout_txid);
+		}
+		PayoutOutcome::LostEscrow => {
+			warn!("The coordinator decided the escrow in favour of the other trader, the bond is lost.");
+		}
 	}
 	Ok(())
 }
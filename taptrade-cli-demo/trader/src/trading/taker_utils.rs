@@ -1,7 +1,7 @@
 use bdk::bitcoin::consensus::encode::serialize_hex;
 use bdk::electrum_client::Request;
 
-use crate::communication::api::{IsOfferReadyRequest, OfferPsbtRequest, PsbtSubmissionRequest};
+use crate::communication::{submit_escrow_psbt, taker_requests::submit_taker_bond};
 
 use super::utils::*;
 use super::*;
@@ -27,7 +27,7 @@ impl ActiveOffer {
 				.escrow_locking_input_amount_without_trade_sum
 				+ taker_config.trade_type.value()
 		};
-		let (bdk_psbt_inputs_hex_csv, client_change_address) =
+		let (escrow_inputs, client_change_address) =
 			trading_wallet.get_escrow_psbt_inputs(input_amount)?;
 
 		let bond_submission_request = BondSubmissionRequest {
@@ -37,29 +37,30 @@ impl ActiveOffer {
 			taproot_pubkey_hex: trading_wallet.taproot_pubkey.to_string(),
 			musig_pub_nonce_hex: musig_data.nonce.get_pub_for_sharing()?.to_string(),
 			musig_pubkey_hex: hex::encode(musig_data.public_key.serialize()),
-			bdk_psbt_inputs_hex_csv: bdk_psbt_inputs_hex_csv.clone(),
+			escrow_inputs: escrow_inputs.clone(),
 			client_change_address: client_change_address.clone(),
 		};
 
 		// now we submit the signed bond transaction to the coordinator and receive the escrow PSBT we have to sign
 		// in exchange
 		let escrow_contract_requirements =
-			OfferPsbtRequest::taker_request(offer, bond_submission_request, taker_config)?;
+			submit_taker_bond(offer, bond_submission_request, taker_config)?;
 
 		let mut escrow_psbt =
 			PartiallySignedTransaction::from_str(&escrow_contract_requirements.escrow_psbt_hex)?;
 
 		// now we have to verify, sign and submit the escrow psbt again
 		trading_wallet
-			.validate_escrow_psbt(&escrow_psbt)?
+			.validate_escrow_psbt(
+				&escrow_psbt,
+				&escrow_contract_requirements,
+				&musig_data,
+				&client_change_address,
+			)?
 			.sign_escrow_psbt(&mut escrow_psbt)?;
 
 		// submit signed escrow psbt back to coordinator
-		PsbtSubmissionRequest::submit_escrow_psbt(
-			&escrow_psbt,
-			offer.offer_id_hex.clone(),
-			taker_config,
-		)?;
+		submit_escrow_psbt(&escrow_psbt, offer.offer_id_hex.clone(), taker_config)?;
 
 		// offer is now active
 		Ok(ActiveOffer {
@@ -68,7 +69,8 @@ impl ActiveOffer {
 			used_bond: bond,
 			expected_payout_address: payout_address,
 			escrow_psbt: Some(escrow_psbt),
-			psbt_inputs_hex_csv: bdk_psbt_inputs_hex_csv,
+			escrow_requirements: Some(escrow_contract_requirements),
+			escrow_inputs,
 			escrow_change_address: client_change_address,
 		})
 	}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::communication::poll_escrow_confirmation;
 
 #[derive(Debug)]
 pub struct ActiveOffer {
@@ -7,8 +8,9 @@ pub struct ActiveOffer {
 	pub used_bond: PartiallySignedTransaction,
 	pub expected_payout_address: AddressInfo,
 	pub escrow_psbt: Option<PartiallySignedTransaction>,
+	pub escrow_requirements: Option<OfferTakenResponse>,
 	pub escrow_change_address: String,
-	pub psbt_inputs_hex_csv: String,
+	pub escrow_inputs: Vec<EscrowInput>,
 }
 
 impl ActiveOffer {
@@ -18,7 +20,13 @@ impl ActiveOffer {
 		&self,
 		trader_config: &TraderSettings,
 	) -> Result<&Self> {
-		IsOfferReadyRequest::poll(trader_config, self)?;
+		poll_escrow_confirmation(trader_config, self)?;
+		if let Some(escrow_requirements) = &self.escrow_requirements {
+			info!(
+				"Escrow locked. Keep the escrow output descriptor to recover the funds if the coordinator disappears: {}",
+				escrow_requirements.escrow_output_descriptor
+			);
+		}
 		Ok(self)
 	}
 
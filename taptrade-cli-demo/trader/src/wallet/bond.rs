@@ -12,8 +12,8 @@ use log::debug;
 use serde::de::value;
 use std::str::FromStr;
 
-use crate::communication::api::BondRequirementResponse;
 use crate::wallet::TraderSettings;
+use taptrade_protocol::BondRequirementResponse;
 
 pub struct Outpoint {
 	pub txid_hex: String,
@@ -32,9 +32,9 @@ impl Bond {
 		trader_input: &TraderSettings,
 	) -> Result<PartiallySignedTransaction> {
 		debug!("Assembling bond transaction");
-		// parse bond locking address as Address struct and verify network is testnet
+		// parse bond locking address as Address struct and verify it is on our network
 		let address: Address =
-			Address::from_str(&bond_target.bond_address)?.require_network(Network::Regtest)?;
+			Address::from_str(&bond_target.bond_address)?.require_network(trader_input.network)?;
 
 		// build bond locking transaction. Use coin selection to add at least enough outputs
 		// to have the full trading sum as change as evidence for the coordinator that the maker owns
@@ -112,6 +112,7 @@ mod tests {
 			bond_ratio: 12,
 			wallet_xprv: ExtendedPrivKey::from_str("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32").unwrap(),
 			duration_unix_ts: 1783593911, // until when the order should stay available
+			network: Network::Testnet,
 		};
 
 		let result = Bond::assemble(&wallet, &bond_target, &trader_input);
@@ -137,6 +138,7 @@ mod tests {
 			bond_ratio: 12,
 			wallet_xprv: ExtendedPrivKey::from_str("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32").unwrap(),
 			duration_unix_ts: 1783593911, // until when the order should stay available
+			network: Network::Testnet,
 		};
 
 		let result = Bond::assemble(&wallet, &bond_target, &trader_input);
@@ -163,6 +165,7 @@ mod tests {
 			bond_ratio: 12,
 			wallet_xprv: ExtendedPrivKey::from_str("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32").unwrap(),
 			duration_unix_ts: 1783593911, // until when the order should stay available
+			network: Network::Testnet,
 		};
 
 		let result = Bond::assemble(&wallet, &bond_target, &trader_input);
@@ -188,6 +191,7 @@ mod tests {
 			bond_ratio: 12,
 			wallet_xprv: ExtendedPrivKey::from_str("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32").unwrap(),
 			duration_unix_ts: 1783593911, // until when the order should stay available
+			network: Network::Testnet,
 		};
 
 		let result = Bond::assemble(&wallet, &bond_target, &trader_input);
@@ -213,6 +217,7 @@ mod tests {
 			bond_ratio: 12,
 			wallet_xprv: ExtendedPrivKey::from_str("tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32").unwrap(),
 			duration_unix_ts: 1783593911, // until when the order should stay available
+			network: Network::Testnet,
 		};
 
 		let result = Bond::assemble(&wallet, &bond_target, &trader_input);
@@ -0,0 +1,563 @@
+/// Toolkit to get the funds out of the escrow output if the coordinator disappears. The escrow
+/// descriptor contains two timelocked tapleafs that can be spent without the coordinator:
+/// maker + taker after the shorter timelock and the maker alone after the longer timelock.
+/// The two party leaf is signed by exchanging the recovery psbt as file between the traders.
+use super::*;
+use crate::cli::RecoverySettings;
+use bdk::{
+	bitcoin::{absolute::LockTime, Sequence, Transaction, TxIn},
+	blockchain::GetHeight,
+	miniscript::{miniscript::decode::Terminal, Miniscript, Tap},
+};
+
+/// rough size of the recovery transaction (1 script path input, up to 2 outputs) used to estimate the fee
+const RECOVERY_TX_SIZE_VB: usize = 150;
+
+/// a timelocked escrow tapleaf that can be spent without the coordinator
+#[derive(Debug, Clone)]
+pub struct RecoveryLeaf {
+	pub leaf_hash: TapLeafHash,
+	pub signers: Vec<XOnlyPublicKey>,
+	pub locktime: u32,
+}
+
+pub struct EscrowRecovery {
+	pub escrow_descriptor: Descriptor<XOnlyPublicKey>,
+	pub backend: ElectrumBlockchain,
+	pub keypair: KeyPair,
+	pub taproot_pubkey: XOnlyPublicKey,
+	pub payout_address: Address,
+	pub network: Network,
+}
+
+impl RecoveryLeaf {
+	/// returns true if only a single signature is required to spend the leaf (maker only leaf)
+	pub fn is_single_signer(&self) -> bool {
+		self.signers.len() == 1
+	}
+}
+
+impl EscrowRecovery {
+	pub fn load(recovery_config: &RecoverySettings) -> Result<Self> {
+		let escrow_descriptor =
+			Descriptor::<XOnlyPublicKey>::from_str(&recovery_config.escrow_output_descriptor)
+				.context("Error parsing escrow output descriptor")?;
+		let backend = ElectrumBlockchain::from(Client::new(&recovery_config.electrum_endpoint)?);
+		let keypair = recovery_config.wallet_xprv.to_keypair(&Secp256k1::new());
+		let payout_address = Address::from_str(&recovery_config.payout_address)?
+			.require_network(recovery_config.network)
+			.context("Payout address wrong network")?;
+
+		Ok(EscrowRecovery {
+			escrow_descriptor,
+			backend,
+			keypair,
+			taproot_pubkey: keypair.x_only_public_key().0,
+			payout_address,
+			network: recovery_config.network,
+		})
+	}
+
+	/// selects the timelocked leaf we can spend at the current block height, preferring the
+	/// maker only leaf which doesn't require the signature of the other trader
+	pub fn get_spendable_leaf(&self) -> Result<RecoveryLeaf> {
+		let current_height = self.backend.get_height()?;
+		let mut leaves = get_own_timelocked_leaves(&self.escrow_descriptor, &self.taproot_pubkey)?;
+		leaves.sort_by_key(|leaf| leaf.signers.len());
+
+		if let Some(leaf) = leaves.iter().find(|leaf| leaf.locktime <= current_height) {
+			return Ok(leaf.clone());
+		}
+		let next_locktime = leaves
+			.iter()
+			.map(|leaf| leaf.locktime)
+			.min()
+			.ok_or(anyhow!("No timelocked escrow leaf found"))?;
+		Err(anyhow!(
+			"Escrow timelock not matured yet, current height: {}, spendable at height: {} ({} blocks remaining)",
+			current_height,
+			next_locktime,
+			next_locktime - current_height
+		))
+	}
+
+	/// loads the escrow descriptor in a temp wallet and returns the escrow utxo (as Input and its Outpoint)
+	fn get_escrow_utxo(&self) -> Result<(Input, OutPoint)> {
+		let escrow_wallet = Wallet::new(
+			&self.escrow_descriptor.to_string(),
+			None,
+			self.network,
+			MemoryDatabase::new(),
+		)?;
+		escrow_wallet.sync(&self.backend, SyncOptions::default())?;
+		let available_utxos = escrow_wallet.list_unspent()?;
+		if available_utxos.len() != 1 {
+			return Err(anyhow!(
+				"Expected exactly one utxo [found: {}] for escrow output: {:?}",
+				available_utxos.len(),
+				available_utxos
+			));
+		};
+
+		let input = escrow_wallet.get_psbt_input(available_utxos[0].clone(), None, false)?;
+		Ok((input, available_utxos[0].outpoint))
+	}
+
+	/// assembles the unsigned recovery psbt spending the escrow output through the passed leaf. If the leaf
+	/// requires the signature of the other trader the passed counterparty output is added
+	pub fn assemble_recovery_psbt(
+		&self,
+		leaf: &RecoveryLeaf,
+		counterparty_output: Option<(Address, u64)>,
+	) -> Result<PartiallySignedTransaction> {
+		let (escrow_input, escrow_outpoint) = self.get_escrow_utxo()?;
+		let feerate = match self.backend.estimate_fee(6) {
+			Ok(feerate) => feerate,
+			Err(e) => {
+				warn!("Failed to estimate fee: {}. Using fallback 40 sat/vb", e);
+				FeeRate::from_sat_per_vb(40.0)
+			}
+		};
+		build_recovery_psbt(
+			escrow_input,
+			escrow_outpoint,
+			leaf,
+			feerate.fee_vb(RECOVERY_TX_SIZE_VB),
+			&self.payout_address,
+			counterparty_output,
+		)
+	}
+
+	/// validates a recovery psbt imported from the other trader before we add our signature. It has to spend
+	/// the escrow utxo and pay at least the agreed amount to our payout address.
+	pub fn validate_recovery_psbt(
+		&self,
+		psbt: &PartiallySignedTransaction,
+		leaf: &RecoveryLeaf,
+		expected_payout_amount_sat: Option<u64>,
+	) -> Result<&Self> {
+		let expected_payout_amount_sat = expected_payout_amount_sat.ok_or(anyhow!(
+			"The agreed payout amount (EXPECTED_PAYOUT_AMOUNT_SAT) is required to sign an imported recovery psbt"
+		))?;
+		let (escrow_input, escrow_outpoint) = self.get_escrow_utxo()?;
+		let escrow_output = escrow_input.witness_utxo.ok_or(anyhow!(
+			"Escrow utxo psbt input is missing the witness utxo"
+		))?;
+		check_recovery_psbt(
+			psbt,
+			leaf,
+			&escrow_outpoint,
+			&escrow_output,
+			&self.payout_address,
+			expected_payout_amount_sat,
+		)?;
+		Ok(self)
+	}
+
+	/// adds our schnorr signature for the passed leaf to the recovery psbt
+	pub fn sign_recovery_psbt(
+		&self,
+		psbt: &mut PartiallySignedTransaction,
+		leaf: &RecoveryLeaf,
+	) -> Result<&Self> {
+		sign_recovery_input(psbt, leaf, &self.keypair)?;
+		Ok(self)
+	}
+
+	/// returns true if all signers of the leaf have added their signature to the recovery psbt
+	pub fn is_fully_signed(&self, psbt: &PartiallySignedTransaction, leaf: &RecoveryLeaf) -> bool {
+		leaf.signers.iter().all(|signer| {
+			psbt.inputs[0]
+				.tap_script_sigs
+				.contains_key(&(*signer, leaf.leaf_hash))
+		})
+	}
+
+	/// finalizes the fully signed recovery psbt and broadcasts the transaction
+	pub fn finalize_and_broadcast(&self, mut psbt: PartiallySignedTransaction) -> Result<Txid> {
+		psbt.finalize_mut(&Secp256k1::new())
+			.map_err(|e| anyhow!("Finalizing recovery psbt failed: {:?}", e))?;
+		let recovery_tx = psbt.extract_tx();
+		self.backend.broadcast(&recovery_tx)?;
+		Ok(recovery_tx.txid())
+	}
+}
+
+/// returns all timelocked tapleafs of the escrow descriptor containing our taproot key
+fn get_own_timelocked_leaves(
+	escrow_descriptor: &Descriptor<XOnlyPublicKey>,
+	taproot_pubkey: &XOnlyPublicKey,
+) -> Result<Vec<RecoveryLeaf>> {
+	let tr_descriptor = if let Descriptor::Tr(tr) = escrow_descriptor {
+		tr
+	} else {
+		return Err(anyhow!(
+			"Escrow output descriptor is not a taproot descriptor"
+		));
+	};
+	let leaves: Vec<RecoveryLeaf> = tr_descriptor
+		.iter_scripts()
+		.filter_map(|(_, leaf)| {
+			let locktime = get_leaf_locktime(leaf)?;
+			let signers: Vec<XOnlyPublicKey> = leaf.iter_pk().collect();
+			Some(RecoveryLeaf {
+				leaf_hash: TapLeafHash::from_script(
+					&leaf.encode(),
+					taproot::LeafVersion::TapScript,
+				),
+				signers,
+				locktime,
+			})
+		})
+		.filter(|leaf| leaf.signers.contains(taproot_pubkey))
+		.collect();
+	if leaves.is_empty() {
+		return Err(anyhow!(
+			"Our taproot pubkey is not part of a timelocked escrow leaf"
+		));
+	}
+	Ok(leaves)
+}
+
+/// builds the unsigned recovery psbt spending the escrow utxo through the passed leaf. Our payout output
+/// receives the escrow value minus the fee and the counterparty output (required by the two party leaf)
+fn build_recovery_psbt(
+	escrow_input: Input,
+	escrow_outpoint: OutPoint,
+	leaf: &RecoveryLeaf,
+	fee_sat: u64,
+	payout_address: &Address,
+	counterparty_output: Option<(Address, u64)>,
+) -> Result<PartiallySignedTransaction> {
+	let escrow_value = escrow_input
+		.witness_utxo
+		.as_ref()
+		.ok_or(anyhow!(
+			"Escrow utxo psbt input is missing the witness utxo"
+		))?
+		.value;
+	let mut remaining_value = escrow_value
+		.checked_sub(fee_sat)
+		.ok_or(anyhow!("Escrow output too small to pay the recovery fee"))?;
+
+	let mut outputs: Vec<TxOut> = Vec::new();
+	if !leaf.is_single_signer() {
+		let (counterparty_address, counterparty_amount) = counterparty_output.ok_or(anyhow!(
+			"The two party recovery requires a payout address and amount of the other trader"
+		))?;
+		remaining_value = remaining_value
+			.checked_sub(counterparty_amount)
+			.ok_or(anyhow!(
+				"Counterparty payout amount exceeds the escrow output"
+			))?;
+		outputs.push(TxOut {
+			value: counterparty_amount,
+			script_pubkey: counterparty_address.script_pubkey(),
+		});
+	}
+	outputs.push(TxOut {
+		value: remaining_value,
+		script_pubkey: payout_address.script_pubkey(),
+	});
+
+	// the locktime of the transaction has to satisfy the after() condition of the leaf
+	let unsigned_tx = Transaction {
+		version: 2,
+		lock_time: LockTime::from_consensus(leaf.locktime),
+		input: vec![TxIn {
+			previous_output: escrow_outpoint,
+			sequence: Sequence::ENABLE_LOCKTIME_NO_RBF,
+			..Default::default()
+		}],
+		output: outputs,
+	};
+	let mut psbt = PartiallySignedTransaction::from_unsigned_tx(unsigned_tx)?;
+	psbt.inputs[0] = escrow_input;
+	Ok(psbt)
+}
+
+/// checks the imported recovery psbt spends only the escrow utxo through the leaf and pays at least the
+/// agreed amount to our payout address
+fn check_recovery_psbt(
+	psbt: &PartiallySignedTransaction,
+	leaf: &RecoveryLeaf,
+	escrow_outpoint: &OutPoint,
+	escrow_output: &TxOut,
+	payout_address: &Address,
+	expected_payout_amount_sat: u64,
+) -> Result<()> {
+	if psbt.unsigned_tx.input.len() != 1
+		|| psbt.inputs.len() != 1
+		|| psbt.unsigned_tx.input[0].previous_output != *escrow_outpoint
+	{
+		return Err(anyhow!(
+			"Recovery psbt does not spend exactly the escrow utxo {}",
+			escrow_outpoint
+		));
+	}
+	let escrow_utxo = psbt.inputs[0]
+		.witness_utxo
+		.as_ref()
+		.ok_or(anyhow!("Recovery psbt input is missing the witness utxo"))?;
+	if escrow_utxo != escrow_output {
+		return Err(anyhow!(
+			"Recovery psbt witness utxo does not match the escrow output"
+		));
+	}
+	if psbt.unsigned_tx.lock_time.to_consensus_u32() < leaf.locktime {
+		return Err(anyhow!(
+			"Recovery psbt locktime {} does not satisfy the leaf timelock {}",
+			psbt.unsigned_tx.lock_time,
+			leaf.locktime
+		));
+	}
+	let own_payout: u64 = psbt
+		.unsigned_tx
+		.output
+		.iter()
+		.filter(|output| output.script_pubkey == payout_address.script_pubkey())
+		.map(|output| output.value)
+		.sum();
+	if own_payout < expected_payout_amount_sat {
+		return Err(anyhow!(
+			"Recovery psbt pays {} sat to our payout address {}, expected at least {} sat",
+			own_payout,
+			payout_address,
+			expected_payout_amount_sat
+		));
+	}
+	info!(
+		"Recovery psbt pays {} of {} sat escrow value to our payout address",
+		own_payout, escrow_output.value
+	);
+	Ok(())
+}
+
+/// adds the schnorr signature of the keypair for the passed leaf to the recovery psbt
+fn sign_recovery_input(
+	psbt: &mut PartiallySignedTransaction,
+	leaf: &RecoveryLeaf,
+	keypair: &KeyPair,
+) -> Result<()> {
+	let secp = Secp256k1::new();
+	let utxo = psbt
+		.iter_funding_utxos()
+		.next()
+		.ok_or(anyhow!("No UTXO found in recovery psbt"))??
+		.clone();
+	let sighash = SighashCache::new(&psbt.unsigned_tx)
+		.taproot_script_spend_signature_hash(
+			0,
+			&Prevouts::All(&[utxo]),
+			leaf.leaf_hash,
+			TapSighashType::Default,
+		)
+		.context("Failed to create script spend sighash")?;
+	let signature = secp.sign_schnorr(&Message::from_slice(&sighash.to_byte_array())?, keypair);
+	psbt.inputs[0].tap_script_sigs.insert(
+		(keypair.x_only_public_key().0, leaf.leaf_hash),
+		taproot::Signature {
+			sig: signature,
+			hash_ty: TapSighashType::Default,
+		},
+	);
+	Ok(())
+}
+
+/// returns the absolute locktime of a tapleaf containing an after() condition
+fn get_leaf_locktime(leaf: &Miniscript<XOnlyPublicKey, Tap>) -> Option<u32> {
+	leaf.iter().find_map(|node| match node.node {
+		Terminal::After(locktime) => Some(locktime.to_consensus_u32()),
+		_ => None,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	struct RecoveryFixture {
+		maker_keypair: KeyPair,
+		taker_keypair: KeyPair,
+		escrow_input: Input,
+		escrow_outpoint: OutPoint,
+		maker_address: Address,
+		taker_address: Address,
+		two_party_leaf: RecoveryLeaf,
+	}
+
+	// escrow of 100k sat with a maker + taker leaf after 2048 and a maker only leaf after 12228
+	fn recovery_fixture() -> RecoveryFixture {
+		let secp = Secp256k1::new();
+		let maker_keypair = KeyPair::from_seckey_slice(&secp, &[1; 32]).unwrap();
+		let taker_keypair = KeyPair::from_seckey_slice(&secp, &[2; 32]).unwrap();
+		let internal_keypair = KeyPair::from_seckey_slice(&secp, &[3; 32]).unwrap();
+		let (maker_pk, taker_pk) = (
+			maker_keypair.x_only_public_key().0,
+			taker_keypair.x_only_public_key().0,
+		);
+		let escrow_descriptor = Descriptor::<XOnlyPublicKey>::from_str(&format!(
+			"tr({},{{and_v(v:and_v(v:pk({}),pk({})),after(2048)),and_v(v:pk({}),after(12228))}})",
+			internal_keypair.x_only_public_key().0,
+			maker_pk,
+			taker_pk,
+			maker_pk
+		))
+		.unwrap();
+
+		let maker_leaves = get_own_timelocked_leaves(&escrow_descriptor, &maker_pk).unwrap();
+		assert_eq!(maker_leaves.len(), 2);
+		let taker_leaves = get_own_timelocked_leaves(&escrow_descriptor, &taker_pk).unwrap();
+		assert_eq!(taker_leaves.len(), 1);
+		assert_eq!(taker_leaves[0].locktime, 2048);
+
+		RecoveryFixture {
+			escrow_input: Input {
+				witness_utxo: Some(TxOut {
+					value: 100_000,
+					script_pubkey: escrow_descriptor.script_pubkey(),
+				}),
+				..Default::default()
+			},
+			escrow_outpoint: OutPoint::new(Txid::all_zeros(), 0),
+			maker_address: Address::p2tr(&secp, maker_pk, None, Network::Regtest),
+			taker_address: Address::p2tr(&secp, taker_pk, None, Network::Regtest),
+			two_party_leaf: taker_leaves[0].clone(),
+			maker_keypair,
+			taker_keypair,
+		}
+	}
+
+	impl RecoveryFixture {
+		// the maker starts the two party recovery, paying 60k sat to the taker and 1k sat fee
+		fn maker_psbt(&self) -> PartiallySignedTransaction {
+			build_recovery_psbt(
+				self.escrow_input.clone(),
+				self.escrow_outpoint,
+				&self.two_party_leaf,
+				1_000,
+				&self.maker_address,
+				Some((self.taker_address.clone(), 60_000)),
+			)
+			.unwrap()
+		}
+
+		fn taker_check(&self, psbt: &PartiallySignedTransaction, expected_sat: u64) -> Result<()> {
+			check_recovery_psbt(
+				psbt,
+				&self.two_party_leaf,
+				&self.escrow_outpoint,
+				self.escrow_input.witness_utxo.as_ref().unwrap(),
+				&self.taker_address,
+				expected_sat,
+			)
+		}
+	}
+
+	#[test]
+	fn test_build_recovery_psbt() {
+		let fixture = recovery_fixture();
+		let psbt = fixture.maker_psbt();
+		assert_eq!(psbt.unsigned_tx.lock_time.to_consensus_u32(), 2048);
+		assert_eq!(
+			psbt.unsigned_tx.input[0].previous_output,
+			fixture.escrow_outpoint
+		);
+		let outputs: Vec<(bitcoin::ScriptBuf, u64)> = psbt
+			.unsigned_tx
+			.output
+			.iter()
+			.map(|output| (output.script_pubkey.clone(), output.value))
+			.collect();
+		assert_eq!(
+			outputs,
+			vec![
+				(fixture.taker_address.script_pubkey(), 60_000),
+				(fixture.maker_address.script_pubkey(), 39_000)
+			]
+		);
+
+		// the two party leaf requires the counterparty output, which can't exceed the escrow value
+		let build = |counterparty_output| {
+			build_recovery_psbt(
+				fixture.escrow_input.clone(),
+				fixture.escrow_outpoint,
+				&fixture.two_party_leaf,
+				1_000,
+				&fixture.maker_address,
+				counterparty_output,
+			)
+		};
+		assert!(build(None).is_err());
+		assert!(build(Some((fixture.taker_address.clone(), 100_000))).is_err());
+	}
+
+	#[test]
+	fn test_check_recovery_psbt() {
+		let fixture = recovery_fixture();
+		let psbt = fixture.maker_psbt();
+		assert!(fixture.taker_check(&psbt, 60_000).is_ok());
+
+		// pays us less than agreed
+		assert!(fixture.taker_check(&psbt, 60_001).is_err());
+
+		// spends another outpoint
+		let mut other_outpoint_psbt = psbt.clone();
+		other_outpoint_psbt.unsigned_tx.input[0].previous_output =
+			OutPoint::new(Txid::all_zeros(), 1);
+		assert!(fixture.taker_check(&other_outpoint_psbt, 60_000).is_err());
+
+		// witness utxo of another output
+		let mut other_utxo_psbt = psbt.clone();
+		other_utxo_psbt.inputs[0].witness_utxo = Some(TxOut {
+			value: 100_000,
+			script_pubkey: fixture.maker_address.script_pubkey(),
+		});
+		assert!(fixture.taker_check(&other_utxo_psbt, 60_000).is_err());
+
+		// locktime before the leaf timelock
+		let mut early_psbt = psbt.clone();
+		early_psbt.unsigned_tx.lock_time = LockTime::from_consensus(2047);
+		assert!(fixture.taker_check(&early_psbt, 60_000).is_err());
+	}
+
+	#[test]
+	fn test_sign_recovery_input() {
+		let fixture = recovery_fixture();
+		let mut psbt = fixture.maker_psbt();
+		sign_recovery_input(&mut psbt, &fixture.two_party_leaf, &fixture.maker_keypair).unwrap();
+		sign_recovery_input(&mut psbt, &fixture.two_party_leaf, &fixture.taker_keypair).unwrap();
+
+		let sighash = SighashCache::new(&psbt.unsigned_tx)
+			.taproot_script_spend_signature_hash(
+				0,
+				&Prevouts::All(&[fixture.escrow_input.witness_utxo.clone().unwrap()]),
+				fixture.two_party_leaf.leaf_hash,
+				TapSighashType::Default,
+			)
+			.unwrap();
+		let message = Message::from_slice(&sighash.to_byte_array()).unwrap();
+		for signer in fixture.two_party_leaf.signers.iter() {
+			let signature =
+				&psbt.inputs[0].tap_script_sigs[&(*signer, fixture.two_party_leaf.leaf_hash)];
+			assert!(Secp256k1::new()
+				.verify_schnorr(&signature.sig, &message, signer)
+				.is_ok());
+		}
+	}
+
+	#[test]
+	fn test_get_leaf_locktime() {
+		let escrow_descriptor = Descriptor::<XOnlyPublicKey>::from_str("tr(f00949d6dd1ce99a03f88a1a4f59117d553b0da51728bb7fd5b98fbf541337fb,{{and_v(v:pk(4987f3de20a9b1fa6f76c6758934953a8d615e415f1a656f0f6563694b53107d),pk(8f808f457423ff5e4e20a36d317ce9426f9da2fde875e74e15a04481b94bec06)),and_v(v:pk(f1f1db08126af105974cde6021096525ed390cf9b7cde5fedb17a0b16ed31151),pk(8f808f457423ff5e4e20a36d317ce9426f9da2fde875e74e15a04481b94bec06))},{and_v(v:and_v(v:pk(4987f3de20a9b1fa6f76c6758934953a8d615e415f1a656f0f6563694b53107d),pk(f1f1db08126af105974cde6021096525ed390cf9b7cde5fedb17a0b16ed31151)),after(2048)),and_v(v:pk(4987f3de20a9b1fa6f76c6758934953a8d615e415f1a656f0f6563694b53107d),after(12228))}})#0edq24m2").unwrap();
+		let tr_descriptor = if let Descriptor::Tr(tr) = escrow_descriptor {
+			tr
+		} else {
+			panic!("Not a taproot descriptor");
+		};
+		let locktimes: Vec<Option<u32>> = tr_descriptor
+			.iter_scripts()
+			.map(|(_, leaf)| get_leaf_locktime(leaf))
+			.collect();
+		assert_eq!(locktimes, vec![None, None, Some(2048), Some(12228)]);
+	}
+}
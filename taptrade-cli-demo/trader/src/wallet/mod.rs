@@ -1,12 +1,10 @@
 pub mod bond;
+pub mod escrow_recovery;
 pub mod musig2_utils;
 pub mod wallet_utils;
 
 use super::*;
-use crate::{
-	cli::TraderSettings,
-	communication::api::{BondRequirementResponse, OfferTakenResponse},
-};
+use crate::{cli::TraderSettings, trading::utils::ActiveOffer};
 use ::musig2::{AggNonce, KeyAggContext};
 use anyhow::{anyhow, Context, Result};
 use bdk::{
@@ -17,14 +15,16 @@ use bdk::{
 		hashes::Hash,
 		key::{KeyPair, Secp256k1, XOnlyPublicKey},
 		psbt::{raw, serialize, Input, PartiallySignedTransaction, Prevouts},
+		secp256k1::Message,
 		sighash::{SighashCache, TapSighash, TapSighashType},
-		Address, Network, TxOut,
+		taproot::{self, TapLeafHash},
+		Address, Network, OutPoint, TxOut, Txid,
 	},
-	blockchain::ElectrumBlockchain,
+	blockchain::{Blockchain, ElectrumBlockchain},
 	database::{Database, MemoryDatabase},
 	electrum_client::Client,
 	keys::{DescriptorPublicKey, ValidNetworks},
-	miniscript::{descriptor::Tr, Descriptor},
+	miniscript::{descriptor::Tr, psbt::PsbtExt, Descriptor},
 	template::{Bip86, DescriptorTemplate},
 	wallet::{AddressIndex, AddressInfo},
 	FeeRate, KeychainKind, SignOptions, SyncOptions, Wallet,
@@ -33,29 +33,62 @@ use bond::Bond;
 use cli::OfferType;
 use hex::ToHex;
 use musig2::secp::MaybeScalar;
-use musig2_utils::MuSigData;
-use serde::Serialize;
+use musig2_utils::{aggregate_musig_pubkeys, MuSigData};
 use std::{ops::Add, str::FromStr};
+use taptrade_protocol::{
+	BondRequirementResponse, EscrowInput, OfferTakenResponse, TapKeyOrigin, WitnessUtxo,
+};
 use wallet_utils::get_seed;
 
+/// upper bound of the mining fee share the coordinator may deduct from our keyspend payout output
+const MAX_PAYOUT_FEE_SHARE_SAT: u64 = 10_000;
+
 pub struct TradingWallet {
 	pub wallet: Wallet<MemoryDatabase>,
 	pub backend: ElectrumBlockchain,
 	pub taproot_pubkey: XOnlyPublicKey,
+	pub network: Network,
 }
 
-#[derive(Serialize)]
-pub struct PsbtInput {
-	pub psbt_input: Input,
-	pub utxo: bdk::bitcoin::OutPoint,
+/// converts the psbt input of one of our utxos into the EscrowInput the coordinator adds to the escrow psbt
+pub fn escrow_input(outpoint: &OutPoint, psbt_input: &Input) -> Result<EscrowInput> {
+	let witness_utxo = psbt_input
+		.witness_utxo
+		.as_ref()
+		.ok_or_else(|| anyhow!("Escrow input {} is missing the witness utxo", outpoint))?;
+	let tap_key_origins = psbt_input
+		.tap_key_origins
+		.iter()
+		.map(
+			|(xonly_pubkey, (leaf_hashes, (fingerprint, path)))| TapKeyOrigin {
+				xonly_pubkey_hex: xonly_pubkey.to_string(),
+				leaf_hashes_hex: leaf_hashes.iter().map(|hash| hash.to_string()).collect(),
+				master_fingerprint_hex: fingerprint.to_string(),
+				derivation_path: path.to_string(),
+			},
+		)
+		.collect();
+	Ok(EscrowInput {
+		outpoint: outpoint.to_string(),
+		witness_utxo: WitnessUtxo {
+			value_sat: witness_utxo.value,
+			script_pubkey_hex: witness_utxo.script_pubkey.to_hex_string(),
+		},
+		tap_internal_key_hex: psbt_input.tap_internal_key.map(|key| key.to_string()),
+		tap_key_origins,
+	})
 }
 
-pub fn get_wallet_xprv(xprv_input: Option<String>) -> Result<ExtendedPrivKey> {
+/// parses the passed xprv or generates a new one for the network. Testnet, signet and regtest share
+/// the tprv encoding, so only mainnet and test network keys can be told apart.
+pub fn get_wallet_xprv(xprv_input: Option<String>, network: Network) -> Result<ExtendedPrivKey> {
 	let xprv: ExtendedPrivKey;
-	let network: Network = Network::Regtest;
 
 	if let Some(xprv_i) = xprv_input {
 		xprv = ExtendedPrivKey::from_str(&xprv_i)?;
+		if (xprv.network == Network::Bitcoin) != (network == Network::Bitcoin) {
+			return Err(anyhow!("Xprv is not valid for {}", network));
+		}
 	} else {
 		xprv = ExtendedPrivKey::new_master(network, &get_seed())?;
 		dbg!("Generated xprv: ", xprv.to_string());
@@ -70,7 +103,7 @@ impl TradingWallet {
 		let wallet = Wallet::new(
 			Bip86(trader_config.wallet_xprv, KeychainKind::External),
 			Some(Bip86(trader_config.wallet_xprv, KeychainKind::Internal)),
-			bitcoin::Network::Regtest,
+			trader_config.network,
 			MemoryDatabase::default(), // non-permanent storage
 		)?;
 		let taproot_pubkey = trader_config
@@ -88,6 +121,7 @@ impl TradingWallet {
 			wallet,
 			backend,
 			taproot_pubkey: taproot_pubkey.0,
+			network: trader_config.network,
 		})
 	}
 
@@ -111,10 +145,10 @@ impl TradingWallet {
 		Ok((bond, musig_data, payout_address))
 	}
 
-	/// returns suitable inputs (binary encoded using bincode, hex serialized, csv formatted) and a change address for the assembly of the escrow psbt (coordinator side)
-	pub fn get_escrow_psbt_inputs(&self, amount_sat: u64) -> Result<(String, String)> {
+	/// returns suitable inputs and a change address for the assembly of the escrow psbt (coordinator side)
+	pub fn get_escrow_psbt_inputs(&self, amount_sat: u64) -> Result<(Vec<EscrowInput>, String)> {
 		let mut amount_sat = amount_sat as i64; // convert to signed int for subtraction
-		let mut inputs: Vec<String> = Vec::new();
+		let mut inputs: Vec<EscrowInput> = Vec::new();
 
 		self.wallet.sync(&self.backend, SyncOptions::default())?;
 		let available_utxos = self.wallet.list_unspent()?;
@@ -122,24 +156,19 @@ impl TradingWallet {
 		// could use more advanced coin selection if neccessary
 		for utxo in available_utxos {
 			let psbt_input: Input = self.wallet.get_psbt_input(utxo.clone(), None, false)?;
-			let input = PsbtInput {
-				psbt_input,
-				utxo: utxo.outpoint,
-			};
-			inputs.push(hex::encode(bincode::serialize(&input)?));
+			inputs.push(escrow_input(&utxo.outpoint, &psbt_input)?);
 			amount_sat -= utxo.txout.value as i64;
 			if amount_sat <= 0 {
 				break;
 			}
 		}
-		let serialized_inputs = inputs.join(",");
 
 		let change_address = self
 			.wallet
 			.get_address(AddressIndex::New)?
 			.address
 			.to_string();
-		Ok((serialized_inputs, change_address))
+		Ok((inputs, change_address))
 	}
 
 	/// signs the inputs of the passed psbt that are controlled by the bdk wallet of the trader
@@ -153,21 +182,231 @@ impl TradingWallet {
 		Ok(self)
 	}
 
-	// validate amounts, escrow output
-	pub fn validate_escrow_psbt(&self, psbt: &PartiallySignedTransaction) -> Result<&Self> {
-		warn!("IMPLEMENT MAKER PSBT VALIDATION for production use!");
-		// validate: change output address, amounts, fee
-		// tbd
+	/// validates the escrow locking psbt returned by the coordinator before we sign our inputs:
+	/// escrow output amount and address, our keys in the escrow descriptor, our change output and our fee share
+	pub fn validate_escrow_psbt(
+		&self,
+		psbt: &PartiallySignedTransaction,
+		escrow_requirements: &OfferTakenResponse,
+		musig_data: &MuSigData,
+		escrow_change_address: &str,
+	) -> Result<&Self> {
+		check_escrow_psbt(
+			&self.wallet,
+			&self.taproot_pubkey,
+			self.network,
+			psbt,
+			escrow_requirements,
+			musig_data,
+			escrow_change_address,
+		)?;
 		Ok(self)
 	}
 
-	pub fn validate_payout_psbt(&self, psbt: &PartiallySignedTransaction) -> Result<&Self> {
-		warn!("IMPLEMENT PAYOUT PSBT VALIDATION for production use!");
-		// validate: change output address, amounts, fee
-		// tbd
+	/// validates the keyspend payout psbt before we create our partial signature: it has to spend only the
+	/// escrow output of our trade, pay our payout address the agreed amount (minus a bounded fee share), contain
+	/// no unexpected outputs and the aggregated musig key has to be the tweaked escrow output key
+	pub fn validate_payout_psbt(
+		&self,
+		psbt: &PartiallySignedTransaction,
+		agg_pubk_ctx: &KeyAggContext,
+		offer: &ActiveOffer,
+	) -> Result<&Self> {
+		let escrow_requirements = offer
+			.escrow_requirements
+			.as_ref()
+			.ok_or(anyhow!("No escrow requirements stored for this offer"))?;
+		let escrow_psbt = offer
+			.escrow_psbt
+			.as_ref()
+			.ok_or(anyhow!("No escrow psbt stored for this offer"))?;
+		let escrow_descriptor =
+			Descriptor::<XOnlyPublicKey>::from_str(&escrow_requirements.escrow_output_descriptor)
+				.context("Error parsing escrow output descriptor")?;
+		let tr_descriptor = if let Descriptor::Tr(tr) = &escrow_descriptor {
+			tr
+		} else {
+			return Err(anyhow!(
+				"Escrow output descriptor is not a taproot descriptor"
+			));
+		};
+
+		// the single input has to be the escrow output of our escrow locking transaction
+		let (escrow_outpoint, escrow_output) = find_escrow_output(escrow_psbt, &escrow_descriptor)?;
+		check_spends_only_escrow_output(psbt, &escrow_outpoint, &escrow_output)?;
+
+		// the aggregated musig key we sign for has to be the (tweaked) escrow output key and contain our key
+		let agg_pubkey: ::musig2::secp256k1::PublicKey = agg_pubk_ctx.aggregated_pubkey();
+		if agg_pubkey.x_only_public_key().0.serialize()
+			!= tr_descriptor
+				.spend_info()
+				.output_key()
+				.to_inner()
+				.serialize()
+		{
+			return Err(anyhow!(
+				"Aggregated musig key does not match the escrow output key"
+			));
+		}
+		let own_musig_pubkey = offer.used_musig_config.public_key.serialize();
+		if !agg_pubk_ctx
+			.pubkeys()
+			.iter()
+			.any(|pubkey| pubkey.serialize() == own_musig_pubkey)
+		{
+			return Err(anyhow!(
+				"Our musig pubkey is not part of the aggregated musig key"
+			));
+		}
+
+		// one output pays us, the other one the counterparty, each receiving the escrow amount of the other party
+		// (the seller gets the bond back, the buyer gets the trade amount and the bond) minus an equal fee share
+		let is_maker =
+			hex::encode(own_musig_pubkey) == escrow_requirements.musig_pubkey_compressed_hex_maker;
+		let (expected_own_payout, expected_counterparty_payout) = if is_maker {
+			(
+				escrow_requirements.escrow_amount_taker_sat,
+				escrow_requirements.escrow_amount_maker_sat,
+			)
+		} else {
+			(
+				escrow_requirements.escrow_amount_maker_sat,
+				escrow_requirements.escrow_amount_taker_sat,
+			)
+		};
+		if psbt.unsigned_tx.output.len() != 2 {
+			return Err(anyhow!(
+				"Payout psbt has {} outputs, expected 2",
+				psbt.unsigned_tx.output.len()
+			));
+		}
+		let payout_script_pubkey = offer.expected_payout_address.address.script_pubkey();
+		let (own_output, counterparty_output) = match psbt
+			.unsigned_tx
+			.output
+			.iter()
+			.partition::<Vec<&TxOut>, _>(|output| output.script_pubkey == payout_script_pubkey)
+		{
+			(own, counterparty) if own.len() == 1 && counterparty.len() == 1 => {
+				(own[0], counterparty[0])
+			}
+			_ => {
+				return Err(anyhow!(
+					"Payout psbt does not contain exactly one output to our payout address {}",
+					offer.expected_payout_address.address
+				))
+			}
+		};
+		let fee_share = expected_own_payout
+			.checked_sub(own_output.value)
+			.ok_or(anyhow!("Payout psbt pays us more than expected"))?;
+		if fee_share > MAX_PAYOUT_FEE_SHARE_SAT {
+			return Err(anyhow!(
+				"Payout fee share of {} sat exceeds the maximum of {} sat",
+				fee_share,
+				MAX_PAYOUT_FEE_SHARE_SAT
+			));
+		}
+		if expected_counterparty_payout.checked_sub(fee_share) != Some(counterparty_output.value) {
+			return Err(anyhow!(
+				"Counterparty payout output of {} sat does not match the expected amount",
+				counterparty_output.value
+			));
+		}
 		Ok(self)
 	}
 
+	/// checks that the script payout psbt returned to the escrow winner spends only the escrow output of
+	/// our trade and pays all of it (minus a bounded mining fee) to our payout address before we add our signature
+	pub fn validate_script_payout_psbt(
+		&self,
+		psbt: &PartiallySignedTransaction,
+		offer: &ActiveOffer,
+	) -> Result<&Self> {
+		let escrow_requirements = offer
+			.escrow_requirements
+			.as_ref()
+			.ok_or(anyhow!("No escrow requirements stored for this offer"))?;
+		let escrow_psbt = offer
+			.escrow_psbt
+			.as_ref()
+			.ok_or(anyhow!("No escrow psbt stored for this offer"))?;
+		let escrow_descriptor =
+			Descriptor::<XOnlyPublicKey>::from_str(&escrow_requirements.escrow_output_descriptor)
+				.context("Error parsing escrow output descriptor")?;
+		let (escrow_outpoint, escrow_output) = find_escrow_output(escrow_psbt, &escrow_descriptor)?;
+		check_script_payout_psbt(
+			psbt,
+			&escrow_outpoint,
+			&escrow_output,
+			&offer.expected_payout_address.address,
+		)?;
+		Ok(self)
+	}
+
+	/// signs the escrow tapleaf (our key + coordinator key) of the coordinator signed script payout psbt,
+	/// finalizes the transaction and broadcasts it
+	pub fn sign_and_broadcast_script_payout(
+		&self,
+		mut script_payout_psbt: PartiallySignedTransaction,
+		trader_config: &TraderSettings,
+	) -> Result<Txid> {
+		let secp = self.wallet.secp_ctx();
+		let keypair = trader_config.wallet_xprv.to_keypair(secp);
+		let escrow_input = &script_payout_psbt.inputs[0];
+
+		// the leaf to sign is the one containing our key which has already been signed by the coordinator
+		let leaf_hash = escrow_input
+			.tap_scripts
+			.values()
+			.filter(|(script, _)| {
+				script
+					.as_bytes()
+					.windows(32)
+					.any(|window| window == self.taproot_pubkey.serialize())
+			})
+			.map(|(script, leaf_version)| TapLeafHash::from_script(script, *leaf_version))
+			.find(|leaf_hash| {
+				escrow_input
+					.tap_script_sigs
+					.keys()
+					.any(|(_, signed_leaf_hash)| signed_leaf_hash == leaf_hash)
+			})
+			.ok_or(anyhow!(
+				"No coordinator signed escrow leaf containing our key found in script payout psbt"
+			))?;
+
+		let utxo = script_payout_psbt
+			.iter_funding_utxos()
+			.next()
+			.ok_or(anyhow!("No UTXO found in script payout psbt"))??
+			.clone();
+		let sighash = SighashCache::new(&script_payout_psbt.unsigned_tx)
+			.taproot_script_spend_signature_hash(
+				0,
+				&Prevouts::All(&[utxo]),
+				leaf_hash,
+				TapSighashType::Default,
+			)
+			.context("Failed to create script spend sighash")?;
+		let signature =
+			secp.sign_schnorr(&Message::from_slice(&sighash.to_byte_array())?, &keypair);
+		script_payout_psbt.inputs[0].tap_script_sigs.insert(
+			(self.taproot_pubkey, leaf_hash),
+			taproot::Signature {
+				sig: signature,
+				hash_ty: TapSighashType::Default,
+			},
+		);
+
+		script_payout_psbt
+			.finalize_mut(secp)
+			.map_err(|e| anyhow!("Finalizing script payout psbt failed: {:?}", e))?;
+		let script_payout_tx = script_payout_psbt.extract_tx();
+		self.backend.broadcast(&script_payout_tx)?;
+		Ok(script_payout_tx.txid())
+	}
+
 	/// creates a partial signature to spend the keyspend path of the escrow output
 	/// which will be returned to the coordinator for aggregation
 	pub fn create_keyspend_payout_signature(
@@ -212,3 +451,555 @@ impl TradingWallet {
 		}
 	}
 }
+
+/// checks of validate_escrow_psbt(), the wallet is only used to recognize our inputs
+fn check_escrow_psbt(
+	wallet: &Wallet<MemoryDatabase>,
+	taproot_pubkey: &XOnlyPublicKey,
+	network: Network,
+	psbt: &PartiallySignedTransaction,
+	escrow_requirements: &OfferTakenResponse,
+	musig_data: &MuSigData,
+	escrow_change_address: &str,
+) -> Result<()> {
+	let escrow_descriptor =
+		Descriptor::<XOnlyPublicKey>::from_str(&escrow_requirements.escrow_output_descriptor)
+			.context("Error parsing escrow output descriptor")?;
+
+	// the escrow output has to pay the sum of both escrow amounts to the descriptor address
+	let escrow_script_pubkey = escrow_descriptor.script_pubkey();
+	let escrow_outputs: Vec<&TxOut> = psbt
+		.unsigned_tx
+		.output
+		.iter()
+		.filter(|output| output.script_pubkey == escrow_script_pubkey)
+		.collect();
+	let expected_escrow_amount =
+		escrow_requirements.escrow_amount_maker_sat + escrow_requirements.escrow_amount_taker_sat;
+	if escrow_outputs.len() != 1 || escrow_outputs[0].value != expected_escrow_amount {
+		return Err(anyhow!(
+			"Escrow psbt does not contain exactly one escrow output of {} sat to {}",
+			expected_escrow_amount,
+			escrow_descriptor.address(network)?
+		));
+	}
+
+	// our musig key has to be aggregated into the internal (keyspend) key, this also tells us our role
+	let own_musig_pubkey_hex = hex::encode(musig_data.public_key.serialize());
+	let is_maker = if own_musig_pubkey_hex == escrow_requirements.musig_pubkey_compressed_hex_maker
+	{
+		true
+	} else if own_musig_pubkey_hex == escrow_requirements.musig_pubkey_compressed_hex_taker {
+		false
+	} else {
+		return Err(anyhow!("Our musig pubkey is not part of the escrow output"));
+	};
+	let tr_descriptor = if let Descriptor::Tr(tr) = &escrow_descriptor {
+		tr
+	} else {
+		return Err(anyhow!(
+			"Escrow output descriptor is not a taproot descriptor"
+		));
+	};
+	let expected_internal_key = aggregate_musig_pubkeys(
+		&escrow_requirements.musig_pubkey_compressed_hex_maker,
+		&escrow_requirements.musig_pubkey_compressed_hex_taker,
+	)?;
+	if *tr_descriptor.internal_key() != expected_internal_key {
+		return Err(anyhow!(
+			"Escrow output internal key is not the aggregated musig key of maker and taker"
+		));
+	}
+
+	// our taproot key has to be part of the escrow script paths
+	if !tr_descriptor
+		.iter_scripts()
+		.any(|(_, leaf)| leaf.iter_pk().any(|pk| pk == *taproot_pubkey))
+	{
+		return Err(anyhow!(
+			"Our taproot pubkey is not part of the escrow output"
+		));
+	}
+
+	// our contribution (own inputs - own change) has to be our escrow amount, our share of the coordinator
+	// fee and half of the mining fee
+	let mut own_input_sum = 0;
+	let mut total_input_sum = 0;
+	for (index, input) in psbt.inputs.iter().enumerate() {
+		let utxo = input.witness_utxo.as_ref().ok_or(anyhow!(
+			"Escrow psbt input {} is missing the witness utxo",
+			index
+		))?;
+		total_input_sum += utxo.value;
+		if wallet.is_mine(&utxo.script_pubkey)? {
+			own_input_sum += utxo.value;
+		}
+	}
+	let total_output_sum: u64 = psbt
+		.unsigned_tx
+		.output
+		.iter()
+		.map(|output| output.value)
+		.sum();
+	let mining_fee = total_input_sum
+		.checked_sub(total_output_sum)
+		.ok_or(anyhow!("Escrow psbt outputs exceed its inputs"))?;
+
+	// inputs exactly covering our contribution need no change output, change paid to a foreign
+	// address is not counted and fails the contribution check below
+	let change_script_pubkey = Address::from_str(escrow_change_address)?
+		.require_network(network)?
+		.script_pubkey();
+	let mut own_change: u64 = 0;
+	for output in psbt.unsigned_tx.output.iter() {
+		if output.script_pubkey == change_script_pubkey || wallet.is_mine(&output.script_pubkey)? {
+			own_change += output.value;
+		}
+	}
+
+	let own_escrow_amount = if is_maker {
+		escrow_requirements.escrow_amount_maker_sat
+	} else {
+		escrow_requirements.escrow_amount_taker_sat
+	};
+	let expected_contribution =
+		own_escrow_amount + escrow_requirements.escrow_fee_sat_per_participant + mining_fee / 2;
+	if own_input_sum.checked_sub(own_change) != Some(expected_contribution) {
+		return Err(anyhow!(
+			"Escrow psbt takes {} sat from our inputs, expected {} sat (escrow amount, coordinator fee and half of the mining fee)",
+			own_input_sum as i64 - own_change as i64,
+			expected_contribution
+		));
+	}
+	Ok(())
+}
+
+/// looks up the escrow output (outpoint and output) paying to the escrow descriptor in the escrow locking psbt
+fn find_escrow_output(
+	escrow_psbt: &PartiallySignedTransaction,
+	escrow_descriptor: &Descriptor<XOnlyPublicKey>,
+) -> Result<(OutPoint, TxOut)> {
+	let escrow_tx = &escrow_psbt.unsigned_tx;
+	let escrow_vout = escrow_tx
+		.output
+		.iter()
+		.position(|output| output.script_pubkey == escrow_descriptor.script_pubkey())
+		.ok_or(anyhow!("Escrow psbt contains no escrow output"))?;
+	Ok((
+		OutPoint::new(escrow_tx.txid(), escrow_vout as u32),
+		escrow_tx.output[escrow_vout].clone(),
+	))
+}
+
+/// the payout psbt has to spend the escrow output as its single input, with a matching witness utxo
+fn check_spends_only_escrow_output(
+	psbt: &PartiallySignedTransaction,
+	escrow_outpoint: &OutPoint,
+	escrow_output: &TxOut,
+) -> Result<()> {
+	if psbt.unsigned_tx.input.len() != 1
+		|| psbt.unsigned_tx.input[0].previous_output != *escrow_outpoint
+	{
+		return Err(anyhow!(
+			"Payout psbt does not spend exactly the escrow output {}",
+			escrow_outpoint
+		));
+	}
+	let escrow_utxo = psbt.inputs[0]
+		.witness_utxo
+		.as_ref()
+		.ok_or(anyhow!("Payout psbt input is missing the witness utxo"))?;
+	if escrow_utxo != escrow_output {
+		return Err(anyhow!(
+			"Payout psbt witness utxo does not match the escrow output"
+		));
+	}
+	Ok(())
+}
+
+/// the script payout psbt has to spend the escrow output and pay it to our payout address in a single output,
+/// only reduced by the mining fee (bounded by MAX_PAYOUT_FEE_SHARE_SAT)
+fn check_script_payout_psbt(
+	psbt: &PartiallySignedTransaction,
+	escrow_outpoint: &OutPoint,
+	escrow_output: &TxOut,
+	payout_address: &Address,
+) -> Result<()> {
+	check_spends_only_escrow_output(psbt, escrow_outpoint, escrow_output)?;
+	let outputs = &psbt.unsigned_tx.output;
+	if outputs.len() != 1 || outputs[0].script_pubkey != payout_address.script_pubkey() {
+		return Err(anyhow!(
+			"Script payout psbt does not contain a single output to our payout address {}",
+			payout_address
+		));
+	}
+	let mining_fee = escrow_output
+		.value
+		.checked_sub(outputs[0].value)
+		.ok_or(anyhow!(
+			"Script payout psbt output exceeds the escrow output"
+		))?;
+	if mining_fee > MAX_PAYOUT_FEE_SHARE_SAT {
+		return Err(anyhow!(
+			"Script payout mining fee of {} sat exceeds the maximum of {} sat",
+			mining_fee,
+			MAX_PAYOUT_FEE_SHARE_SAT
+		));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bdk::bitcoin::{absolute::LockTime, Sequence, Transaction, TxIn, Witness};
+
+	const TEST_TPRV: &str = "tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32";
+
+	// offline regtest wallet, only used to derive addresses and check ownership
+	fn test_wallet(tprv: &str) -> Wallet<MemoryDatabase> {
+		let xprv = ExtendedPrivKey::from_str(tprv).unwrap();
+		Wallet::new(
+			Bip86(xprv, KeychainKind::External),
+			Some(Bip86(xprv, KeychainKind::Internal)),
+			Network::Regtest,
+			MemoryDatabase::default(),
+		)
+		.unwrap()
+	}
+
+	fn new_address(wallet: &Wallet<MemoryDatabase>) -> Address {
+		wallet.get_address(AddressIndex::New).unwrap().address
+	}
+
+	// unsigned psbt spending the passed outpoints (with witness utxos) to the passed outputs
+	fn test_psbt(
+		inputs: Vec<(OutPoint, TxOut)>,
+		outputs: Vec<TxOut>,
+	) -> PartiallySignedTransaction {
+		let tx = Transaction {
+			version: 2,
+			lock_time: LockTime::ZERO,
+			input: inputs
+				.iter()
+				.map(|(outpoint, _)| TxIn {
+					previous_output: *outpoint,
+					script_sig: bitcoin::ScriptBuf::new(),
+					sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+					witness: Witness::new(),
+				})
+				.collect(),
+			output: outputs,
+		};
+		let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx).unwrap();
+		for (psbt_input, (_, utxo)) in psbt.inputs.iter_mut().zip(inputs) {
+			psbt_input.witness_utxo = Some(utxo);
+		}
+		psbt
+	}
+
+	fn txout(address: &Address, value: u64) -> TxOut {
+		TxOut {
+			value,
+			script_pubkey: address.script_pubkey(),
+		}
+	}
+
+	const COUNTERPARTY_TPRV: &str = "tprv8ZgxMBicQKsPeWHBt7a68nPnvgTnuDhUgDWC8wZCgA8GahrQ3f3uWpq7wE7Uc1dLBnCe1hhCZ886K6ND37memRDWqsA9HgSKDXtwh2Qxo6J";
+
+	struct EscrowPsbtFixture {
+		wallet: Wallet<MemoryDatabase>,
+		taproot_pubkey: XOnlyPublicKey,
+		musig_data: MuSigData,
+		requirements: OfferTakenResponse,
+		escrow_output: TxOut,
+		own_input: (OutPoint, TxOut),
+		counterparty_input: (OutPoint, TxOut),
+		own_change_address: Address,
+		foreign_address: Address,
+	}
+
+	impl EscrowPsbtFixture {
+		// we are the maker: escrow of 110k (maker) + 10k (taker) sat, 1k sat coordinator fee each and
+		// 2k sat mining fee split between the traders
+		fn new() -> Self {
+			let secp = Secp256k1::new();
+			let xprv = ExtendedPrivKey::from_str(TEST_TPRV).unwrap();
+			let counterparty_xprv = ExtendedPrivKey::from_str(COUNTERPARTY_TPRV).unwrap();
+			let wallet = test_wallet(TEST_TPRV);
+			let counterparty_wallet = test_wallet(COUNTERPARTY_TPRV);
+			let taproot_pubkey = xprv.to_keypair(&secp).x_only_public_key().0;
+			let musig_data = MuSigData::create(&xprv, &secp).unwrap();
+			let counterparty_musig_data = MuSigData::create(&counterparty_xprv, &secp).unwrap();
+			let musig_pubkey_compressed_hex_maker = hex::encode(musig_data.public_key.serialize());
+			let musig_pubkey_compressed_hex_taker =
+				hex::encode(counterparty_musig_data.public_key.serialize());
+			let internal_key = aggregate_musig_pubkeys(
+				&musig_pubkey_compressed_hex_maker,
+				&musig_pubkey_compressed_hex_taker,
+			)
+			.unwrap();
+			let counterparty_taproot_pubkey =
+				counterparty_xprv.to_keypair(&secp).x_only_public_key().0;
+			let escrow_output_descriptor = format!(
+				"tr({},and_v(v:pk({}),pk({})))",
+				internal_key, taproot_pubkey, counterparty_taproot_pubkey
+			);
+			let escrow_script_pubkey =
+				Descriptor::<XOnlyPublicKey>::from_str(&escrow_output_descriptor)
+					.unwrap()
+					.script_pubkey();
+			let foreign_address = new_address(&counterparty_wallet);
+			Self {
+				own_input: (
+					OutPoint::new(Txid::all_zeros(), 0),
+					txout(&new_address(&wallet), 200_000),
+				),
+				counterparty_input: (
+					OutPoint::new(Txid::all_zeros(), 1),
+					txout(&new_address(&counterparty_wallet), 50_000),
+				),
+				own_change_address: new_address(&wallet),
+				escrow_output: TxOut {
+					value: 120_000,
+					script_pubkey: escrow_script_pubkey,
+				},
+				requirements: OfferTakenResponse {
+					escrow_psbt_hex: String::new(),
+					escrow_output_descriptor,
+					escrow_amount_maker_sat: 110_000,
+					escrow_amount_taker_sat: 10_000,
+					escrow_fee_sat_per_participant: 1_000,
+					musig_pubkey_compressed_hex_maker,
+					musig_pubkey_compressed_hex_taker,
+				},
+				foreign_address,
+				wallet,
+				taproot_pubkey,
+				musig_data,
+			}
+		}
+
+		// outputs of the escrow psbt, without own change if our inputs exactly cover our contribution
+		fn outputs(&self, own_change: Option<TxOut>) -> Vec<TxOut> {
+			let mut outputs = vec![
+				self.escrow_output.clone(),
+				txout(&self.foreign_address, 2_000),  // coordinator fee
+				txout(&self.foreign_address, 38_000), // counterparty change
+			];
+			outputs.extend(own_change);
+			outputs
+		}
+
+		fn own_change(&self) -> Option<TxOut> {
+			Some(txout(&self.own_change_address, 88_000))
+		}
+
+		fn inputs(&self) -> Vec<(OutPoint, TxOut)> {
+			vec![self.own_input.clone(), self.counterparty_input.clone()]
+		}
+
+		fn check(
+			&self,
+			psbt: &PartiallySignedTransaction,
+			requirements: &OfferTakenResponse,
+		) -> Result<()> {
+			check_escrow_psbt(
+				&self.wallet,
+				&self.taproot_pubkey,
+				Network::Regtest,
+				psbt,
+				requirements,
+				&self.musig_data,
+				&self.own_change_address.to_string(),
+			)
+		}
+	}
+
+	#[test]
+	fn test_check_escrow_psbt() {
+		let fixture = EscrowPsbtFixture::new();
+		let valid_psbt = test_psbt(fixture.inputs(), fixture.outputs(fixture.own_change()));
+		assert!(fixture.check(&valid_psbt, &fixture.requirements).is_ok());
+
+		// our input exactly covers escrow amount, coordinator fee and half of the mining fee
+		let exact_input = (
+			fixture.own_input.0,
+			txout(&new_address(&fixture.wallet), 112_000),
+		);
+		let zero_change_psbt = test_psbt(
+			vec![exact_input, fixture.counterparty_input.clone()],
+			fixture.outputs(None),
+		);
+		assert!(fixture
+			.check(&zero_change_psbt, &fixture.requirements)
+			.is_ok());
+
+		// escrow descriptor without our taproot key, the escrow output pays to it
+		let internal_key = match Descriptor::<XOnlyPublicKey>::from_str(
+			&fixture.requirements.escrow_output_descriptor,
+		)
+		.unwrap()
+		{
+			Descriptor::Tr(tr) => *tr.internal_key(),
+			_ => unreachable!(),
+		};
+		let foreign_key = Secp256k1::new()
+			.generate_keypair(&mut rand_core::OsRng)
+			.1
+			.x_only_public_key()
+			.0;
+		let foreign_descriptor = format!("tr({},pk({}))", internal_key, foreign_key);
+		let mut foreign_escrow_outputs = fixture.outputs(fixture.own_change());
+		foreign_escrow_outputs[0].script_pubkey =
+			Descriptor::<XOnlyPublicKey>::from_str(&foreign_descriptor)
+				.unwrap()
+				.script_pubkey();
+
+		let mut foreign_input_psbt_inputs = fixture.inputs();
+		foreign_input_psbt_inputs.push((
+			OutPoint::new(Txid::all_zeros(), 2),
+			txout(&fixture.foreign_address, 20_000),
+		));
+
+		let invalid_cases: Vec<(&str, PartiallySignedTransaction, OfferTakenResponse)> = vec![
+			(
+				"escrow amount differs from the agreed amounts",
+				valid_psbt.clone(),
+				OfferTakenResponse {
+					escrow_amount_maker_sat: 120_000,
+					..fixture.requirements.clone()
+				},
+			),
+			(
+				"escrow descriptor without our taproot key",
+				test_psbt(fixture.inputs(), foreign_escrow_outputs),
+				OfferTakenResponse {
+					escrow_output_descriptor: foreign_descriptor,
+					..fixture.requirements.clone()
+				},
+			),
+			(
+				"escrow descriptor with another internal key",
+				valid_psbt.clone(),
+				OfferTakenResponse {
+					musig_pubkey_compressed_hex_taker: fixture
+						.requirements
+						.musig_pubkey_compressed_hex_maker
+						.clone(),
+					..fixture.requirements.clone()
+				},
+			),
+			(
+				"additional foreign input, its value ends up in the mining fee we pay half of",
+				test_psbt(
+					foreign_input_psbt_inputs,
+					fixture.outputs(fixture.own_change()),
+				),
+				fixture.requirements.clone(),
+			),
+			(
+				"our change goes to an unknown address",
+				test_psbt(
+					fixture.inputs(),
+					fixture.outputs(Some(txout(&fixture.foreign_address, 88_000))),
+				),
+				fixture.requirements.clone(),
+			),
+			(
+				"our change is reduced",
+				test_psbt(
+					fixture.inputs(),
+					fixture.outputs(Some(txout(&fixture.own_change_address, 80_000))),
+				),
+				fixture.requirements.clone(),
+			),
+			(
+				"no change although our inputs exceed our contribution",
+				test_psbt(fixture.inputs(), fixture.outputs(None)),
+				fixture.requirements.clone(),
+			),
+		];
+		for (case, psbt, requirements) in invalid_cases {
+			assert!(fixture.check(&psbt, &requirements).is_err(), "{}", case);
+		}
+	}
+
+	#[test]
+	fn test_check_script_payout_psbt() {
+		let wallet = test_wallet(TEST_TPRV);
+		let payout_address = new_address(&wallet);
+		let foreign_address = new_address(&test_wallet(COUNTERPARTY_TPRV));
+		let escrow_output = txout(&new_address(&wallet), 200_000);
+		let escrow_outpoint = OutPoint::new(Txid::all_zeros(), 1);
+		let escrow_input = (escrow_outpoint, escrow_output.clone());
+		let check = |psbt: &PartiallySignedTransaction| {
+			check_script_payout_psbt(psbt, &escrow_outpoint, &escrow_output, &payout_address)
+		};
+
+		let valid_psbt = test_psbt(
+			vec![escrow_input.clone()],
+			vec![txout(&payout_address, 199_000)],
+		);
+		assert!(check(&valid_psbt).is_ok());
+
+		// pays us a dust amount and the rest to someone else
+		let psbt = test_psbt(
+			vec![escrow_input.clone()],
+			vec![txout(&payout_address, 1), txout(&foreign_address, 198_999)],
+		);
+		assert!(check(&psbt).is_err());
+
+		// single output to a foreign address
+		let psbt = test_psbt(
+			vec![escrow_input.clone()],
+			vec![txout(&foreign_address, 199_000)],
+		);
+		assert!(check(&psbt).is_err());
+
+		// mining fee above the bound
+		let psbt = test_psbt(
+			vec![escrow_input.clone()],
+			vec![txout(
+				&payout_address,
+				200_000 - MAX_PAYOUT_FEE_SHARE_SAT - 1,
+			)],
+		);
+		assert!(check(&psbt).is_err());
+
+		// spends another outpoint
+		let psbt = test_psbt(
+			vec![(OutPoint::new(Txid::all_zeros(), 2), escrow_output.clone())],
+			vec![txout(&payout_address, 199_000)],
+		);
+		assert!(check(&psbt).is_err());
+
+		// additional input
+		let psbt = test_psbt(
+			vec![
+				escrow_input.clone(),
+				(OutPoint::new(Txid::all_zeros(), 3), escrow_output.clone()),
+			],
+			vec![txout(&payout_address, 199_000)],
+		);
+		assert!(check(&psbt).is_err());
+
+		// witness utxo doesn't match the escrow output
+		let psbt = test_psbt(
+			vec![(escrow_outpoint, txout(&payout_address, 200_000))],
+			vec![txout(&payout_address, 199_000)],
+		);
+		assert!(check(&psbt).is_err());
+	}
+
+	#[test]
+	fn test_get_wallet_xprv_network() {
+		let tprv = "tprv8ZgxMBicQKsPdHuCSjhQuSZP1h6ZTeiRqREYS5guGPdtL7D1uNLpnJmb2oJep99Esq1NbNZKVJBNnD2ZhuXSK7G5eFmmcx73gsoa65e2U32";
+		assert!(get_wallet_xprv(Some(tprv.to_string()), Network::Regtest).is_ok());
+		assert!(get_wallet_xprv(Some(tprv.to_string()), Network::Signet).is_ok());
+		assert!(get_wallet_xprv(Some(tprv.to_string()), Network::Bitcoin).is_err());
+
+		let generated_xprv = get_wallet_xprv(None, Network::Bitcoin).unwrap();
+		assert_eq!(generated_xprv.network, Network::Bitcoin);
+	}
+}
@@ -27,7 +27,10 @@ use bdk::{
 	keys::{DescriptorPublicKey, DescriptorSecretKey},
 	template::{Bip86, DescriptorTemplate},
 };
-use musig2::{secp256k1::SecretKey as MusigSecretKey, PubNonce, SecNonce, SecNonceBuilder};
+use musig2::{
+	secp256k1::SecretKey as MusigSecretKey, KeyAggContext, PubNonce, SecNonce, SecNonceBuilder,
+};
+use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 // https://docs.rs/musig2/latest/musig2/
@@ -97,3 +100,20 @@ impl MuSigData {
 		})
 	}
 }
+
+/// aggregates the hex encoded maker and taker musig pubkeys (in this order, like the coordinator does)
+/// into the untweaked xonly key used as internal key of the escrow output
+pub fn aggregate_musig_pubkeys(
+	maker_musig_pubkey: &str,
+	taker_musig_pubkey: &str,
+) -> Result<XOnlyPublicKey> {
+	let pubkeys: [musig2::secp256k1::PublicKey; 2] = [
+		musig2::secp256k1::PublicKey::from_str(maker_musig_pubkey)?,
+		musig2::secp256k1::PublicKey::from_str(taker_musig_pubkey)?,
+	];
+	let key_agg_ctx = KeyAggContext::new(pubkeys)?;
+	let aggregated_pubkey: musig2::secp256k1::PublicKey = key_agg_ctx.aggregated_pubkey();
+	Ok(XOnlyPublicKey::from_slice(
+		&aggregated_pubkey.x_only_public_key().0.serialize(),
+	)?)
+}